@@ -0,0 +1,92 @@
+//! Decode path of a received frame: the old copying one against the in-place
+//! one used by `ComT`.
+//!
+//! Besides the copies, the copying path keeps three `CAP` sized buffers on the
+//! stack (192 B with the `COM_BUF_SIZE` of the drone) while the in-place one
+//! keeps none, the frame stays in the receive buffer of `ComT`.
+//!
+//! Run with `cargo bench -p common --target x86_64-unknown-linux-gnu`.
+#![feature(test)]
+
+extern crate test;
+
+use common::io::{ComT, Loopback, Transport};
+use common::protocol::{DataT, SensorLogDT};
+use common::uart_com::{self, Checksum, DefaultChecksum, DefaultEncoding, Encoding};
+use fixed::types::I16F16;
+use test::{black_box, Bencher};
+
+const CAP: usize = 64;
+
+// the header of a frame (sequence number and checksum) then the payload, as
+// the frame was deserialized before it was done in place
+type CopiedFrame = (
+    u8,
+    <DefaultChecksum as Checksum>::Value,
+    heapless::Vec<u8, CAP>,
+);
+
+fn sensor_log() -> DataT {
+    DataT::SensorLog(SensorLogDT {
+        time: 73_500_000,
+        gyro_x: -1200,
+        gyro_y: 62,
+        gyro_z: 3,
+        accel_x: 16000,
+        accel_y: -60,
+        accel_z: 0,
+        roll: I16F16::from_num(0.25),
+        pitch: I16F16::from_num(-1.5),
+        yaw: I16F16::from_num(3.1),
+    })
+}
+
+// the decode path before it was done in place: the received frame is copied
+// out of the receive buffer, decoded into a second buffer, the packet is
+// deserialized into a third one and only then `DataT` is deserialized
+#[inline(never)]
+fn copying_unframe(received: &[u8]) -> DataT {
+    let frame: heapless::Vec<u8, CAP> = heapless::Vec::from_slice(received).unwrap();
+    let decoded: heapless::Vec<u8, CAP> = DefaultEncoding::decode::<CAP>(frame).unwrap();
+    let (_seq, _crc, payload): CopiedFrame = postcard::from_bytes(&decoded).unwrap();
+
+    DataT::from_payload(&payload).unwrap()
+}
+
+#[inline(never)]
+fn in_place_unframe(received: &mut [u8]) -> DataT {
+    let (_seq, payload) =
+        uart_com::unframe_in_place::<DefaultChecksum, DefaultEncoding>(received).unwrap();
+
+    DataT::from_payload(payload).unwrap()
+}
+
+#[bench]
+fn bench_copying_unframe(b: &mut Bencher) {
+    let frame: heapless::Vec<u8, CAP> = sensor_log().to_packet::<CAP>(0).unwrap();
+
+    b.iter(|| copying_unframe(black_box(&frame)));
+}
+
+#[bench]
+fn bench_in_place_unframe(b: &mut Bencher) {
+    let frame: heapless::Vec<u8, CAP> = sensor_log().to_packet::<CAP>(0).unwrap();
+    let mut received: [u8; CAP] = [0; CAP];
+
+    b.iter(|| {
+        // stands for the bytes arriving in the receive buffer
+        received[..frame.len()].copy_from_slice(&frame);
+        in_place_unframe(black_box(&mut received[..frame.len()]))
+    });
+}
+
+#[bench]
+fn bench_com_read_data(b: &mut Bencher) {
+    let frame: heapless::Vec<u8, CAP> = sensor_log().to_packet::<CAP>(0).unwrap();
+    let mut com: ComT<128, Loopback<128>> = ComT::with_transport(Loopback::new());
+
+    b.iter(|| {
+        com.transport_mut().write(&frame);
+        black_box(com.read_data::<CAP>().unwrap())
+    });
+}
@@ -0,0 +1,17 @@
+//! Prints the description of the messages exchanged with the drone as JSON,
+//! see `common::protocol::schema`. The copy in the repository is refreshed
+//! with
+//!
+//! `cargo run -p common --features schema --example export_schema --target x86_64-unknown-linux-gnu > common/protocol_schema.json`
+
+use common::protocol::schema;
+
+fn main() {
+    match schema::trace_protocol() {
+        Ok(schema) => print!("{}", schema.to_json()),
+        Err(err) => {
+            eprintln!("the messages can not be traced: {}", err);
+            std::process::exit(1);
+        }
+    }
+}
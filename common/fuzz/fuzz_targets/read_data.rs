@@ -0,0 +1,35 @@
+//! Arbitrary bytes fed to `ComT::read_data`: it must never panic and must
+//! always drain the stream.
+//!
+//! Run with `cargo fuzz run read_data` from `common/`.
+#![no_main]
+
+use common::io::{ComErr, ComT, Loopback, Transport};
+use common::uart_com::{Cobs, Encoding, Escape};
+use libfuzzer_sys::fuzz_target;
+
+fn drain<E: Encoding>(stream: &[u8]) {
+    let mut com: ComT<64, Loopback<4096>, E> = ComT::with_transport(Loopback::new());
+    if !com.transport_mut().write(stream) {
+        return;
+    }
+
+    // every read consumes bytes or ends with `Empty`
+    for _ in 0..=stream.len() + 1 {
+        match com.read_data::<64>() {
+            Ok(data) => {
+                // what passed the checksum must be a valid message again
+                assert!(data.to_packet::<512>(0).is_ok());
+            }
+            Err(ComErr::Empty) => return,
+            Err(_) => {}
+        }
+    }
+
+    panic!("read_data did not drain a stream of {} bytes", stream.len());
+}
+
+fuzz_target!(|data: &[u8]| {
+    drain::<Escape>(data);
+    drain::<Cobs>(data);
+});
@@ -0,0 +1,71 @@
+//! Valid frames corrupted and truncated by the fuzzer: a frame sent after the
+//! damage must always come through.
+//!
+//! The input is read as pairs of bytes, the first one picks a position in the
+//! stream and the second one is written there. The last byte, if left alone,
+//! is where the stream is cut.
+//!
+//! Run with `cargo fuzz run resync` from `common/`.
+#![no_main]
+
+use common::io::{ComErr, ComT, Loopback, Transport};
+use common::protocol::{DataT, MotorsDT, WarningDT};
+use common::uart_com::{Cobs, Encoding, Escape};
+use common::DroneMode;
+use libfuzzer_sys::fuzz_target;
+
+fn encoded<E: Encoding>(data: &DataT) -> Vec<u8> {
+    data.to_packet_with::<E, 64>(0).unwrap().to_vec()
+}
+
+fn check<E: Encoding>(input: &[u8]) {
+    let sent: [DataT; 4] = [
+        DataT::KeepAlive,
+        DataT::Mode(DroneMode::Manual),
+        DataT::MotorsState(MotorsDT {
+            time: 0x3C3E_5C00,
+            ae1: 0x3C3E,
+            ae2: 0x5C00,
+            ae3: 400,
+            ae4: 0,
+        }),
+        DataT::Message(heapless::String::from("<escaped> \\ bytes")),
+    ];
+    let mut stream: Vec<u8> = sent.iter().flat_map(encoded::<E>).collect();
+
+    let mut pairs = input.chunks_exact(2);
+    for pair in pairs.by_ref() {
+        let pos: usize = pair[0] as usize % stream.len();
+        stream[pos] = pair[1];
+    }
+    if let [cut] = pairs.remainder() {
+        stream.truncate(*cut as usize % (stream.len() + 1));
+    }
+
+    // COBS needs a delimiter to find where the next frame starts
+    stream.push(E::END);
+    let last: DataT = DataT::Warning(WarningDT::ControlNotNeutral);
+    stream.extend_from_slice(&encoded::<E>(&last));
+
+    let mut com: ComT<64, Loopback<4096>, E> = ComT::with_transport(Loopback::new());
+    assert!(com.transport_mut().write(&stream));
+
+    let mut received: Option<DataT> = None;
+    for _ in 0..=stream.len() + 1 {
+        match com.read_data::<64>() {
+            Ok(data) => received = Some(data),
+            Err(ComErr::Empty) => {
+                assert_eq!(received, Some(last));
+                return;
+            }
+            Err(_) => {}
+        }
+    }
+
+    panic!("read_data did not drain a stream of {} bytes", stream.len());
+}
+
+fuzz_target!(|input: &[u8]| {
+    check::<Escape>(input);
+    check::<Cobs>(input);
+});
@@ -0,0 +1,450 @@
+//! The drone end of the protocol, without the hardware: the handshake, the
+//! parameters, the telemetry rates, the time sync and the requests of the
+//! runner. `DroneState` of the drone runs it over the UART, the tests of the
+//! runner over an in-memory link.
+
+use crate::io::{ComErr, ComT, OutQueue, Priority, Transport};
+use crate::param::{self, ParamDef};
+use crate::protocol::{
+    CommandT, ControlDT, DataT, EventCodeT, EventDT, HandshakeDT, ParamId, ParamValueDT, RequestId,
+    ResponseDT, ResultT, StreamRateDT, StreamRatesDT, StreamT, TimeSyncDT, Timestamp, WarningDT,
+};
+use crate::telemetry::rates::StreamRates;
+use crate::DroneMode;
+
+/// Size of the receive buffer of the drone.
+pub const DRONE_PIPE_SIZE: usize = 128;
+/// Largest frame the drone sends.
+pub const DRONE_FRAME_CAP: usize = 64;
+/// Messages the drone queues per priority class.
+pub const DRONE_OUT_SLOTS: usize = 4;
+
+// a request of the runner, answered once the mode handled its command
+struct RequestT {
+    id: RequestId,
+    command: CommandT,
+    result: Option<ResultT>, // set once the command was executed or rejected
+}
+
+/// What the link needs from the drone it belongs to.
+pub trait DroneHost: Sized + 'static {
+    type Transport: Transport;
+
+    fn link(&mut self) -> &mut DroneLink<Self::Transport>;
+
+    /// The parameters the runner can read and change.
+    fn params(&self) -> &'static [ParamDef<Self>];
+
+    fn mode(&self) -> DroneMode;
+
+    /// Time on the drone, see `Timestamp`.
+    fn now(&self) -> Timestamp;
+
+    /// Draws the nonce of a new session, see `ComT::set_session`.
+    fn new_session(&mut self) -> u64;
+}
+
+/// Link of the drone with the runner: the pipe, the messages waiting for
+/// their share of the bandwidth and the state of the protocol.
+pub struct DroneLink<T: Transport> {
+    // communication double way pipe
+    pipe: ComT<DRONE_PIPE_SIZE, T>,
+    // messages waiting for their share of the UART bandwidth
+    out_queue: OutQueue<DRONE_OUT_SLOTS>,
+
+    handshake_ok: bool, // the runner uses the same protocol definition
+
+    // position in the parameters of the next one to list, if listing
+    param_list_pos: Option<usize>,
+
+    // request being handled, see `answer_request`
+    request: Option<RequestT>,
+
+    // rates of the telemetry streams, chosen by the runner
+    streams: StreamRates,
+}
+
+impl<T: Transport> DroneLink<T> {
+    pub fn new(
+        pipe: ComT<DRONE_PIPE_SIZE, T>,
+        out_queue: OutQueue<DRONE_OUT_SLOTS>,
+        streams: StreamRates,
+    ) -> Self {
+        Self {
+            pipe,
+            out_queue,
+            handshake_ok: false,
+            param_list_pos: None,
+            request: None,
+            streams,
+        }
+    }
+
+    /// Queues `data`, it is sent by `flush` if the bandwidth allows it.
+    /// Returns `false` if it was dropped.
+    ///
+    /// Messages of the safety class (see `Priority`) always go out before
+    /// telemetry and log data.
+    pub fn send_data(&mut self, data: DataT) -> bool {
+        // a warning sent while handling a request is the reason it is rejected
+        if let DataT::Warning(warning) = &data {
+            self.set_request_result(ResultT::Rejected(warning.clone()));
+        }
+
+        match self.out_queue.push(data) {
+            Ok(_) => true,
+            // messages that must not be lost never wait behind a full queue
+            Err(data) if Priority::of(&data) == Priority::Safety || data.is_critical() => {
+                match self.pipe.send_data::<DRONE_FRAME_CAP>(data) {
+                    Ok(_) => true,
+                    Err(err) => {
+                        Self::send_error(&mut self.pipe, err);
+                        false
+                    }
+                }
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// Sends the queued messages by priority, as many as the bandwidth of one
+    /// tick allows. The rest waits for the next tick.
+    pub fn flush(&mut self) {
+        let pipe: &mut ComT<DRONE_PIPE_SIZE, T> = &mut self.pipe;
+
+        if let Err(err) = self
+            .out_queue
+            .drain(|data| pipe.send_data::<DRONE_FRAME_CAP>(data))
+        {
+            Self::send_error(&mut self.pipe, err);
+        }
+    }
+
+    // informs the runner that a message could not be sent
+    fn send_error(pipe: &mut ComT<DRONE_PIPE_SIZE, T>, err: ComErr) {
+        let event: EventDT = EventDT::new(EventCodeT::SendFailed, [err.code(), 0]);
+
+        #[allow(unused_must_use)]
+        {
+            // if this fails, do not try again
+            pipe.send_data::<DRONE_FRAME_CAP>(DataT::Event(event));
+        }
+    }
+
+    /// Retransmits the unacknowledged critical messages, once per tick.
+    pub fn tick(&mut self) {
+        self.pipe.tick_reliable::<DRONE_FRAME_CAP>();
+    }
+
+    /// Free places of the queue for `priority`.
+    pub fn room(&self, priority: Priority) -> usize {
+        self.out_queue.room(priority)
+    }
+
+    pub fn pipe(&self) -> &ComT<DRONE_PIPE_SIZE, T> {
+        &self.pipe
+    }
+
+    pub fn pipe_mut(&mut self) -> &mut ComT<DRONE_PIPE_SIZE, T> {
+        &mut self.pipe
+    }
+
+    /// Checks if a runner built from the same protocol definition is
+    /// connected. Until then the drone must stay in safe mode.
+    pub fn is_handshake_ok(&self) -> bool {
+        self.handshake_ok
+    }
+
+    /// Checks if a message of `stream` has to be sent at this tick.
+    pub fn is_stream_due(&self, stream: StreamT, iter_count: u32) -> bool {
+        self.streams.is_due(stream, iter_count)
+    }
+
+    /// Period of `stream` in ticks, 0 when it is off.
+    pub fn stream_period(&self, stream: StreamT) -> u32 {
+        self.streams.period(stream)
+    }
+
+    /// Changes the period of `stream` in ticks, within the telemetry budget
+    /// (except the heartbeat, see `StreamRates`).
+    pub fn set_stream_period(&mut self, stream: StreamT, period: u32) {
+        self.streams.set_period(stream, period);
+    }
+
+    /// Command of the request being handled, if any.
+    pub fn request(&self) -> Option<CommandT> {
+        self.request.as_ref().map(|request| request.command)
+    }
+
+    /// Records the outcome of the request being handled, the first one
+    /// recorded is the answer.
+    pub fn set_request_result(&mut self, result: ResultT) {
+        if let Some(request) = self.request.as_mut() {
+            request.result.get_or_insert(result);
+        }
+    }
+}
+
+/// Guarantees the return of some data, even in case of error. If error
+/// returns `DataT::Empty`.
+///
+/// Handshake, parameter and time sync messages are answered here, in every mode, and
+/// never returned. The command of a request is returned as the equivalent
+/// message, the request is answered once the mode asks for the next one.
+pub fn read_data<H: DroneHost>(host: &mut H) -> DataT {
+    answer_request(host);
+
+    loop {
+        let received: Result<DataT, ComErr> = host.link().pipe.read_data::<DRONE_FRAME_CAP>();
+
+        match received {
+            Ok(DataT::Handshake(remote)) => {
+                let link: &mut DroneLink<H::Transport> = host.link();
+                link.handshake_ok = remote.is_compatible();
+                // the runner may have restarted, its sequence numbers too
+                link.pipe.reset_dedup();
+                // the frames recorded before are useless from now on
+                let nonce: u64 = host.new_session();
+                host.link().pipe.set_session(nonce);
+
+                // answer with our own so the runner can check it as well
+                host.link().send_data(DataT::Handshake(HandshakeDT {
+                    nonce,
+                    ..HandshakeDT::local()
+                }));
+            }
+            Ok(DataT::ParamGet(id)) => param_get(host, id),
+            Ok(DataT::ParamSet(param)) => {
+                param_set(host, param);
+            }
+            Ok(DataT::ParamList) => host.link().param_list_pos = Some(0),
+            Ok(DataT::StreamSet(rate)) => stream_set(host.link(), rate),
+            Ok(DataT::StreamGet) => send_stream_rates(host.link()),
+            Ok(DataT::TimeSync(request)) => {
+                let drone_time: Timestamp = host.now();
+                host.link().send_data(DataT::TimeSync(TimeSyncDT {
+                    host_time: request.host_time,
+                    drone_time,
+                }));
+            }
+            Ok(DataT::Request(request)) => {
+                host.link().request = Some(RequestT {
+                    id: request.id,
+                    command: request.command,
+                    result: None,
+                });
+
+                match request.command {
+                    CommandT::ParamSet(param) => {
+                        let result: ResultT = param_set(host, param);
+                        host.link().set_request_result(result);
+                        answer_request(host);
+                    }
+                    command => return command.to_data(),
+                }
+            }
+            Ok(data) => return data,
+            // TODO: do more error handling
+            Err(err) => {
+                match err {
+                    ComErr::Empty => {}
+                    // counted in the link statistics, answering could
+                    // flood the link
+                    ComErr::Unauthenticated => {}
+
+                    _ => {
+                        let event: EventDT =
+                            EventDT::new(EventCodeT::ReceiveFailed, [err.code(), 0]);
+
+                        #[allow(unused_must_use)]
+                        {
+                            // if this fails, do not try again
+                            host.link()
+                                .pipe
+                                .send_data::<DRONE_FRAME_CAP>(DataT::Event(event));
+                        }
+                    }
+                };
+
+                return DataT::Empty;
+            }
+        }
+    }
+}
+
+/// Answers the request being handled, if any, with its outcome in the
+/// current mode.
+pub fn answer_request<H: DroneHost>(host: &mut H) {
+    let mode: DroneMode = host.mode();
+    let link: &mut DroneLink<H::Transport> = host.link();
+
+    let request: RequestT = match link.request.take() {
+        Some(request) => request,
+        None => return,
+    };
+
+    let result: ResultT = match request.result {
+        Some(result) => result,
+        None => command_result(request.command, mode),
+    };
+
+    link.send_data(DataT::Response(ResponseDT {
+        id: request.id,
+        result,
+    }));
+}
+
+/// Informs the runner that the drone is now in `mode`. The transition is the
+/// outcome of a mode request, which is answered.
+pub fn mode_changed<H: DroneHost>(host: &mut H, mode: DroneMode) {
+    let link: &mut DroneLink<H::Transport> = host.link();
+
+    if let Some(CommandT::Mode(requested)) = link.request() {
+        let result: ResultT = if requested == mode
+            // the drone goes through panic mode to get back to safe mode
+            || (requested == DroneMode::Safe && mode == DroneMode::Panic)
+        {
+            ResultT::Ok
+        } else {
+            ResultT::InvalidInMode(mode)
+        };
+        link.set_request_result(result);
+    }
+
+    link.send_data(DataT::Mode(mode));
+    answer_request(host);
+}
+
+// outcome of a command the mode did not act on
+fn command_result(command: CommandT, mode: DroneMode) -> ResultT {
+    match command {
+        // already there, nothing to change
+        CommandT::Mode(requested) if requested == mode => ResultT::Ok,
+        // the drone goes through panic mode to get back to safe mode
+        CommandT::Mode(DroneMode::Safe) if mode == DroneMode::Panic => ResultT::Ok,
+        _ => ResultT::InvalidInMode(mode),
+    }
+}
+
+/// Where a mode change asked for in safe mode leads, or the warning it is
+/// rejected with. The sticks count as centered within `dead_margin` of the
+/// middle.
+pub fn safe_mode_transition(
+    requested: DroneMode,
+    handshake_ok: bool,
+    control: ControlDT,
+    dead_margin: u16,
+    calibrated: bool,
+) -> Result<DroneMode, WarningDT> {
+    match requested {
+        DroneMode::Panic => Ok(DroneMode::Panic),
+        // stay in safe mode until the runner proved it speaks the same
+        // protocol
+        _ if !handshake_ok => Err(WarningDT::HandshakeMissing),
+        // the last control received must be {thrust = 0 & p/r/y = 1024}
+        _ if !is_control_neutral(control, dead_margin) => Err(WarningDT::ControlNotNeutral),
+        DroneMode::YawControl | DroneMode::FullControl | DroneMode::RawMode if !calibrated => {
+            Err(WarningDT::SensorNotCalibrated)
+        }
+        mode => Ok(mode),
+    }
+}
+
+// no thrust and the sticks centered, within a dead margin
+fn is_control_neutral(ctrl: ControlDT, dead_margin: u16) -> bool {
+    let centered = |value: u16| (1024 - dead_margin..=1024 + dead_margin).contains(&value);
+
+    ctrl.lift == 0 && centered(ctrl.roll) && centered(ctrl.pitch) && centered(ctrl.yaw)
+}
+
+/// Answers with the current value of the parameter `id`.
+fn param_get<H: DroneHost>(host: &mut H, id: ParamId) {
+    match param::find(host.params(), id) {
+        Ok(param) => send_param_value(host, param),
+        Err(_) => {
+            host.link()
+                .send_data(DataT::Warning(WarningDT::ParamRejected));
+        }
+    }
+}
+
+/// Changes a parameter and answers with its value, which is the old one
+/// if the new one was rejected or the parameter can not change in this mode.
+///
+/// @return `ResultT::Ok` if the new value was applied
+fn param_set<H: DroneHost>(host: &mut H, update: ParamValueDT) -> ResultT {
+    let param: &ParamDef<H> = match param::find(host.params(), update.id) {
+        Ok(param) => param,
+        Err(_) => {
+            host.link()
+                .send_data(DataT::Warning(WarningDT::ParamRejected));
+            return ResultT::Rejected(WarningDT::ParamRejected);
+        }
+    };
+
+    // e.g. the layout of the motors does not change under the props
+    let mode: DroneMode = host.mode();
+    if param.safe_only && mode != DroneMode::Safe {
+        send_param_value(host, param);
+        return ResultT::InvalidInMode(mode);
+    }
+
+    let result: ResultT = match param.set(host, update.value) {
+        Ok(_) => ResultT::Ok,
+        Err(_) => {
+            host.link()
+                .send_data(DataT::Warning(WarningDT::ParamRejected));
+            ResultT::Rejected(WarningDT::ParamRejected)
+        }
+    };
+
+    send_param_value(host, param);
+    result
+}
+
+fn send_param_value<H: DroneHost>(host: &mut H, param: &ParamDef<H>) {
+    let value: ParamValueDT = ParamValueDT {
+        id: param.id,
+        value: param.get(host),
+    };
+    host.link().send_data(DataT::ParamValue(value));
+}
+
+/// Changes the rate of a telemetry stream and answers with the rates
+/// applied, the stream is slowed down if the telemetry budget does not
+/// allow the rate asked for. The heartbeat can not be switched off.
+fn stream_set<T: Transport>(link: &mut DroneLink<T>, rate: StreamRateDT) {
+    link.streams.set_interval_ms(rate.stream, rate.interval_ms);
+    send_stream_rates(link);
+}
+
+fn send_stream_rates<T: Transport>(link: &mut DroneLink<T>) {
+    let report: StreamRatesDT = link.streams.report();
+    link.send_data(DataT::StreamRates(report));
+}
+
+/// Sends the definition and the value of the next parameter to list, once
+/// the previous ones went out.
+pub fn param_list_if_enabled<H: DroneHost>(host: &mut H) {
+    let pos: usize = match host.link().param_list_pos {
+        Some(pos) => pos,
+        None => return,
+    };
+
+    let param: &ParamDef<H> = match host.params().get(pos) {
+        Some(param) => param,
+        None => {
+            host.link().param_list_pos = None;
+            return;
+        }
+    };
+
+    // the definition and the value go together
+    if host.link().room(Priority::Log) < 2 {
+        return;
+    }
+
+    host.link().send_data(DataT::ParamInfo(param.info()));
+    send_param_value(host, param);
+    host.link().param_list_pos = Some(pos + 1);
+}
@@ -0,0 +1,194 @@
+use crate::io::ComErr;
+use crate::protocol::DataT;
+use crate::utility::siphash::SipHasher24;
+
+pub const AUTH_KEY_SIZE: usize = 16;
+
+/// Pre-shared key of the authenticated frames.
+pub type AuthKey = [u8; AUTH_KEY_SIZE];
+
+/// Bytes appended to the payload of an authenticated frame: the counter of
+/// the sender followed by the tag.
+pub const AUTH_TRAILER_SIZE: usize = COUNTER_SIZE + TAG_SIZE;
+
+const COUNTER_SIZE: usize = 8;
+const TAG_SIZE: usize = 8;
+
+/// Session of the frames sent before the first handshake, see `AuthT`.
+pub const NO_SESSION: u64 = 0;
+
+/// Key compiled into the binaries from the `DRONE_AUTH_KEY` environment
+/// variable (32 hex digits), `None` if it was not set at build time. Both the
+/// drone and the runner have to be built with the same one.
+pub const PRESHARED_KEY: Option<AuthKey> = match option_env!("DRONE_AUTH_KEY") {
+    Some(hex) => Some(parse_key(hex)),
+    None => None,
+};
+
+/// Parses a key written as 32 hex digits, at compile time.
+pub const fn parse_key(hex: &str) -> AuthKey {
+    let hex: &[u8] = hex.as_bytes();
+    assert!(
+        hex.len() == 2 * AUTH_KEY_SIZE,
+        "an authentication key is 32 hex digits"
+    );
+
+    let mut key: AuthKey = [0; AUTH_KEY_SIZE];
+    let mut i: usize = 0;
+    while i < AUTH_KEY_SIZE {
+        key[i] = (hex_digit(hex[2 * i]) << 4) | hex_digit(hex[2 * i + 1]);
+        i += 1;
+    }
+
+    key
+}
+
+const fn hex_digit(c: u8) -> u8 {
+    match c {
+        b'0'..=b'9' => c - b'0',
+        b'a'..=b'f' => c - b'a' + 10,
+        b'A'..=b'F' => c - b'A' + 10,
+        _ => panic!("an authentication key is 32 hex digits"),
+    }
+}
+
+// tag of a frame, it covers the session, the sequence number and the counter
+// as well
+fn tag(key: &AuthKey, session: u64, seq: u8, counter: &[u8], data: &[u8]) -> [u8; TAG_SIZE] {
+    let mut hasher: SipHasher24 = SipHasher24::new(key);
+    hasher.write(&session.to_le_bytes());
+    hasher.write(&[seq]);
+    hasher.write(counter);
+    hasher.write(data);
+
+    hasher.finish().to_le_bytes()
+}
+
+/// Authentication of the frames sent in one direction. The sender appends a
+/// counter, which grows with every frame, and a MAC of the frame keyed with
+/// the pre-shared key and bound to the session. The receiver drops the frames
+/// with a wrong MAC (forged, or from another session) or with a counter it
+/// already saw in the session (replayed).
+///
+/// Every handshake starts a session: the drone issues a new nonce in its
+/// answer and both sides switch to it, the counter starts again from 0. The
+/// frames recorded in an earlier session, also before a restart, are
+/// rejected. Outside of a session (`NO_SESSION`) only a handshake is
+/// accepted, replaying one only starts yet another session.
+pub(crate) struct AuthT {
+    session: u64, // nonce of the current session
+
+    sign: Option<AuthKey>,
+    counter: u64, // of the next frame sent
+
+    verify: Option<AuthKey>,
+    last: Option<u64>, // counter of the last accepted frame
+}
+
+impl AuthT {
+    pub(crate) fn new() -> Self {
+        Self {
+            session: NO_SESSION,
+            sign: None,
+            counter: 0,
+            verify: None,
+            last: None,
+        }
+    }
+
+    pub(crate) fn sign_with(&mut self, key: AuthKey) {
+        self.sign = Some(key);
+    }
+
+    pub(crate) fn require(&mut self, key: AuthKey) {
+        self.verify = Some(key);
+        self.last = None;
+    }
+
+    pub(crate) fn set_session(&mut self, nonce: u64) {
+        self.session = nonce;
+        self.counter = 0;
+        self.last = None;
+    }
+
+    /// Appends the authentication to the payload of a frame, if enabled.
+    pub(crate) fn sign<const CAP: usize>(
+        &mut self,
+        seq: u8,
+        payload: &mut heapless::Vec<u8, CAP>,
+    ) -> Result<(), ComErr> {
+        let key: AuthKey = match self.sign {
+            Some(key) => key,
+            None => return Ok(()),
+        };
+
+        let counter: [u8; COUNTER_SIZE] = self.counter.to_le_bytes();
+        self.counter = self.counter.wrapping_add(1);
+
+        let tag: [u8; TAG_SIZE] = tag(&key, self.session, seq, &counter, payload);
+        match payload
+            .extend_from_slice(&counter)
+            .and_then(|_| payload.extend_from_slice(&tag))
+        {
+            Ok(_) => Ok(()),
+            Err(_) => Err(ComErr::ENOMEM),
+        }
+    }
+
+    /// Checks the authentication of a received payload, if required.
+    /// @return the payload without it, `None` if the frame is forged,
+    /// replayed or from another session
+    pub(crate) fn verify<'a>(&mut self, seq: u8, payload: &'a [u8]) -> Option<&'a [u8]> {
+        let key: AuthKey = match self.verify {
+            Some(key) => key,
+            None => return Some(payload),
+        };
+
+        let data_len: usize = payload.len().checked_sub(AUTH_TRAILER_SIZE)?;
+        let (data, trailer) = payload.split_at(data_len);
+        let (counter, received) = trailer.split_at(COUNTER_SIZE);
+
+        if self.session == NO_SESSION
+            || !is_tag(&tag(&key, self.session, seq, counter, data), received)
+        {
+            // a handshake starts a new session, it is the only frame accepted
+            // outside of one
+            let handshake: bool = is_tag(&tag(&key, NO_SESSION, seq, counter, data), received)
+                && matches!(DataT::from_payload(data), Ok(DataT::Handshake(_)));
+            return if handshake { Some(data) } else { None };
+        }
+
+        let mut bytes: [u8; COUNTER_SIZE] = [0; COUNTER_SIZE];
+        bytes.copy_from_slice(counter);
+        let counter: u64 = u64::from_le_bytes(bytes);
+        if matches!(self.last, Some(last) if counter <= last) {
+            return None;
+        }
+        self.last = Some(counter);
+
+        Some(data)
+    }
+}
+
+// compares all the bytes, so the time taken tells nothing about the tag
+fn is_tag(expected: &[u8; TAG_SIZE], received: &[u8]) -> bool {
+    let diff: u8 = expected
+        .iter()
+        .zip(received.iter())
+        .fold(0, |diff, (a, b)| diff | (a ^ b));
+
+    diff == 0
+}
+
+#[cfg(test)]
+mod test {
+    use crate::io::auth::*;
+
+    #[test]
+    fn test_parse_key() {
+        let key: AuthKey = parse_key("000102030405060708090a0B0c0D0e0F");
+        for (i, byte) in key.iter().enumerate() {
+            assert_eq!(*byte as usize, i);
+        }
+    }
+}
@@ -0,0 +1,234 @@
+use alloc::rc::Rc;
+use core::cell::RefCell;
+
+use crate::io::Transport;
+
+/// Faults injected into the bytes sent in one direction of a `duplex` link.
+/// The rates are per 1000 bytes, the faults are pseudo-random but the same
+/// for the same `seed`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct FaultConfig {
+    pub loss: u16,       // bytes dropped, per 1000
+    pub corruption: u16, // bytes with a flipped bit, per 1000
+    pub delay: u32,      // calls to `DuplexEnd::tick` of the receiver before a byte can be read
+    pub seed: u32,
+}
+
+impl FaultConfig {
+    /// A perfect link.
+    pub fn none() -> Self {
+        Self::default()
+    }
+}
+
+/// Bytes the faults of a direction dropped or corrupted so far.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct FaultStats {
+    pub lost: u32,
+    pub corrupted: u32,
+}
+
+// one direction of the link, the bytes with the tick they can be read at
+struct PipeT<const CAP: usize> {
+    bytes: heapless::Deque<(u32, u8), CAP>,
+    now: u32, // ticks of the receiving end
+
+    faults: FaultConfig,
+    rng: u32, // xorshift32 state, never 0
+    stats: FaultStats,
+}
+
+impl<const CAP: usize> PipeT<CAP> {
+    fn new(faults: FaultConfig) -> Self {
+        Self {
+            bytes: heapless::Deque::new(),
+            now: 0,
+            faults,
+            rng: faults.seed.max(1),
+            stats: FaultStats::default(),
+        }
+    }
+
+    // true with a chance of `per_mille` in 1000
+    fn chance(&mut self, per_mille: u16) -> bool {
+        if per_mille == 0 {
+            return false;
+        }
+
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 17;
+        self.rng ^= self.rng << 5;
+        self.rng % 1000 < per_mille as u32
+    }
+}
+
+/// One end of an in-memory duplex link, made with `duplex`. What one end
+/// writes, the other reads, after the faults of that direction. A clone is
+/// the same end, e.g. to read it apart from a `ComT` writing to it.
+#[derive(Clone)]
+pub struct DuplexEnd<const CAP: usize> {
+    rx: Rc<RefCell<PipeT<CAP>>>,
+    tx: Rc<RefCell<PipeT<CAP>>>,
+}
+
+/// Links two `ComT` endpoints in memory, e.g. a runner and a drone in a test.
+/// Each direction holds at most `CAP` bytes, writes that do not fit fail like
+/// a full TX buffer. `a_to_b` are the faults of what the first end writes.
+pub fn duplex<const CAP: usize>(
+    a_to_b: FaultConfig,
+    b_to_a: FaultConfig,
+) -> (DuplexEnd<CAP>, DuplexEnd<CAP>) {
+    let a_to_b: Rc<RefCell<PipeT<CAP>>> = Rc::new(RefCell::new(PipeT::new(a_to_b)));
+    let b_to_a: Rc<RefCell<PipeT<CAP>>> = Rc::new(RefCell::new(PipeT::new(b_to_a)));
+
+    (
+        DuplexEnd {
+            rx: b_to_a.clone(),
+            tx: a_to_b.clone(),
+        },
+        DuplexEnd {
+            rx: a_to_b,
+            tx: b_to_a,
+        },
+    )
+}
+
+impl<const CAP: usize> DuplexEnd<CAP> {
+    /// Advances the time of the bytes sent to this end, see
+    /// `FaultConfig::delay`.
+    pub fn tick(&mut self) {
+        let mut rx = self.rx.borrow_mut();
+        rx.now = rx.now.wrapping_add(1);
+    }
+
+    /// Number of bytes sent to this end, readable or still delayed.
+    pub fn pending(&self) -> usize {
+        self.rx.borrow().bytes.len()
+    }
+
+    /// Faults injected into the bytes sent by this end.
+    pub fn sent_faults(&self) -> FaultStats {
+        self.tx.borrow().stats
+    }
+}
+
+impl<const CAP: usize> Transport for DuplexEnd<CAP> {
+    fn read(&mut self, buf: &mut [u8]) -> usize {
+        let mut rx = self.rx.borrow_mut();
+        let now: u32 = rx.now;
+        let mut read: usize = 0;
+
+        for byte in buf.iter_mut() {
+            match rx.bytes.front() {
+                Some((due, _)) if *due <= now => {}
+                _ => break,
+            }
+
+            // checked above
+            if let Some((_, val)) = rx.bytes.pop_front() {
+                *byte = val;
+            }
+            read += 1;
+        }
+
+        read
+    }
+
+    fn write(&mut self, data: &[u8]) -> bool {
+        let mut tx = self.tx.borrow_mut();
+        if CAP - tx.bytes.len() < data.len() {
+            return false;
+        }
+
+        let due: u32 = tx.now.wrapping_add(tx.faults.delay);
+        for byte in data {
+            let (loss, corruption) = (tx.faults.loss, tx.faults.corruption);
+
+            if tx.chance(loss) {
+                tx.stats.lost += 1;
+                continue;
+            }
+
+            let mut byte: u8 = *byte;
+            if tx.chance(corruption) {
+                byte ^= 1 << (tx.rng % 8);
+                tx.stats.corrupted += 1;
+            }
+
+            // there is enough space, checked above
+            let _ = tx.bytes.push_back((due, byte));
+        }
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::io::duplex::*;
+
+    #[test]
+    fn test_both_ways() {
+        let (mut a, mut b) = duplex::<16>(FaultConfig::none(), FaultConfig::none());
+
+        assert!(a.write(&[1, 2, 3]));
+        assert!(b.write(&[4]));
+        assert!(!a.write(&[0; 14]));
+
+        let mut buf: [u8; 8] = [0; 8];
+        assert_eq!(b.read(&mut buf), 3);
+        assert_eq!(buf[..3], [1, 2, 3]);
+        assert_eq!(a.read(&mut buf), 1);
+        assert_eq!(buf[0], 4);
+        assert_eq!(a.read(&mut buf), 0);
+    }
+
+    #[test]
+    fn test_delay() {
+        let delayed: FaultConfig = FaultConfig {
+            delay: 2,
+            ..FaultConfig::none()
+        };
+        let (mut a, mut b) = duplex::<16>(delayed, FaultConfig::none());
+        let mut buf: [u8; 8] = [0; 8];
+
+        assert!(a.write(&[1, 2]));
+        b.tick();
+        assert!(a.write(&[3]));
+        assert_eq!(b.read(&mut buf), 0);
+        assert_eq!(b.pending(), 3);
+
+        b.tick();
+        assert_eq!(b.read(&mut buf), 2);
+        b.tick();
+        assert_eq!(b.read(&mut buf), 1);
+        assert_eq!(buf[0], 3);
+    }
+
+    #[test]
+    fn test_loss_and_corruption() {
+        let faulty: FaultConfig = FaultConfig {
+            loss: 100,
+            corruption: 100,
+            delay: 0,
+            seed: 7,
+        };
+        let (mut a, mut b) = duplex::<1024>(faulty, FaultConfig::none());
+        assert!(a.write(&[0x55; 1000]));
+
+        let stats: FaultStats = a.sent_faults();
+        assert!(stats.lost > 50 && stats.lost < 150, "{:?}", stats);
+        assert!(stats.corrupted > 50 && stats.corrupted < 150, "{:?}", stats);
+
+        let mut buf: [u8; 1024] = [0; 1024];
+        let read: usize = b.read(&mut buf);
+        assert_eq!(read as u32, 1000 - stats.lost);
+        let changed: usize = buf[..read].iter().filter(|byte| **byte != 0x55).count();
+        assert_eq!(changed as u32, stats.corrupted);
+
+        // the same seed gives the same faults
+        let (mut c, _d) = duplex::<1024>(faulty, FaultConfig::none());
+        assert!(c.write(&[0x55; 1000]));
+        assert_eq!(c.sent_faults(), stats);
+    }
+}
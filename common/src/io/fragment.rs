@@ -0,0 +1,263 @@
+use crate::protocol::{FragmentDT, FRAGMENT_SIZE};
+
+/// Most fragments a payload can be split into.
+pub const MAX_FRAGMENTS: usize = 32;
+
+/// Largest payload that can be sent in fragments.
+pub const MAX_FRAGMENTED_SIZE: usize = MAX_FRAGMENTS * FRAGMENT_SIZE;
+
+/// Number of fragments `len` bytes are split into.
+pub(crate) fn fragment_count(len: usize) -> usize {
+    // an empty payload is still sent as one empty fragment
+    len.div_ceil(FRAGMENT_SIZE).max(1)
+}
+
+// a payload being reassembled
+struct SlotT<const SIZE: usize> {
+    id: u8,
+    count: u8,
+    received: u32, // bit i set when fragment i arrived
+    len: usize,    // known once the last fragment arrived
+    age: u32,      // ticks since the last fragment
+    data: [u8; SIZE],
+}
+
+impl<const SIZE: usize> SlotT<SIZE> {
+    fn is_complete(&self) -> bool {
+        self.received.count_ones() == self.count as u32
+    }
+}
+
+/// Puts back together the payloads sent with `ComT::send_large`.
+///
+/// Memory is bounded: at most `SLOTS` payloads of up to `SIZE` bytes are
+/// reassembled at the same time. When a fragment of a new payload arrives and
+/// all the slots are taken, the oldest payload is dropped. A payload that
+/// gets no fragment for `timeout_ticks` calls to `tick` is dropped too.
+pub struct Reassembler<const SIZE: usize, const SLOTS: usize> {
+    slots: [Option<SlotT<SIZE>>; SLOTS],
+    timeout_ticks: u32,
+
+    dropped: u32,
+}
+
+impl<const SIZE: usize, const SLOTS: usize> Reassembler<SIZE, SLOTS> {
+    pub fn new(timeout_ticks: u32) -> Self {
+        Self {
+            slots: core::array::from_fn(|_| None),
+            timeout_ticks,
+            dropped: 0,
+        }
+    }
+
+    /// Number of payloads (and invalid fragments) dropped so far.
+    pub fn dropped(&self) -> u32 {
+        self.dropped
+    }
+
+    /// Adds a fragment, returns the payload once all its fragments arrived.
+    /// Invalid fragments are dropped.
+    pub fn push(&mut self, fragment: FragmentDT) -> Option<heapless::Vec<u8, SIZE>> {
+        let index: usize = fragment.index as usize;
+        let count: usize = fragment.count as usize;
+        let is_last: bool = index + 1 == count;
+        let offset: usize = index * FRAGMENT_SIZE;
+
+        if count == 0
+            || count > MAX_FRAGMENTS
+            || index >= count
+            || (!is_last && fragment.data.len() != FRAGMENT_SIZE)
+            || offset + fragment.data.len() > SIZE
+        {
+            self.dropped += 1;
+            return None;
+        }
+
+        let pos: usize = self.find_slot(fragment.id, fragment.count)?;
+        let slot: &mut SlotT<SIZE> = self.slots[pos].as_mut()?;
+
+        slot.age = 0;
+        slot.received |= 1 << index;
+        slot.data[offset..offset + fragment.data.len()].copy_from_slice(&fragment.data);
+        if is_last {
+            slot.len = offset + fragment.data.len();
+        }
+
+        if !slot.is_complete() {
+            return None;
+        }
+
+        let payload: heapless::Vec<u8, SIZE> =
+            heapless::Vec::from_slice(&slot.data[..slot.len]).ok()?;
+        self.slots[pos] = None;
+
+        Some(payload)
+    }
+
+    /// Ages the payloads being reassembled, it should be called once per
+    /// tick. Returns how many were dropped because of the timeout.
+    pub fn tick(&mut self) -> usize {
+        let mut timed_out: usize = 0;
+
+        for slot in self.slots.iter_mut() {
+            let expired: bool = match slot {
+                Some(s) => {
+                    s.age += 1;
+                    s.age > self.timeout_ticks
+                }
+                None => false,
+            };
+
+            if expired {
+                *slot = None;
+                timed_out += 1;
+            }
+        }
+
+        self.dropped += timed_out as u32;
+        timed_out
+    }
+
+    // slot of the payload `id`, a new one is taken if it is not known yet
+    fn find_slot(&mut self, id: u8, count: u8) -> Option<usize> {
+        if let Some(pos) = self
+            .slots
+            .iter()
+            .position(|s| matches!(s, Some(s) if s.id == id))
+        {
+            if self.slots[pos].as_ref()?.count == count {
+                return Some(pos);
+            }
+
+            // the id was reused for another payload, the old one is lost
+            self.slots[pos] = None;
+            self.dropped += 1;
+        }
+
+        let pos: usize = match self.slots.iter().position(|s| s.is_none()) {
+            Some(pos) => pos,
+            None => {
+                let oldest: usize = self
+                    .slots
+                    .iter()
+                    .enumerate()
+                    .max_by_key(|(_, s)| s.as_ref().map_or(0, |s| s.age))
+                    .map(|(pos, _)| pos)?;
+                self.dropped += 1;
+                oldest
+            }
+        };
+
+        self.slots[pos] = Some(SlotT {
+            id,
+            count,
+            received: 0,
+            len: 0,
+            age: 0,
+            data: [0; SIZE],
+        });
+
+        Some(pos)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::io::fragment::*;
+
+    fn fragments(id: u8, payload: &[u8]) -> heapless::Vec<FragmentDT, MAX_FRAGMENTS> {
+        let count: usize = fragment_count(payload.len());
+        let mut ret: heapless::Vec<FragmentDT, MAX_FRAGMENTS> = heapless::Vec::new();
+
+        for index in 0..count {
+            let end: usize = payload.len().min((index + 1) * FRAGMENT_SIZE);
+            ret.push(FragmentDT {
+                id,
+                index: index as u8,
+                count: count as u8,
+                data: heapless::Vec::from_slice(&payload[index * FRAGMENT_SIZE..end]).unwrap(),
+            })
+            .unwrap();
+        }
+
+        ret
+    }
+
+    fn payload() -> [u8; 50] {
+        let mut payload: [u8; 50] = [0; 50];
+        for (i, b) in payload.iter_mut().enumerate() {
+            *b = i as u8;
+        }
+        payload
+    }
+
+    #[test]
+    fn test_out_of_order_and_duplicates() {
+        let mut re: Reassembler<64, 2> = Reassembler::new(10);
+        let mut frags = fragments(3, &payload());
+        assert_eq!(frags.len(), 3);
+
+        frags.swap(0, 2);
+        assert_eq!(re.push(frags[0].clone()), None);
+        assert_eq!(re.push(frags[0].clone()), None);
+        assert_eq!(re.push(frags[1].clone()), None);
+        assert_eq!(&re.push(frags[2].clone()).unwrap()[..], &payload()[..]);
+        assert_eq!(re.dropped(), 0);
+    }
+
+    #[test]
+    fn test_interleaved_payloads() {
+        let mut re: Reassembler<64, 2> = Reassembler::new(10);
+        let a = fragments(1, &payload());
+        let b = fragments(2, &payload()[..30]);
+
+        assert_eq!(re.push(a[0].clone()), None);
+        assert_eq!(re.push(b[0].clone()), None);
+        assert_eq!(re.push(a[1].clone()), None);
+        assert_eq!(&re.push(b[1].clone()).unwrap()[..], &payload()[..30]);
+        assert_eq!(&re.push(a[2].clone()).unwrap()[..], &payload()[..]);
+    }
+
+    #[test]
+    fn test_timeout() {
+        let mut re: Reassembler<64, 2> = Reassembler::new(2);
+        let frags = fragments(1, &payload());
+
+        re.push(frags[0].clone());
+        re.tick();
+        re.tick();
+        assert_eq!(re.tick(), 1);
+        assert_eq!(re.dropped(), 1);
+
+        // the rest alone is not enough anymore
+        assert_eq!(re.push(frags[1].clone()), None);
+        assert_eq!(re.push(frags[2].clone()), None);
+    }
+
+    #[test]
+    fn test_memory_is_bounded() {
+        let mut re: Reassembler<64, 1> = Reassembler::new(10);
+        let a = fragments(1, &payload());
+        let b = fragments(2, &payload()[..30]);
+
+        re.push(a[0].clone());
+        // no free slot, the first payload is given up
+        re.push(b[0].clone());
+        assert_eq!(re.dropped(), 1);
+        assert_eq!(&re.push(b[1].clone()).unwrap()[..], &payload()[..30]);
+
+        // does not fit in the slot
+        let big = fragments(4, &[1; 100]);
+        assert_eq!(re.push(big[3].clone()), None);
+        assert_eq!(re.dropped(), 2);
+    }
+
+    #[test]
+    fn test_empty_payload() {
+        let mut re: Reassembler<64, 1> = Reassembler::new(10);
+        let frags = fragments(1, &[]);
+
+        assert_eq!(frags.len(), 1);
+        assert_eq!(&re.push(frags[0].clone()).unwrap()[..], &[] as &[u8]);
+    }
+}
@@ -1,43 +1,282 @@
-use ringbuffer::{
-    ConstGenericRingBuffer, RingBuffer, RingBufferExt, RingBufferRead, RingBufferWrite,
+use core::marker::PhantomData;
+
+use crate::protocol::{
+    AckNackDT, BatchDT, DataT, FragmentDT, LargeDataT, LinkStatsDT, FRAGMENT_SIZE,
+    LARGE_SERIALIZED_CAP,
 };
+use crate::uart_com::{self, DefaultChecksum, DefaultEncoding, Encoding};
 
-use crate::protocol::DataT;
-use crate::uart_com;
+mod auth;
+mod duplex;
+mod fragment;
+mod queue;
+mod reliable;
+mod requests;
+mod transport;
 
-const BUFFER_SIZE: usize = 8;
+use auth::AuthT;
+pub use auth::{parse_key, AuthKey, AUTH_KEY_SIZE, AUTH_TRAILER_SIZE, NO_SESSION, PRESHARED_KEY};
+pub use duplex::{duplex, DuplexEnd, FaultConfig, FaultStats};
+pub use fragment::{Reassembler, MAX_FRAGMENTED_SIZE, MAX_FRAGMENTS};
+pub use queue::{bytes_per_tick, OutQueue, Priority};
+use reliable::ReliableT;
+pub use reliable::{ReliableConfig, RELIABLE_SLOTS};
+pub use requests::{Outcome, Requests};
+pub use transport::{FnTransport, Loopback, Transport};
 
 #[derive(Debug)]
 pub enum ComErr {
-    Empty,       // no hole packet to be extracted
-    InvalPacket, // found invalid pair START_BYTE - END_BYTE (maybe corruption)
-    ENOMEM,      // passed capacity is too small
-    Busy,        // send_f returned false and could not send data
+    Empty,           // no hole packet to be extracted
+    InvalPacket,     // found an invalid or unterminated frame (maybe corruption)
+    ENOMEM,          // passed capacity is too small
+    Busy,            // the transport could not take the data, nothing was sent
+    WindowFull,      // too many critical messages are waiting for an ACK
+    Unauthenticated, // the frame is forged or replayed (see `ComT::require_auth`)
 
     UartError(uart_com::Error),
 
     Bug, // something wrong happened
 }
 
+impl ComErr {
+    /// Number of the error, as reported in `EventDT`.
+    pub fn code(&self) -> u32 {
+        match self {
+            ComErr::Empty => 0,
+            ComErr::InvalPacket => 1,
+            ComErr::ENOMEM => 2,
+            ComErr::Busy => 3,
+            ComErr::WindowFull => 4,
+            ComErr::Unauthenticated => 5,
+            ComErr::UartError(uart_com::Error::CRC) => 16,
+            ComErr::UartError(uart_com::Error::Des) => 17,
+            ComErr::UartError(uart_com::Error::ENOMEM) => 18,
+            ComErr::UartError(uart_com::Error::Frame) => 19,
+            ComErr::UartError(uart_com::Error::LibBug) => 20,
+            ComErr::Bug => 255,
+        }
+    }
+
+    /// Name of the error with the number `code`, see `code`.
+    pub fn name_of(code: u32) -> &'static str {
+        match code {
+            0 => "nothing to read",
+            1 => "invalid frame",
+            2 => "out of memory",
+            3 => "transport busy",
+            4 => "reliable window full",
+            5 => "unauthenticated frame",
+            16 => "checksum mismatch",
+            17 => "deserialization failed",
+            18 => "frame too large",
+            19 => "bad frame encoding",
+            20 => "framing bug",
+            255 => "bug",
+            _ => "unknown error",
+        }
+    }
+}
+
 /// Structure to reuse the code in both dronecode and runner.
 ///
-/// `BUF_CAP` is the underlying size of the receiving buffer, `read_f` is the
-/// funciton used to populate the buffer, `send_f` is the function used to send
-/// serialized data.
-pub struct ComT<const BUF_CAP: usize> {
-    recv_buffer: ConstGenericRingBuffer<u8, BUF_CAP>,
-
-    read_f: fn(&mut [u8]) -> usize,
-    send_f: fn(&[u8]) -> bool,
+/// `BUF_CAP` is the underlying size of the receiving buffer, `T` is the
+/// transport used to populate the buffer and to send serialized data (see
+/// `FnTransport` for plain read/send functions). Frames are encoded with `E` (see `uart_com::Encoding`).
+///
+/// Critical messages (see `DataT::is_critical`) can be sent over a reliable
+/// channel enabled with `enable_reliable`. They are then sent with a sequence
+/// number, retransmitted from `tick_reliable` until the other side ACKs them
+/// and the receiver drops the duplicates. They are delivered in order: only
+/// the oldest one is in flight, the others wait for its ACK. The duplicates
+/// are only recognized until the next handshake, see `reset_dedup`.
+/// Everything else stays fire-and-forget.
+///
+/// Frames can be authenticated with a pre-shared key, the sender enables
+/// it with `sign_with` and the receiver with `require_auth`. The receiver
+/// then drops (and counts) the frames that are forged or replayed, both bind
+/// them to the session of the last handshake with `set_session`.
+///
+/// The health of the link is counted in `link_stats`.
+///
+/// Payloads too large for a frame are split with `send_large` and put back
+/// together on the other side with a `Reassembler`. Small messages can share a
+/// frame in a `DataT::Batch`, it is unpacked by `read_data`.
+pub struct ComT<const BUF_CAP: usize, T: Transport = FnTransport, E: Encoding = DefaultEncoding> {
+    // received bytes, frames are decoded where they were received
+    recv_buffer: [u8; BUF_CAP],
+    recv_len: usize,
+
+    transport: T,
+
+    reliable: ReliableT,
+
+    auth: AuthT,
+
+    stats: LinkStatsDT,
+
+    next_fragment_id: u8,
+
+    // received batch still being returned by `read_data`
+    batch: BatchDT,
+    batch_pos: usize,
+
+    encoding: PhantomData<E>,
 }
 
-impl<const BUF_CAP: usize> ComT<BUF_CAP> {
+impl<const BUF_CAP: usize, E: Encoding> ComT<BUF_CAP, FnTransport, E> {
+    /// `read_f` is the function used to populate the buffer, `send_f` is the
+    /// function used to send serialized data.
     pub fn new(read_f: fn(&mut [u8]) -> usize, send_f: fn(&[u8]) -> bool) -> Self {
+        Self::with_transport(FnTransport::new(read_f, send_f))
+    }
+}
+
+impl<const BUF_CAP: usize, T: Transport, E: Encoding> ComT<BUF_CAP, T, E> {
+    pub fn with_transport(transport: T) -> Self {
         Self {
-            recv_buffer: ConstGenericRingBuffer::default(),
-            read_f,
-            send_f,
+            recv_buffer: [0; BUF_CAP],
+            recv_len: 0,
+            transport,
+            reliable: ReliableT::new(),
+            auth: AuthT::new(),
+            stats: LinkStatsDT::default(),
+            next_fragment_id: 0,
+            batch: BatchDT::new(),
+            batch_pos: 0,
+            encoding: PhantomData,
+        }
+    }
+
+    /// Sends the critical messages over the reliable channel from now on.
+    pub fn enable_reliable(&mut self, config: ReliableConfig) {
+        self.reliable.enable(config);
+    }
+
+    /// Forgets the sequence numbers received so far, it has to be called on
+    /// every handshake. A restarted peer counts from the start again, its
+    /// frames would otherwise be dropped as duplicates.
+    pub fn reset_dedup(&mut self) {
+        self.reliable.reset_dedup();
+    }
+
+    /// Authenticates every frame sent from now on with `key`, see
+    /// `set_session`.
+    pub fn sign_with(&mut self, key: AuthKey) {
+        self.auth.sign_with(key);
+    }
+
+    /// Drops every frame received from now on that is not authenticated with
+    /// `key`, that was already received or that belongs to another session.
+    pub fn require_auth(&mut self, key: AuthKey) {
+        self.auth.require(key);
+    }
+
+    /// Binds the authenticated frames to the session `nonce`, the one the
+    /// drone issued in its last handshake. Until the first session only
+    /// handshakes are accepted, the frames of an earlier session are rejected
+    /// from now on.
+    pub fn set_session(&mut self, nonce: u64) {
+        self.auth.set_session(nonce);
+    }
+
+    /// Advances the retransmission timers of the reliable channel, it should
+    /// be called once per tick. `CAP` is used for internal buffers.
+    ///
+    /// Returns how many critical messages were given up on during this tick.
+    pub fn tick_reliable<const CAP: usize>(&mut self) -> usize {
+        let transport: &mut T = &mut self.transport;
+        let auth: &mut AuthT = &mut self.auth;
+        let stats: &mut LinkStatsDT = &mut self.stats;
+
+        self.reliable.tick(|seq, data| {
+            // a failed retransmission is retried on the next timeout
+            let _ = Self::send_frame::<CAP>(transport, auth, stats, data, seq);
+        })
+    }
+
+    pub fn transport(&self) -> &T {
+        &self.transport
+    }
+
+    pub fn transport_mut(&mut self) -> &mut T {
+        &mut self.transport
+    }
+
+    /// Pushes out the bytes still buffered by the transport.
+    pub fn flush(&mut self) {
+        self.transport.flush();
+    }
+
+    /// Number of critical messages still waiting for an ACK.
+    pub fn reliable_pending(&self) -> usize {
+        self.reliable.pending()
+    }
+
+    /// Total number of critical messages that were never acknowledged.
+    pub fn reliable_lost(&self) -> u32 {
+        self.reliable.lost()
+    }
+
+    /// Counters of everything that happened on the link since the creation
+    /// of the `ComT` (or the last `reset_link_stats`).
+    pub fn link_stats(&self) -> LinkStatsDT {
+        self.stats
+    }
+
+    pub fn reset_link_stats(&mut self) {
+        self.stats = LinkStatsDT::default();
+    }
+
+    /// Splits `payload` in `DataT::Fragment`s and sends them fire-and-forget.
+    /// `CAP` is used for internal buffers.
+    ///
+    /// Returns `ComErr::ENOMEM` if `payload` is larger than
+    /// `MAX_FRAGMENTED_SIZE`. If a fragment can not be sent, the rest are not
+    /// sent either and the error is returned. The other side then drops the
+    /// incomplete payload after its timeout.
+    pub fn send_large<const CAP: usize>(&mut self, payload: &[u8]) -> Result<(), ComErr> {
+        if payload.len() > MAX_FRAGMENTED_SIZE {
+            return Err(ComErr::ENOMEM);
+        }
+
+        let id: u8 = self.next_fragment_id;
+        self.next_fragment_id = self.next_fragment_id.wrapping_add(1);
+
+        let count: usize = fragment::fragment_count(payload.len());
+
+        for index in 0..count {
+            let start: usize = index * FRAGMENT_SIZE;
+            let end: usize = payload.len().min(start + FRAGMENT_SIZE);
+
+            let data: heapless::Vec<u8, FRAGMENT_SIZE> =
+                match heapless::Vec::from_slice(&payload[start..end]) {
+                    Ok(val) => val,
+                    Err(_) => {
+                        return Err(ComErr::Bug);
+                    }
+                };
+
+            self.send_data::<CAP>(DataT::Fragment(FragmentDT {
+                id,
+                index: index as u8,
+                count: count as u8,
+                data,
+            }))?;
         }
+
+        Ok(())
+    }
+
+    /// Serializes `data` and sends it with `send_large`.
+    pub fn send_large_data<const CAP: usize>(&mut self, data: &LargeDataT) -> Result<(), ComErr> {
+        let serialized: heapless::Vec<u8, LARGE_SERIALIZED_CAP> = match data.to_bytes() {
+            Ok(val) => val,
+            Err(err) => {
+                return Err(ComErr::UartError(err));
+            }
+        };
+
+        self.send_large::<CAP>(&serialized)
     }
 
     /// Serializes data and sends it over the UART. `CAP` is used for internal
@@ -46,18 +285,86 @@ impl<const BUF_CAP: usize> ComT<BUF_CAP> {
     /// Returns `ComErr::ENOMEM` or `ComErr::UartError::ENOMEM` depending on where
     /// `CAP` became too small.
     ///
-    /// Returns `ComErr::Busy` when `send_f` returned `false`.
+    /// Returns `ComErr::Busy` when the transport could not take the frame. Critical
+    /// messages on the reliable channel do not return `Busy`, they are
+    /// retransmitted later instead. If too many of them wait for an ACK,
+    /// `ComErr::WindowFull` is returned.
     ///
     /// Returns also other errors from `uart_com::Error`.
-    pub fn send_data<const CAP: usize>(&mut self, data: DataT) -> Result<(), ComErr> {
-        match data.to_packet::<CAP>() {
+    ///
+    /// On success, returns the number of bytes handed to the transport (0 when
+    /// a critical message is left for a retransmission or waits for the ACK
+    /// of an older one).
+    pub fn send_data<const CAP: usize>(&mut self, data: DataT) -> Result<usize, ComErr> {
+        if !self.reliable.is_enabled() || !data.is_critical() {
+            return Self::send_frame::<CAP>(
+                &mut self.transport,
+                &mut self.auth,
+                &mut self.stats,
+                &data,
+                uart_com::NO_SEQ,
+            );
+        }
+
+        self.reliable.track(data)?;
+
+        self.send_queued::<CAP>()
+    }
+
+    // sends the oldest critical message if it is not in flight yet
+    #[inline]
+    fn send_queued<const CAP: usize>(&mut self) -> Result<usize, ComErr> {
+        let (seq, data) = match self.reliable.next_to_send() {
+            Some(val) => val,
+            None => {
+                return Ok(0);
+            }
+        };
+
+        match Self::send_frame::<CAP>(
+            &mut self.transport,
+            &mut self.auth,
+            &mut self.stats,
+            data,
+            seq,
+        ) {
+            Ok(sent) => Ok(sent),
+            Err(ComErr::Busy) => Ok(0),
+            Err(e) => {
+                // it would fail the same way on every retransmission
+                self.reliable.forget(seq);
+                Err(e)
+            }
+        }
+    }
+
+    // frames `data` with the sequence number `seq` and sends it
+    #[inline]
+    fn send_frame<const CAP: usize>(
+        transport: &mut T,
+        auth: &mut AuthT,
+        stats: &mut LinkStatsDT,
+        data: &DataT,
+        seq: u8,
+    ) -> Result<usize, ComErr> {
+        let mut payload: heapless::Vec<u8, CAP> = match data.to_payload() {
+            Ok(val) => val,
+            Err(e) => {
+                return Err(ComErr::UartError(e));
+            }
+        };
+        auth.sign(seq, &mut payload)?;
+
+        match uart_com::frame_with::<DefaultChecksum, E, CAP>(payload, seq) {
             Ok(frame) => {
                 // send the frame
-                let ret: bool = (self.send_f)(&frame);
+                let ret: bool = transport.write(&frame);
 
                 if ret == true {
-                    return Ok(());
+                    stats.frames_sent += 1;
+                    return Ok(frame.len());
                 } else {
+                    stats.busy_sends += 1;
                     return Err(ComErr::Busy);
                 }
             }
@@ -67,56 +374,164 @@ impl<const BUF_CAP: usize> ComT<BUF_CAP> {
         }
     }
 
-    /// Populates the internal buffer using `read_f`. Looks for a data frame
-    /// (between `uart_com::START_BYTE` and `uart_com::END_BYTE`).
+    /// Populates the internal buffer from the transport. Looks for a data frame
+    /// (between `E::START` and `E::END`). The frame is decoded and
+    /// deserialized inside the internal buffer, without copies. A frame can
+    /// be at most `BUF_CAP` bytes long.
     ///
     /// Returns `ComErr::Empty` if no data is ready yet.
     ///
-    /// `CAP` is used for internal buffers and is configurable. Returns
-    /// `ComErr::ENOMEM` or `ComErr::UartError::ENOMEM` depending on where `CAP`
-    /// became too small.
+    /// `CAP` is used for the buffers of the ACKs sent back.
     ///
     /// If an invalid frame is found, it returns `ComErr::InvalPacket`.
     ///
+    /// Frames that are not authenticated when it is required are counted and
+    /// return `ComErr::Unauthenticated`.
+    ///
+    /// Frames of the reliable channel are acknowledged here. Duplicates and
+    /// ACK/NACK messages are consumed and never returned.
+    ///
+    /// Batches are unpacked here, their messages are returned one per call.
+    ///
     /// Returns also other errors from `uart_com::Error`.
     pub fn read_data<const CAP: usize>(&mut self) -> Result<DataT, ComErr> {
-        self.fill_recv_buffer::<CAP>();
+        loop {
+            let data: DataT = match self.next_batched() {
+                Some(item) => item?,
+                None => match self.next_frame::<CAP>()? {
+                    Some(DataT::Batch(batch)) => {
+                        self.batch = batch;
+                        self.batch_pos = 0;
+                        continue;
+                    }
+                    Some(val) => val,
+                    // a duplicate
+                    None => continue,
+                },
+            };
+
+            if let DataT::AckNack(ack) = data {
+                self.reliable.on_ack(ack);
 
-        let frame: heapless::Vec<u8, CAP> = self.extract_frame_from_recv_buf::<CAP>()?;
+                // the next critical message can go out, the ones that can
+                // never be sent are dropped
+                while self.send_queued::<CAP>().is_err() {}
+                continue;
+            }
+
+            return Ok(data);
+        }
+    }
 
-        // extract the payload
-        match DataT::from_packet::<CAP>(frame) {
-            Ok(val) => {
-                return Ok(val);
+    // next message of the batch being unpacked, if any
+    #[inline]
+    fn next_batched(&mut self) -> Option<Result<DataT, ComErr>> {
+        match self.batch.get(self.batch_pos)? {
+            Ok((data, next)) => {
+                self.batch_pos = next;
+                Some(Ok(data))
             }
             Err(err) => {
-                return Err(ComErr::UartError(err));
+                // the rest of the batch can not be found anymore
+                self.batch_pos = self.batch.data.len();
+                self.stats.des_errors += 1;
+                Some(Err(ComErr::UartError(err)))
             }
-        };
+        }
     }
 
-    // reads from the uart RX buffer and fills in the buffer passed as argument
+    // reads the next frame, returns `None` for the duplicates of the reliable
+    // channel
     #[inline]
-    fn fill_recv_buffer<const CAP: usize>(&mut self) -> usize {
-        let mut written: usize = 0;
+    fn next_frame<const CAP: usize>(&mut self) -> Result<Option<DataT>, ComErr> {
+        self.fill_recv_buffer();
 
-        loop {
-            let mut buffer: [u8; BUFFER_SIZE] = [0; BUFFER_SIZE];
+        let frame_len: usize = self.find_frame()?;
 
-            let remaining_space: usize = self.recv_buffer.capacity() - self.recv_buffer.len();
+        // extract the payload and deserialize it in place
+        let frame: &mut [u8] = &mut self.recv_buffer[..frame_len];
+        let auth: &mut AuthT = &mut self.auth;
+        // the data is `None` if the frame is not authentic
+        let decoded: Result<(u8, Option<Result<DataT, uart_com::Error>>), uart_com::Error> =
+            uart_com::unframe_in_place::<DefaultChecksum, E>(frame)
+                .map(|(seq, payload)| (seq, auth.verify(seq, payload).map(DataT::from_payload)));
 
-            if BUFFER_SIZE > remaining_space {
-                break;
+        self.consume(frame_len);
+
+        let (seq, data) = match decoded {
+            Ok(val) => val,
+            Err(err) => {
+                match err {
+                    uart_com::Error::CRC => self.stats.crc_errors += 1,
+                    uart_com::Error::Des | uart_com::Error::Frame => self.stats.des_errors += 1,
+                    _ => {}
+                }
+                return Err(ComErr::UartError(err));
             }
+        };
 
-            let written_now: usize = (self.read_f)(&mut buffer);
-            written += written_now;
+        let data: Result<DataT, uart_com::Error> = match data {
+            Some(val) => val,
+            None => {
+                // not even ACKed, it did not come from the other side
+                self.stats.auth_errors += 1;
+                return Err(ComErr::Unauthenticated);
+            }
+        };
+
+        let data: DataT = match data {
+            Ok(val) => val,
+            Err(err) => {
+                self.stats.des_errors += 1;
+                if seq != uart_com::NO_SEQ {
+                    // retransmitting it would not help
+                    self.send_ack::<CAP>(seq, false);
+                }
+                return Err(ComErr::UartError(err));
+            }
+        };
+
+        self.stats.frames_received += 1;
 
-            for i in 0..written_now {
-                self.recv_buffer.enqueue(buffer[i])
+        if seq != uart_com::NO_SEQ {
+            // ACK also the duplicates, the previous ACK might have been lost
+            self.send_ack::<CAP>(seq, true);
+
+            if self.reliable.is_duplicate(seq) {
+                return Ok(None);
             }
+        }
+
+        Ok(Some(data))
+    }
+
+    // best-effort answer to a frame of the reliable channel
+    #[inline]
+    fn send_ack<const CAP: usize>(&mut self, seq: u8, ack: bool) {
+        // a lost ACK is compensated by the retransmission of the frame
+        let _ = Self::send_frame::<CAP>(
+            &mut self.transport,
+            &mut self.auth,
+            &mut self.stats,
+            &DataT::AckNack(AckNackDT { seq, ack }),
+            uart_com::NO_SEQ,
+        );
+    }
+
+    // reads from the transport into the free end of the internal buffer
+    #[inline]
+    fn fill_recv_buffer(&mut self) -> usize {
+        let mut written: usize = 0;
+
+        while self.recv_len < BUF_CAP {
+            let space: usize = BUF_CAP - self.recv_len;
+
+            let written_now: usize = self.transport.read(&mut self.recv_buffer[self.recv_len..]);
+            self.recv_len += written_now;
+            written += written_now;
 
-            if written_now != BUFFER_SIZE || self.recv_buffer.is_full() == true {
+            // nothing more is available right now
+            if written_now < space {
                 break;
             }
         }
@@ -124,65 +539,374 @@ impl<const BUF_CAP: usize> ComT<BUF_CAP> {
         written
     }
 
-    // tries to extract an entire Protocol packet
+    // drops the first `n` bytes of the internal buffer
+    #[inline]
+    fn consume(&mut self, n: usize) {
+        self.recv_buffer.copy_within(n..self.recv_len, 0);
+        self.recv_len -= n;
+    }
+
+    // tries to find an entire frame at the beginning of the internal buffer,
+    // returns its length
     #[inline]
-    fn extract_frame_from_recv_buf<const CAP: usize>(
-        &mut self,
-    ) -> Result<heapless::Vec<u8, CAP>, ComErr> {
+    fn find_frame(&mut self) -> Result<usize, ComErr> {
         self.align_to_packet();
 
-        if self.recv_buffer.is_empty() {
+        if self.recv_len == 0 {
             return Err(ComErr::Empty);
         }
 
         let end_byte_pos: usize = self.get_end_of_packet()?;
 
-        // the raw byte escaped packet
-        let mut data: heapless::Vec<u8, CAP> = heapless::Vec::<u8, CAP>::new();
-
-        for _ in 0..=end_byte_pos {
-            match self.recv_buffer.dequeue() {
-                Some(val) => {
-                    match data.push(val) {
-                        Ok(_) => {}
-                        Err(_) => {
-                            return Err(ComErr::ENOMEM);
-                        }
-                    };
-                }
-                None => {
-                    return Err(ComErr::Bug);
-                }
-            };
-        }
-
-        Ok(data)
+        Ok(end_byte_pos + 1)
     }
 
-    // skip bytes untill the top value is the start byte in the protocol
+    // skip bytes untill the first one is the start of a frame
     #[inline]
     fn align_to_packet(&mut self) {
-        while (self.recv_buffer.is_empty() == false)
-            && (*self.recv_buffer.peek().unwrap() != uart_com::START_BYTE)
-        {
-            self.recv_buffer.skip();
+        let skipped: usize = self.recv_buffer[..self.recv_len]
+            .iter()
+            .position(|byte| match E::START {
+                Some(start_byte) => *byte == start_byte,
+                // the frame starts right after the end of the previous one
+                None => *byte != E::END,
+            })
+            .unwrap_or(self.recv_len);
+
+        if skipped > 0 {
+            self.consume(skipped);
+            self.stats.skipped_bytes += skipped as u32;
         }
     }
 
     #[inline]
     fn get_end_of_packet(&mut self) -> Result<usize, ComErr> {
-        for (i, it) in self.recv_buffer.iter().enumerate() {
-            if *it == uart_com::END_BYTE {
+        for (i, it) in self.recv_buffer[..self.recv_len].iter().enumerate() {
+            if *it == E::END {
                 return Ok(i);
-            } else if (i != 0) && (*it == uart_com::START_BYTE) {
-                // delete packet with no END_BYTE
-                for _ in 0..i {
-                    self.recv_buffer.skip();
-                }
+            } else if (i != 0) && (Some(*it) == E::START) {
+                // delete packet with no END
+                self.consume(i);
+                self.stats.resyncs += 1;
+                self.stats.skipped_bytes += i as u32;
                 return Err(ComErr::InvalPacket);
             }
         }
 
+        if self.recv_len == BUF_CAP {
+            // the frame can never fit, drop it to find the next one
+            self.stats.resyncs += 1;
+            self.stats.skipped_bytes += self.recv_len as u32;
+            self.recv_len = 0;
+            return Err(ComErr::InvalPacket);
+        }
+
         Err(ComErr::Empty)
     }
 }
+
+#[cfg(test)]
+mod test {
+    extern crate std;
+
+    use std::vec::Vec;
+
+    use crate::io::*;
+    use crate::protocol::{HandshakeDT, WarningDT, LARGE_CAP};
+    use crate::uart_com::{Cobs, Escape};
+
+    // the loopback also receives everything the `ComT` sends
+    type TestComT<E> = ComT<64, Loopback<256>, E>;
+
+    fn new_com<E: Encoding>() -> TestComT<E> {
+        ComT::with_transport(Loopback::new())
+    }
+
+    fn encoded<E: Encoding>(data: DataT) -> Vec<u8> {
+        data.to_packet_with::<E, 64>(uart_com::NO_SEQ)
+            .unwrap()
+            .to_vec()
+    }
+
+    const KEY: AuthKey = [0x5a; AUTH_KEY_SIZE];
+
+    const SESSION: u64 = 0x1234;
+
+    // the frames of `data`, in order, as sent by a `ComT` authenticating with
+    // `key` in `session`
+    fn signed(key: AuthKey, session: u64, data: &[DataT]) -> Vec<Vec<u8>> {
+        let mut com: TestComT<Escape> = new_com();
+        com.sign_with(key);
+        com.set_session(session);
+
+        let mut frames: Vec<Vec<u8>> = Vec::new();
+        for item in data {
+            com.send_data::<64>(item.clone()).unwrap();
+
+            let mut frame: [u8; 64] = [0; 64];
+            let len: usize = com.transport_mut().read(&mut frame);
+            frames.push(frame[..len].to_vec());
+        }
+
+        frames
+    }
+
+    // reads until a message arrives or there is nothing left on the wire
+    fn read_next<T: Transport, E: Encoding>(com: &mut ComT<64, T, E>) -> Option<DataT> {
+        loop {
+            match com.read_data::<64>() {
+                Ok(data) => return Some(data),
+                Err(ComErr::Empty) => return None,
+                Err(_) => {}
+            }
+        }
+    }
+
+    // garbage, a truncated frame and a few bytes of another frame, then
+    // valid frames: the valid ones must come through
+    fn check_resync<E: Encoding>() {
+        let mut com: TestComT<E> = new_com();
+        let first: DataT = DataT::Warning(WarningDT::ControlNotNeutral);
+        let second: DataT = DataT::KeepAlive;
+
+        let mut stream: Vec<u8> = Vec::new();
+        stream.extend_from_slice(&[0x11, uart_com::END_BYTE, 0x00, 0x42]);
+        let truncated: Vec<u8> = encoded::<E>(first.clone());
+        stream.extend_from_slice(&truncated[..truncated.len() - 2]);
+        if E::START.is_none() {
+            // nothing marks the start of a frame, so the truncated one is
+            // only detected at the next delimiter
+            stream.push(E::END);
+        }
+        stream.extend_from_slice(&encoded::<E>(first.clone()));
+        stream.extend_from_slice(&encoded::<E>(second.clone()));
+        com.transport_mut().write(&stream);
+
+        assert_eq!(read_next(&mut com), Some(first));
+        assert_eq!(read_next(&mut com), Some(second));
+        assert_eq!(read_next(&mut com), None);
+    }
+
+    #[test]
+    fn test_resync_escape() {
+        check_resync::<Escape>();
+    }
+
+    #[test]
+    fn test_resync_cobs() {
+        check_resync::<Cobs>();
+    }
+
+    #[test]
+    fn test_link_stats() {
+        let mut com: TestComT<Escape> = new_com();
+
+        let sent: usize = com.send_data::<64>(DataT::KeepAlive).unwrap();
+        assert_eq!(sent, encoded::<Escape>(DataT::KeepAlive).len());
+        let mut corrupted: Vec<u8> = encoded::<Escape>(DataT::StartLogging);
+        let last: usize = corrupted.len() - 2;
+        corrupted[last] ^= 0x01;
+        // garbage, a truncated frame and a frame with a bad checksum
+        com.transport_mut()
+            .write(&[0x11, 0x22, uart_com::START_BYTE, 0x33]);
+        com.transport_mut().write(&corrupted);
+
+        assert_eq!(read_next(&mut com), Some(DataT::KeepAlive));
+        assert_eq!(read_next(&mut com), None);
+
+        let stats: LinkStatsDT = com.link_stats();
+        assert_eq!(stats.frames_sent, 1);
+        assert_eq!(stats.frames_received, 1);
+        assert_eq!(stats.crc_errors, 1);
+        assert_eq!(stats.des_errors, 0);
+        assert_eq!(stats.resyncs, 1);
+        assert_eq!(stats.skipped_bytes, 4);
+
+        com.reset_link_stats();
+        assert_eq!(com.link_stats(), LinkStatsDT::default());
+    }
+
+    #[test]
+    fn test_authenticated_round_trip() {
+        let mut com: TestComT<Escape> = new_com();
+        com.sign_with(KEY);
+        com.require_auth(KEY);
+        com.set_session(SESSION);
+
+        com.send_data::<64>(DataT::StartLogging).unwrap();
+        com.send_data::<64>(DataT::KeepAlive).unwrap();
+        assert!(com.transport().len() > 2 * AUTH_TRAILER_SIZE);
+
+        assert_eq!(read_next(&mut com), Some(DataT::StartLogging));
+        assert_eq!(read_next(&mut com), Some(DataT::KeepAlive));
+        assert_eq!(com.link_stats().auth_errors, 0);
+    }
+
+    #[test]
+    fn test_forged_frames_are_rejected() {
+        let mut com: TestComT<Escape> = new_com();
+        com.require_auth(KEY);
+        com.set_session(SESSION);
+
+        // no authentication, another key and a made-up one (with a valid
+        // checksum, like a forger would compute)
+        com.transport_mut()
+            .write(&encoded::<Escape>(DataT::KeepAlive));
+        com.transport_mut()
+            .write(&signed([0xa5; AUTH_KEY_SIZE], SESSION, &[DataT::KeepAlive])[0]);
+        let mut forged: heapless::Vec<u8, 64> = DataT::KeepAlive.to_payload().unwrap();
+        forged
+            .extend_from_slice(&[0x01; AUTH_TRAILER_SIZE])
+            .unwrap();
+        com.transport_mut().write(
+            &uart_com::frame_with::<DefaultChecksum, Escape, 64>(forged, uart_com::NO_SEQ).unwrap(),
+        );
+
+        for _ in 0..3 {
+            assert!(matches!(
+                com.read_data::<64>(),
+                Err(ComErr::Unauthenticated)
+            ));
+        }
+        assert_eq!(read_next(&mut com), None);
+
+        let stats: LinkStatsDT = com.link_stats();
+        assert_eq!(stats.frames_received, 0);
+        assert_eq!(stats.auth_errors, 3);
+    }
+
+    #[test]
+    fn test_replayed_frames_are_rejected() {
+        let mut com: TestComT<Escape> = new_com();
+        com.require_auth(KEY);
+        com.set_session(SESSION);
+
+        let frames: Vec<Vec<u8>> = signed(
+            KEY,
+            SESSION,
+            &[DataT::StopLogging, DataT::StartLogging, DataT::KeepAlive],
+        );
+        com.transport_mut().write(&frames[1]);
+        com.transport_mut().write(&frames[1]);
+        // an older frame
+        com.transport_mut().write(&frames[0]);
+        // a newer one
+        com.transport_mut().write(&frames[2]);
+
+        assert_eq!(read_next(&mut com), Some(DataT::StartLogging));
+        assert_eq!(read_next(&mut com), Some(DataT::KeepAlive));
+        assert_eq!(com.link_stats().auth_errors, 2);
+    }
+
+    #[test]
+    fn test_frames_of_other_sessions_are_rejected() {
+        let mut com: TestComT<Escape> = new_com();
+        com.require_auth(KEY);
+
+        // before the first session only a handshake is accepted
+        let handshake: DataT = DataT::Handshake(HandshakeDT::local());
+        let unbound: Vec<Vec<u8>> =
+            signed(KEY, NO_SESSION, &[DataT::StartLogging, handshake.clone()]);
+        com.transport_mut().write(&unbound[0]);
+        com.transport_mut().write(&unbound[1]);
+        assert!(matches!(
+            com.read_data::<64>(),
+            Err(ComErr::Unauthenticated)
+        ));
+        assert_eq!(read_next(&mut com), Some(handshake.clone()));
+
+        // e.g. recorded before the drone restarted, the counters start from
+        // 0 in every session
+        let earlier: Vec<Vec<u8>> = signed(KEY, SESSION + 1, &[DataT::StopLogging]);
+        com.set_session(SESSION);
+        com.transport_mut().write(&earlier[0]);
+        com.transport_mut()
+            .write(&signed(KEY, SESSION, &[DataT::KeepAlive])[0]);
+        // starts yet another session, it does not disturb this one
+        com.transport_mut().write(&unbound[1]);
+
+        assert!(matches!(
+            com.read_data::<64>(),
+            Err(ComErr::Unauthenticated)
+        ));
+        assert_eq!(read_next(&mut com), Some(DataT::KeepAlive));
+        assert_eq!(read_next(&mut com), Some(handshake));
+        assert_eq!(com.link_stats().auth_errors, 2);
+    }
+
+    #[test]
+    fn test_large_data_round_trip() {
+        // all the fragments wait in the loopback until they are read
+        let mut com: ComT<64, Loopback<1024>, Escape> = ComT::with_transport(Loopback::new());
+        let mut re: Reassembler<LARGE_SERIALIZED_CAP, 1> = Reassembler::new(10);
+
+        let mut text: heapless::String<LARGE_CAP> = heapless::String::new();
+        for _ in 0..8 {
+            text.push_str("a long message <with> escapes\\").unwrap();
+        }
+        let sent: LargeDataT = LargeDataT::Message(text);
+
+        let serialized: heapless::Vec<u8, LARGE_SERIALIZED_CAP> = sent.to_bytes().unwrap();
+        assert!(serialized.len() > 5 * FRAGMENT_SIZE);
+        com.send_large_data::<64>(&sent).unwrap();
+
+        let mut received: Option<LargeDataT> = None;
+
+        while let Some(data) = read_next(&mut com) {
+            if let DataT::Fragment(fragment) = data {
+                if let Some(payload) = re.push(fragment) {
+                    received = Some(LargeDataT::from_bytes(&payload).unwrap());
+                }
+            }
+        }
+
+        assert_eq!(received, Some(sent));
+    }
+
+    #[test]
+    fn test_large_data_too_large() {
+        let mut com: TestComT<Escape> = ComT::with_transport(Loopback::new());
+
+        assert!(matches!(
+            com.send_large::<64>(&[0; MAX_FRAGMENTED_SIZE + 1]),
+            Err(ComErr::ENOMEM)
+        ));
+        assert!(LARGE_SERIALIZED_CAP <= MAX_FRAGMENTED_SIZE);
+    }
+
+    #[test]
+    fn test_batch_is_unpacked() {
+        let mut com: TestComT<Escape> = new_com();
+
+        let mut batch: BatchDT = BatchDT::new();
+        assert!(batch.push(&DataT::KeepAlive));
+        assert!(batch.push(&DataT::Warning(WarningDT::ControlNotNeutral)));
+        com.send_data::<64>(DataT::Batch(batch)).unwrap();
+        com.send_data::<64>(DataT::StartLogging).unwrap();
+
+        assert_eq!(read_next(&mut com), Some(DataT::KeepAlive));
+        assert_eq!(
+            read_next(&mut com),
+            Some(DataT::Warning(WarningDT::ControlNotNeutral))
+        );
+        assert_eq!(read_next(&mut com), Some(DataT::StartLogging));
+        assert_eq!(read_next(&mut com), None);
+        assert_eq!(com.link_stats().frames_received, 2);
+    }
+
+    #[test]
+    fn test_unterminated_frame_is_dropped() {
+        let mut com: TestComT<Cobs> = new_com();
+
+        // longer than the receive buffer and never terminated
+        com.transport_mut().write(&[0x55; 100]);
+        assert_eq!(read_next(&mut com), None);
+
+        // the rest of the garbage makes the first frame after it invalid
+        com.transport_mut()
+            .write(&encoded::<Cobs>(DataT::StartLogging));
+        com.transport_mut()
+            .write(&encoded::<Cobs>(DataT::KeepAlive));
+        assert_eq!(read_next(&mut com), Some(DataT::KeepAlive));
+    }
+}
@@ -0,0 +1,419 @@
+use crate::io::ComErr;
+use crate::protocol::{BatchDT, DataT};
+
+/// Bits on the wire per byte with 8N1 framing (start bit, 8 data bits, stop
+/// bit).
+const BITS_PER_BYTE: u32 = 10;
+
+/// Classes of outgoing messages, a class is only sent when the ones before it
+/// are empty.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Priority {
+    Safety, // mode changes, warnings, errors, answers to requests, keep alives, heartbeats, the handshake
+    Telemetry, // everything else
+    Log,    // the log report and the parameter list, bulk data that can wait
+}
+
+const PRIORITIES: usize = 3;
+
+impl Priority {
+    pub fn of(data: &DataT) -> Self {
+        match data {
+            DataT::Handshake(_)
+            | DataT::Mode(_)
+            | DataT::Warning(_)
+            | DataT::Response(_)
+            | DataT::KeepAlive
+            | DataT::Heartbeat(_)
+            | DataT::AckNack(_) => Priority::Safety,
+
+            // an error must get through a busy link like a warning
+            DataT::Event(event) if event.code.is_error() => Priority::Safety,
+
+            // time spent in the queue shows up as link delay in the clock sync
+            DataT::TimeSync(_) => Priority::Safety,
+
+            // the end of the report must not overtake its last entries
+            DataT::SensorLog(_) | DataT::StopLogReporting | DataT::Fragment(_) => Priority::Log,
+            DataT::SensorStream(stream) if !stream.live => Priority::Log,
+
+            // the definition of a parameter must not overtake its value
+            DataT::ParamInfo(_) | DataT::ParamValue(_) => Priority::Log,
+
+            _ => Priority::Telemetry,
+        }
+    }
+}
+
+/// Bytes a UART at `baud_rate` carries in one tick of a loop running at
+/// `tick_frequency` Hz.
+pub fn bytes_per_tick(baud_rate: u32, tick_frequency: u32) -> u32 {
+    baud_rate / BITS_PER_BYTE / tick_frequency
+}
+
+/// Outgoing messages waiting for their turn, at most `SLOTS` per `Priority`.
+///
+/// `drain` sends them by priority while the byte budget lasts. The budget grows
+/// by `bytes_per_tick` on every call and up to `burst` bytes (e.g. the size of
+/// the TX buffer) can be saved for later. A frame is sent as long as the budget
+/// is positive, so it may go below zero by at most one frame.
+///
+/// Consecutive messages that fit together are sent in one `DataT::Batch`, in
+/// the same order.
+///
+/// When the telemetry slots are full the oldest telemetry is dropped, newer
+/// values are worth more. The other classes refuse new messages instead.
+pub struct OutQueue<const SLOTS: usize> {
+    queues: [heapless::Deque<DataT, SLOTS>; PRIORITIES],
+
+    budget: i32,
+    bytes_per_tick: i32,
+    burst: i32,
+
+    dropped: u32,
+}
+
+impl<const SLOTS: usize> OutQueue<SLOTS> {
+    pub fn new(bytes_per_tick: u32, burst: u32) -> Self {
+        Self {
+            queues: Default::default(),
+
+            budget: 0,
+            bytes_per_tick: bytes_per_tick as i32,
+            burst: burst as i32,
+
+            dropped: 0,
+        }
+    }
+
+    /// Queues `data`. If there is no room for it, it is given back, except for
+    /// telemetry which takes the place of the oldest one.
+    pub fn push(&mut self, data: DataT) -> Result<(), DataT> {
+        let priority: Priority = Priority::of(&data);
+        let queue: &mut heapless::Deque<DataT, SLOTS> = &mut self.queues[priority as usize];
+
+        if queue.is_full() {
+            if priority != Priority::Telemetry {
+                return Err(data);
+            }
+
+            queue.pop_front();
+            self.dropped += 1;
+        }
+
+        queue.push_back(data)
+    }
+
+    /// Checks if a message of class `priority` would be queued.
+    pub fn has_room(&self, priority: Priority) -> bool {
+        self.room(priority) > 0
+    }
+
+    /// Number of messages of class `priority` that can still be queued.
+    pub fn room(&self, priority: Priority) -> usize {
+        let queue: &heapless::Deque<DataT, SLOTS> = &self.queues[priority as usize];
+        queue.capacity() - queue.len()
+    }
+
+    /// Number of queued messages.
+    pub fn len(&self) -> usize {
+        self.queues.iter().map(|q| q.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Number of telemetry messages dropped so far because newer ones came.
+    pub fn dropped(&self) -> u32 {
+        self.dropped
+    }
+
+    /// Adds a tick worth of budget and hands the queued messages to `send`,
+    /// highest priority first, until the budget or the queue runs out. It
+    /// should be called once per tick.
+    ///
+    /// `send` returns the number of bytes it sent. On `ComErr::Busy` the
+    /// message stays in front of its queue and the rest waits for the next
+    /// tick. On any other error the message is dropped.
+    ///
+    /// Returns the error of the first message dropped that way, if any.
+    pub fn drain<F>(&mut self, mut send: F) -> Result<(), ComErr>
+    where
+        F: FnMut(DataT) -> Result<usize, ComErr>,
+    {
+        self.budget = (self.budget + self.bytes_per_tick).min(self.burst);
+
+        let mut ret: Result<(), ComErr> = Ok(());
+
+        while self.budget > 0 {
+            let (data, count) = match self.front_batch() {
+                Some(val) => val,
+                None => break,
+            };
+
+            match send(data) {
+                Ok(sent) => {
+                    self.budget -= sent as i32;
+                }
+                Err(ComErr::Busy) => break,
+                Err(err) => {
+                    if ret.is_ok() {
+                        ret = Err(err);
+                    }
+                }
+            }
+
+            for _ in 0..count {
+                self.pop_front();
+            }
+        }
+
+        ret
+    }
+
+    // the next message to send and how many queued messages it contains,
+    // several of them are packed in a batch when they fit
+    fn front_batch(&self) -> Option<(DataT, usize)> {
+        let mut queued = self.queues.iter().flat_map(|q| q.iter());
+        let first: &DataT = queued.next()?;
+
+        let mut batch: BatchDT = BatchDT::new();
+        if !batch.push(first) {
+            return Some((first.clone(), 1));
+        }
+
+        let mut count: usize = 1;
+        for data in queued {
+            if !batch.push(data) {
+                break;
+            }
+            count += 1;
+        }
+
+        if count == 1 {
+            // a batch of one only adds overhead
+            return Some((first.clone(), 1));
+        }
+
+        Some((DataT::Batch(batch), count))
+    }
+
+    // removes the message that is sent first
+    fn pop_front(&mut self) -> Option<DataT> {
+        self.queues.iter_mut().find_map(|q| q.pop_front())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::io::queue::*;
+    use crate::protocol::{EventCodeT, EventDT, WarningDT};
+    use crate::DroneMode;
+
+    fn message(text: &str) -> DataT {
+        DataT::Message(heapless::String::from(text))
+    }
+
+    // too long to share a batch
+    fn long_message() -> DataT {
+        message("a long telemetry message")
+    }
+
+    // the messages sent, with the batches unpacked
+    fn unpack(sent: &[DataT]) -> Vec<DataT> {
+        let mut ret: Vec<DataT> = Vec::new();
+
+        for data in sent {
+            match data {
+                DataT::Batch(batch) => {
+                    let mut pos: usize = 0;
+                    while let Some(item) = batch.get(pos) {
+                        let (item, next) = item.unwrap();
+                        ret.push(item);
+                        pos = next;
+                    }
+                }
+                _ => ret.push(data.clone()),
+            }
+        }
+
+        ret
+    }
+
+    #[test]
+    fn test_budget_from_baud_rate() {
+        assert_eq!(bytes_per_tick(115200, 100), 115);
+    }
+
+    #[test]
+    fn test_safety_goes_first() {
+        let mut queue: OutQueue<4> = OutQueue::new(100, 200);
+        let mut sent: Vec<DataT> = Vec::new();
+
+        queue.push(DataT::StopLogReporting).unwrap();
+        queue.push(message("telemetry")).unwrap();
+        queue.push(DataT::Mode(DroneMode::Panic)).unwrap();
+        queue
+            .push(DataT::Warning(WarningDT::HandshakeMissing))
+            .unwrap();
+
+        queue
+            .drain(|data| {
+                sent.push(data);
+                Ok(10)
+            })
+            .unwrap();
+
+        assert!(queue.is_empty());
+        assert_eq!(
+            unpack(&sent),
+            [
+                DataT::Mode(DroneMode::Panic),
+                DataT::Warning(WarningDT::HandshakeMissing),
+                message("telemetry"),
+                DataT::StopLogReporting,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_errors_are_safety() {
+        let error: DataT = DataT::Event(EventDT::new(EventCodeT::LogFull, [0, 0]));
+        let info: DataT = DataT::Event(EventDT::new(EventCodeT::LogStarted, [0, 0]));
+
+        assert_eq!(Priority::of(&error), Priority::Safety);
+        assert_eq!(Priority::of(&info), Priority::Telemetry);
+    }
+
+    #[test]
+    fn test_budget_limits_each_tick() {
+        let mut queue: OutQueue<4> = OutQueue::new(25, 50);
+        let mut sent: usize = 0;
+
+        for _ in 0..4 {
+            queue.push(long_message()).unwrap();
+        }
+
+        // 25 bytes of budget, the second frame takes it below zero
+        queue
+            .drain(|_| {
+                sent += 1;
+                Ok(20)
+            })
+            .unwrap();
+        assert_eq!(sent, 2);
+
+        // -15 + 25 leaves room for a single frame
+        queue
+            .drain(|_| {
+                sent += 1;
+                Ok(20)
+            })
+            .unwrap();
+        assert_eq!(sent, 3);
+        assert_eq!(queue.len(), 1);
+
+        // an idle link saves up to `burst` bytes
+        queue.drain(|_| Ok(20)).unwrap();
+        for _ in 0..10 {
+            queue.drain(|_| Ok(20)).unwrap();
+        }
+        for _ in 0..4 {
+            queue.push(long_message()).unwrap();
+        }
+        sent = 0;
+        queue
+            .drain(|_| {
+                sent += 1;
+                Ok(20)
+            })
+            .unwrap();
+        assert_eq!(sent, 3);
+    }
+
+    #[test]
+    fn test_busy_keeps_the_message() {
+        let mut queue: OutQueue<4> = OutQueue::new(100, 200);
+
+        queue.push(DataT::KeepAlive).unwrap();
+        queue.drain(|_| Err(ComErr::Busy)).unwrap();
+        assert_eq!(queue.len(), 1);
+
+        assert!(matches!(
+            queue.drain(|_| Err(ComErr::ENOMEM)),
+            Err(ComErr::ENOMEM)
+        ));
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn test_full_classes() {
+        let mut queue: OutQueue<2> = OutQueue::new(100, 200);
+
+        queue.push(message("1")).unwrap();
+        queue.push(message("2")).unwrap();
+        queue.push(message("3")).unwrap();
+        assert_eq!(queue.dropped(), 1);
+        assert!(queue.has_room(Priority::Safety));
+
+        queue.push(DataT::KeepAlive).unwrap();
+        queue.push(DataT::KeepAlive).unwrap();
+        assert!(!queue.has_room(Priority::Safety));
+        assert_eq!(queue.room(Priority::Log), 2);
+        assert_eq!(queue.push(DataT::KeepAlive), Err(DataT::KeepAlive));
+
+        let mut sent: Vec<DataT> = Vec::new();
+        queue
+            .drain(|data| {
+                sent.push(data);
+                Ok(1)
+            })
+            .unwrap();
+        assert_eq!(
+            unpack(&sent),
+            [
+                DataT::KeepAlive,
+                DataT::KeepAlive,
+                message("2"),
+                message("3")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_small_messages_are_batched() {
+        let mut queue: OutQueue<4> = OutQueue::new(100, 200);
+        let mut sent: Vec<DataT> = Vec::new();
+
+        queue.push(DataT::KeepAlive).unwrap();
+        queue.push(message("health")).unwrap();
+        queue.push(DataT::Mode(DroneMode::Safe)).unwrap();
+        queue.push(message("motors")).unwrap();
+        queue.push(long_message()).unwrap();
+
+        queue
+            .drain(|data| {
+                sent.push(data);
+                Ok(10)
+            })
+            .unwrap();
+
+        // the critical mode change is sent on its own, the keep alive before
+        // it is left alone as well
+        assert_eq!(sent.len(), 4);
+        assert_eq!(sent[0], DataT::KeepAlive);
+        assert_eq!(sent[1], DataT::Mode(DroneMode::Safe));
+        assert!(matches!(sent[2], DataT::Batch(_)));
+        assert_eq!(
+            unpack(&sent),
+            [
+                DataT::KeepAlive,
+                DataT::Mode(DroneMode::Safe),
+                message("health"),
+                message("motors"),
+                long_message(),
+            ]
+        );
+    }
+}
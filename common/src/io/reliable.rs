@@ -0,0 +1,373 @@
+use crate::io::ComErr;
+use crate::protocol::{AckNackDT, DataT};
+use crate::uart_com::NO_SEQ;
+
+/// How many critical messages can wait for an ACK at the same time.
+pub const RELIABLE_SLOTS: usize = 4;
+
+/// How many of the last received sequence numbers are remembered in order to
+/// drop retransmitted duplicates.
+const DEDUP_WINDOW: usize = 8;
+
+/// Timing of the reliable channel. All values are expressed in calls to
+/// `ComT::tick_reliable`, so each side can use its own tick rate.
+#[derive(Debug, Clone, Copy)]
+pub struct ReliableConfig {
+    pub retransmit_ticks: u32, // wait for an ACK before sending again
+    pub max_retries: u8,       // retransmissions before giving up on a message
+    pub dedup_ticks: u32,      // how long a received sequence number is remembered
+}
+
+impl Default for ReliableConfig {
+    fn default() -> Self {
+        Self {
+            retransmit_ticks: 20,
+            max_retries: 5,
+            dedup_ticks: 200,
+        }
+    }
+}
+
+// a critical message waiting for its ACK
+struct PendingT {
+    seq: u8,
+    data: DataT,
+    sent: bool, // only the oldest message is in flight
+    ticks_left: u32,
+    retries_left: u8,
+}
+
+// a recently received sequence number
+#[derive(Clone, Copy, Default)]
+struct SeenT {
+    seq: u8, // NO_SEQ marks an empty entry
+    age: u32,
+}
+
+/// State of the reliable channel: sequence numbers handed out, messages
+/// waiting for an ACK and the recently received sequence numbers.
+///
+/// The receiving part (ACKing and dropping duplicates) is always active since
+/// the sender decides which frames are tracked. Only the sending part has to
+/// be enabled.
+///
+/// Critical messages are delivered in the order they were tracked: only the
+/// oldest one is in flight, the next one is sent once it is ACKed or given up
+/// on.
+pub(crate) struct ReliableT {
+    enabled: bool,
+    config: ReliableConfig,
+
+    next_seq: u8,
+    pending: heapless::Vec<PendingT, RELIABLE_SLOTS>, // oldest first
+
+    seen: [SeenT; DEDUP_WINDOW],
+    seen_pos: usize,
+
+    lost: u32,
+}
+
+impl ReliableT {
+    pub(crate) fn new() -> Self {
+        Self {
+            enabled: false,
+            config: ReliableConfig::default(),
+
+            next_seq: NO_SEQ,
+            pending: heapless::Vec::new(),
+
+            seen: [SeenT::default(); DEDUP_WINDOW],
+            seen_pos: 0,
+
+            lost: 0,
+        }
+    }
+
+    pub(crate) fn enable(&mut self, config: ReliableConfig) {
+        self.enabled = true;
+        self.config = config;
+    }
+
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Number of critical messages that were given up on (no ACK after all
+    /// the retries or a NACK was received).
+    pub(crate) fn lost(&self) -> u32 {
+        self.lost
+    }
+
+    /// Number of critical messages still waiting for an ACK.
+    pub(crate) fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Remembers `data` until it is acknowledged and returns the sequence
+    /// number it has to be sent with. It is sent once `next_to_send` returns
+    /// it.
+    pub(crate) fn track(&mut self, data: DataT) -> Result<u8, ComErr> {
+        if self.pending.is_full() {
+            return Err(ComErr::WindowFull);
+        }
+
+        // NO_SEQ is reserved for best-effort frames
+        self.next_seq = self.next_seq.wrapping_add(1);
+        if self.next_seq == NO_SEQ {
+            self.next_seq = self.next_seq.wrapping_add(1);
+        }
+
+        let pending: PendingT = PendingT {
+            seq: self.next_seq,
+            data,
+            sent: false,
+            ticks_left: self.config.retransmit_ticks,
+            retries_left: self.config.max_retries,
+        };
+        if self.pending.push(pending).is_err() {
+            return Err(ComErr::Bug);
+        }
+
+        Ok(self.next_seq)
+    }
+
+    /// Returns the oldest message if it was not sent yet, it is considered in
+    /// flight from now on. Returns `None` while it waits for its ACK.
+    pub(crate) fn next_to_send(&mut self) -> Option<(u8, &DataT)> {
+        let front: &mut PendingT = self.pending.first_mut()?;
+        if front.sent {
+            return None;
+        }
+
+        front.sent = true;
+        front.ticks_left = self.config.retransmit_ticks;
+
+        Some((front.seq, &front.data))
+    }
+
+    /// Stops tracking `seq` without counting it as lost (it could never be
+    /// sent in the first place).
+    pub(crate) fn forget(&mut self, seq: u8) {
+        self.pending.retain(|p| p.seq != seq);
+    }
+
+    /// Handles an ACK/NACK from the other side. The next message can then be
+    /// sent, see `next_to_send`.
+    pub(crate) fn on_ack(&mut self, ack: AckNackDT) {
+        let before: usize = self.pending.len();
+        self.pending.retain(|p| p.seq != ack.seq);
+
+        if self.pending.len() < before && !ack.ack {
+            self.lost += 1;
+        }
+    }
+
+    /// Returns `true` if `seq` was already received recently. Otherwise it is
+    /// remembered for the next `dedup_ticks`.
+    pub(crate) fn is_duplicate(&mut self, seq: u8) -> bool {
+        if self.seen.iter().any(|s| s.seq == seq) {
+            return true;
+        }
+
+        self.seen[self.seen_pos] = SeenT { seq, age: 0 };
+        self.seen_pos = (self.seen_pos + 1) % DEDUP_WINDOW;
+
+        false
+    }
+
+    /// Forgets the sequence numbers received so far. It is called on a
+    /// handshake: a restarted peer counts from the start again and its fresh
+    /// frames would be taken for duplicates.
+    pub(crate) fn reset_dedup(&mut self) {
+        self.seen = [SeenT::default(); DEDUP_WINDOW];
+        self.seen_pos = 0;
+    }
+
+    /// Advances the timers by one tick. `resend` is called for the message in
+    /// flight when its ACK timed out, or for the next message once the
+    /// previous one was given up on. Returns the number of messages given up
+    /// on during this tick.
+    pub(crate) fn tick<F: FnMut(u8, &DataT)>(&mut self, mut resend: F) -> usize {
+        for seen in self.seen.iter_mut() {
+            seen.age += 1;
+
+            if seen.age > self.config.dedup_ticks {
+                seen.seq = NO_SEQ;
+            }
+        }
+
+        let mut given_up: usize = 0;
+
+        while let Some(front) = self.pending.first_mut() {
+            if !front.sent {
+                // the previous message was given up on
+                front.sent = true;
+                front.ticks_left = self.config.retransmit_ticks;
+                resend(front.seq, &front.data);
+                break;
+            }
+
+            front.ticks_left = front.ticks_left.saturating_sub(1);
+            if front.ticks_left > 0 {
+                break;
+            }
+
+            if front.retries_left > 0 {
+                front.retries_left -= 1;
+                front.ticks_left = self.config.retransmit_ticks;
+                resend(front.seq, &front.data);
+                break;
+            }
+
+            self.pending.remove(0);
+            self.lost += 1;
+            given_up += 1;
+        }
+
+        given_up
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::io::reliable::*;
+
+    fn config() -> ReliableConfig {
+        ReliableConfig {
+            retransmit_ticks: 2,
+            max_retries: 1,
+            dedup_ticks: 3,
+        }
+    }
+
+    #[test]
+    fn test_retransmit_then_give_up() {
+        let mut rel = ReliableT::new();
+        rel.enable(config());
+
+        let seq: u8 = rel.track(DataT::StopLogging).unwrap();
+        assert_ne!(seq, NO_SEQ);
+        assert_eq!(rel.next_to_send(), Some((seq, &DataT::StopLogging)));
+
+        let mut resent: usize = 0;
+        assert_eq!(rel.tick(|_, _| resent += 1), 0);
+        assert_eq!(
+            rel.tick(|s, d| {
+                assert_eq!(s, seq);
+                assert_eq!(*d, DataT::StopLogging);
+                resent += 1;
+            }),
+            0
+        );
+        assert_eq!(resent, 1);
+
+        rel.tick(|_, _| {});
+        assert_eq!(rel.tick(|_, _| {}), 1);
+        assert_eq!(rel.pending(), 0);
+        assert_eq!(rel.lost(), 1);
+    }
+
+    #[test]
+    fn test_ack_and_nack() {
+        let mut rel = ReliableT::new();
+        rel.enable(config());
+
+        let a: u8 = rel.track(DataT::StartLogging).unwrap();
+        let b: u8 = rel.track(DataT::StopLogging).unwrap();
+        assert_ne!(a, b);
+
+        rel.on_ack(AckNackDT { seq: a, ack: true });
+        assert_eq!(rel.pending(), 1);
+        assert_eq!(rel.lost(), 0);
+
+        rel.on_ack(AckNackDT { seq: b, ack: false });
+        assert_eq!(rel.pending(), 0);
+        assert_eq!(rel.lost(), 1);
+    }
+
+    #[test]
+    fn test_one_in_flight_in_order() {
+        let mut rel = ReliableT::new();
+        rel.enable(config());
+
+        let a: u8 = rel.track(DataT::StartLogging).unwrap();
+        let b: u8 = rel.track(DataT::StopLogging).unwrap();
+        let c: u8 = rel.track(DataT::StartLogReporting).unwrap();
+
+        assert_eq!(rel.next_to_send(), Some((a, &DataT::StartLogging)));
+        // `b` waits for `a`
+        assert_eq!(rel.next_to_send(), None);
+
+        let mut resent: heapless::Vec<u8, 8> = heapless::Vec::new();
+        rel.tick(|s, _| resent.push(s).unwrap());
+        rel.tick(|s, _| resent.push(s).unwrap());
+        assert_eq!(resent, [a]);
+
+        rel.on_ack(AckNackDT { seq: a, ack: true });
+        assert_eq!(rel.next_to_send(), Some((b, &DataT::StopLogging)));
+
+        // `b` is given up on, `c` goes out on the same tick
+        resent.clear();
+        for _ in 0..4 {
+            rel.tick(|s, _| resent.push(s).unwrap());
+        }
+        assert_eq!(resent, [b, c]);
+        assert_eq!(rel.pending(), 1);
+        assert_eq!(rel.lost(), 1);
+    }
+
+    #[test]
+    fn test_window_full() {
+        let mut rel = ReliableT::new();
+        rel.enable(config());
+
+        for _ in 0..RELIABLE_SLOTS {
+            rel.track(DataT::StartLogging).unwrap();
+        }
+
+        assert!(matches!(
+            rel.track(DataT::StartLogging),
+            Err(ComErr::WindowFull)
+        ));
+    }
+
+    #[test]
+    fn test_duplicates_expire() {
+        let mut rel = ReliableT::new();
+        rel.enable(config());
+
+        assert_eq!(rel.is_duplicate(5), false);
+        assert_eq!(rel.is_duplicate(5), true);
+
+        for _ in 0..4 {
+            rel.tick(|_, _| {});
+        }
+
+        assert_eq!(rel.is_duplicate(5), false);
+    }
+
+    #[test]
+    fn test_reset_dedup() {
+        let mut rel = ReliableT::new();
+
+        assert_eq!(rel.is_duplicate(1), false);
+        assert_eq!(rel.is_duplicate(2), false);
+
+        // the peer restarted and counts from 1 again
+        rel.reset_dedup();
+        assert_eq!(rel.is_duplicate(1), false);
+        assert_eq!(rel.is_duplicate(1), true);
+    }
+
+    #[test]
+    fn test_seq_skips_no_seq() {
+        let mut rel = ReliableT::new();
+        rel.enable(config());
+
+        for _ in 0..600 {
+            let seq: u8 = rel.track(DataT::StartLogging).unwrap();
+            assert_ne!(seq, NO_SEQ);
+            rel.forget(seq);
+        }
+    }
+}
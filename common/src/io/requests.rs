@@ -0,0 +1,235 @@
+use crate::io::ComErr;
+use crate::protocol::{CommandT, DataT, RequestDT, RequestId, ResponseDT, ResultT};
+
+/// What became of a request, see `Requests::poll`.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Outcome {
+    Pending, // no answer yet
+    Answered(ResultT),
+    TimedOut, // no answer in time, the command may or may not have been executed
+}
+
+// a request sent to the other side
+struct SlotT {
+    id: RequestId,
+    command: CommandT,
+    outcome: Outcome,
+    ticks_left: u32, // until it times out, while pending
+}
+
+/// Keeps track of the requests sent to the other side: hands out their ids,
+/// matches the responses to them and times out the ones left unanswered.
+///
+/// At most `SLOTS` requests are tracked at the same time, a finished one keeps
+/// its slot until its outcome is taken with `poll` or `take_finished`. The
+/// timeout is expressed in calls to `tick`, it should be longer than the
+/// retransmissions of the reliable channel.
+pub struct Requests<const SLOTS: usize> {
+    slots: [Option<SlotT>; SLOTS],
+    timeout_ticks: u32,
+
+    next_id: RequestId,
+}
+
+impl<const SLOTS: usize> Requests<SLOTS> {
+    pub fn new(timeout_ticks: u32) -> Self {
+        Self {
+            slots: core::array::from_fn(|_| None),
+            timeout_ticks,
+            next_id: 0,
+        }
+    }
+
+    /// Starts tracking `command`.
+    /// @return its id and the message to send it with
+    pub fn start(&mut self, command: CommandT) -> Result<(RequestId, DataT), ComErr> {
+        let id: RequestId = self.next_id;
+        let slot: &mut Option<SlotT> = match self.slots.iter_mut().find(|s| s.is_none()) {
+            Some(slot) => slot,
+            None => {
+                return Err(ComErr::WindowFull);
+            }
+        };
+
+        *slot = Some(SlotT {
+            id,
+            command,
+            outcome: Outcome::Pending,
+            ticks_left: self.timeout_ticks,
+        });
+        self.next_id = self.next_id.wrapping_add(1);
+
+        Ok((id, DataT::Request(RequestDT { id, command })))
+    }
+
+    /// Stops tracking the request `id`, e.g. because it could not be sent.
+    pub fn cancel(&mut self, id: RequestId) {
+        for slot in self.slots.iter_mut() {
+            if matches!(slot, Some(s) if s.id == id) {
+                *slot = None;
+            }
+        }
+    }
+
+    /// Records the answer to a pending request. Returns `false` if no request
+    /// waits for it (it timed out or the answer is a duplicate).
+    pub fn on_response(&mut self, response: ResponseDT) -> bool {
+        for s in self.slots.iter_mut().flatten() {
+            if s.id == response.id && s.outcome == Outcome::Pending {
+                s.outcome = Outcome::Answered(response.result);
+                return true;
+            }
+        }
+
+        false
+    }
+
+    /// Ages the pending requests, it should be called once per tick. Returns
+    /// how many timed out.
+    pub fn tick(&mut self) -> usize {
+        let mut timed_out: usize = 0;
+
+        for s in self.slots.iter_mut().flatten() {
+            if s.outcome != Outcome::Pending {
+                continue;
+            }
+
+            if s.ticks_left == 0 {
+                s.outcome = Outcome::TimedOut;
+                timed_out += 1;
+            } else {
+                s.ticks_left -= 1;
+            }
+        }
+
+        timed_out
+    }
+
+    /// Outcome of the request `id`, a finished request is forgotten once its
+    /// outcome was returned. `None` if the request is not tracked.
+    pub fn poll(&mut self, id: RequestId) -> Option<Outcome> {
+        let slot: &mut Option<SlotT> = self
+            .slots
+            .iter_mut()
+            .find(|s| matches!(s, Some(s) if s.id == id))?;
+
+        match slot {
+            Some(s) if s.outcome == Outcome::Pending => Some(Outcome::Pending),
+            _ => slot.take().map(|s| s.outcome),
+        }
+    }
+
+    /// Returns one of the finished requests (answered or timed out) with its
+    /// command and forgets it, `None` if all are pending.
+    pub fn take_finished(&mut self) -> Option<(RequestId, CommandT, Outcome)> {
+        let slot: &mut Option<SlotT> = self
+            .slots
+            .iter_mut()
+            .find(|s| matches!(s, Some(s) if s.outcome != Outcome::Pending))?;
+
+        slot.take().map(|s| (s.id, s.command, s.outcome))
+    }
+
+    /// Number of requests waiting for an answer.
+    pub fn pending(&self) -> usize {
+        self.slots
+            .iter()
+            .flatten()
+            .filter(|s| s.outcome == Outcome::Pending)
+            .count()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::io::requests::*;
+    use crate::protocol::WarningDT;
+    use crate::DroneMode;
+
+    #[test]
+    fn test_response_matches_request() {
+        let mut requests: Requests<4> = Requests::new(10);
+
+        let (first, data) = requests.start(CommandT::StartLogging).unwrap();
+        assert_eq!(
+            data,
+            DataT::Request(RequestDT {
+                id: first,
+                command: CommandT::StartLogging
+            })
+        );
+        let (second, _) = requests.start(CommandT::Mode(DroneMode::Manual)).unwrap();
+        assert_ne!(first, second);
+        assert_eq!(requests.pending(), 2);
+
+        // answered out of order
+        let rejected: ResultT = ResultT::Rejected(WarningDT::ControlNotNeutral);
+        assert!(requests.on_response(ResponseDT {
+            id: second,
+            result: rejected.clone(),
+        }));
+        assert_eq!(requests.poll(first), Some(Outcome::Pending));
+        assert_eq!(requests.poll(second), Some(Outcome::Answered(rejected)));
+
+        // the outcome is returned once
+        assert_eq!(requests.poll(second), None);
+        assert_eq!(requests.pending(), 1);
+    }
+
+    #[test]
+    fn test_unknown_and_duplicated_responses() {
+        let mut requests: Requests<4> = Requests::new(10);
+        let (id, _) = requests.start(CommandT::StopLogging).unwrap();
+
+        let response: ResponseDT = ResponseDT {
+            id,
+            result: ResultT::Ok,
+        };
+        assert!(!requests.on_response(ResponseDT {
+            id: id.wrapping_add(1),
+            result: ResultT::Ok,
+        }));
+        assert!(requests.on_response(response.clone()));
+        assert!(!requests.on_response(response));
+    }
+
+    #[test]
+    fn test_timeout() {
+        let mut requests: Requests<4> = Requests::new(2);
+        let (id, _) = requests
+            .start(CommandT::Mode(DroneMode::Calibrate))
+            .unwrap();
+
+        assert_eq!(requests.tick(), 0);
+        assert_eq!(requests.tick(), 0);
+        assert_eq!(requests.poll(id), Some(Outcome::Pending));
+        assert_eq!(requests.tick(), 1);
+
+        // a late answer does not change the outcome
+        assert!(!requests.on_response(ResponseDT {
+            id,
+            result: ResultT::Ok,
+        }));
+        assert_eq!(
+            requests.take_finished(),
+            Some((id, CommandT::Mode(DroneMode::Calibrate), Outcome::TimedOut))
+        );
+        assert_eq!(requests.take_finished(), None);
+    }
+
+    #[test]
+    fn test_slots_are_bounded() {
+        let mut requests: Requests<2> = Requests::new(10);
+        let (id, _) = requests.start(CommandT::StartLogReporting).unwrap();
+        requests.start(CommandT::StopLogReporting).unwrap();
+
+        assert!(matches!(
+            requests.start(CommandT::StartLogging),
+            Err(ComErr::WindowFull)
+        ));
+
+        // a request that could not be sent frees its slot
+        requests.cancel(id);
+        assert!(requests.start(CommandT::StartLogging).is_ok());
+    }
+}
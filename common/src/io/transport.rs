@@ -0,0 +1,121 @@
+/// Byte stream `ComT` reads frames from and writes frames to.
+pub trait Transport {
+    /// Reads the bytes available into `buf`. It may wait for them up to a
+    /// short timeout (e.g. the read timeout of a serial port) but never
+    /// longer, the callers read again later. Returns how many were read (0
+    /// if none arrived in time).
+    fn read(&mut self, buf: &mut [u8]) -> usize;
+
+    /// Writes the whole `data`. Returns `false` if it could not (e.g. the
+    /// TX buffer is full), the frame is then not sent at all.
+    fn write(&mut self, data: &[u8]) -> bool;
+
+    /// Pushes out the bytes still buffered by the transport, if any.
+    fn flush(&mut self) {}
+}
+
+/// Adapter for a pair of plain functions, like the ones of
+/// `tudelft_quadrupel::uart`.
+pub struct FnTransport {
+    read_f: fn(&mut [u8]) -> usize,
+    send_f: fn(&[u8]) -> bool,
+}
+
+impl FnTransport {
+    pub fn new(read_f: fn(&mut [u8]) -> usize, send_f: fn(&[u8]) -> bool) -> Self {
+        Self { read_f, send_f }
+    }
+}
+
+impl Transport for FnTransport {
+    #[inline]
+    fn read(&mut self, buf: &mut [u8]) -> usize {
+        (self.read_f)(buf)
+    }
+
+    #[inline]
+    fn write(&mut self, data: &[u8]) -> bool {
+        (self.send_f)(data)
+    }
+}
+
+/// In-memory transport where everything written is read back, to test `ComT`
+/// without a serial port. It holds at most `CAP` bytes, writes that do not fit
+/// fail like a full TX buffer.
+pub struct Loopback<const CAP: usize> {
+    buffer: heapless::Deque<u8, CAP>,
+}
+
+impl<const CAP: usize> Loopback<CAP> {
+    pub fn new() -> Self {
+        Self {
+            buffer: heapless::Deque::new(),
+        }
+    }
+
+    /// Number of bytes waiting to be read.
+    pub fn len(&self) -> usize {
+        self.buffer.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buffer.is_empty()
+    }
+}
+
+impl<const CAP: usize> Default for Loopback<CAP> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const CAP: usize> Transport for Loopback<CAP> {
+    fn read(&mut self, buf: &mut [u8]) -> usize {
+        let mut read: usize = 0;
+
+        for byte in buf.iter_mut() {
+            match self.buffer.pop_front() {
+                Some(val) => *byte = val,
+                None => break,
+            }
+            read += 1;
+        }
+
+        read
+    }
+
+    fn write(&mut self, data: &[u8]) -> bool {
+        if CAP - self.buffer.len() < data.len() {
+            return false;
+        }
+
+        for byte in data {
+            // there is enough space, checked above
+            let _ = self.buffer.push_back(*byte);
+        }
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::io::transport::*;
+
+    #[test]
+    fn test_loopback() {
+        let mut lo: Loopback<8> = Loopback::new();
+
+        assert!(lo.write(&[1, 2, 3]));
+        assert!(!lo.write(&[0; 6]));
+        assert_eq!(lo.len(), 3);
+
+        let mut buf: [u8; 2] = [0; 2];
+        assert_eq!(lo.read(&mut buf), 2);
+        assert_eq!(buf, [1, 2]);
+        assert_eq!(lo.read(&mut buf), 1);
+        assert_eq!(buf[0], 3);
+        assert_eq!(lo.read(&mut buf), 0);
+        assert!(lo.is_empty());
+    }
+}
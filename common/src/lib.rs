@@ -2,9 +2,12 @@
 
 use serde::{Deserialize, Serialize};
 
+pub mod drone_link;
 pub mod io;
 pub mod motor_control;
+pub mod param;
 pub mod protocol;
+pub mod telemetry;
 pub mod uart_com;
 pub mod utility;
 
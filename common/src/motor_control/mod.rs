@@ -38,9 +38,118 @@ pub enum MappingError {
     InputOutOfBounds,
 }
 
+/// Share of the roll, pitch and yaw modifiers a motor gets, in percent. A
+/// positive roll tilts the drone to the right, a positive pitch lifts the
+/// nose and a positive yaw speeds up the motors spinning clockwise.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MotorMix {
+    pub roll: i8,
+    pub pitch: i8,
+    pub yaw: i8,
+}
+
+impl MotorMix {
+    const fn new(roll: i8, pitch: i8, yaw: i8) -> Self {
+        Self { roll, pitch, yaw }
+    }
+
+    // the modifier of this motor
+    fn apply(&self, roll_modif: i32, pitch_modif: i32, yaw_modif: i32) -> i32 {
+        (roll_modif * self.roll as i32
+            + pitch_modif * self.pitch as i32
+            + yaw_modif * self.yaw as i32)
+            / 100
+    }
+}
+
+/// Layout of the motors around the body of the drone.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FrameT {
+    Plus, // motors front, right, back and left
+    X,    // motors front right, back right, back left and front left
+}
+
+impl FrameT {
+    pub const ALL: [FrameT; 2] = [FrameT::Plus, FrameT::X];
+
+    pub fn from_index(index: u32) -> Option<FrameT> {
+        FrameT::ALL.get(index as usize).copied()
+    }
+}
+
+/// How the commands are mixed into the motors, one `MotorMix` per motor in
+/// the order of the motor outputs (`ae1` to `ae4`).
+///
+/// The presets number the motors clockwise seen from above, `ae1` first
+/// spinning clockwise. Custom frames can set the coefficients directly.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MixerConfig {
+    pub motors: [MotorMix; 4],
+}
+
+impl MixerConfig {
+    /// + frame, `ae1` at the front.
+    pub const PLUS: MixerConfig = MixerConfig {
+        motors: [
+            MotorMix::new(0, 100, 100),
+            MotorMix::new(-100, 0, -100),
+            MotorMix::new(0, -100, 100),
+            MotorMix::new(100, 0, -100),
+        ],
+    };
+
+    /// X frame, `ae1` at the front right.
+    pub const X: MixerConfig = MixerConfig {
+        motors: [
+            MotorMix::new(-100, 100, 100),
+            MotorMix::new(-100, -100, -100),
+            MotorMix::new(100, -100, 100),
+            MotorMix::new(100, 100, -100),
+        ],
+    };
+
+    /// Frame and prop directions chosen at build time, with the `frame-x` and
+    /// `reversed-props` features.
+    pub const DEFAULT: MixerConfig = MixerConfig::preset(DEFAULT_FRAME, DEFAULT_PROPS_REVERSED);
+
+    /// Mixer of `frame`, with `ae1` spinning counter-clockwise if
+    /// `reversed_props`.
+    pub const fn preset(frame: FrameT, reversed_props: bool) -> MixerConfig {
+        let mixer: MixerConfig = match frame {
+            FrameT::Plus => MixerConfig::PLUS,
+            FrameT::X => MixerConfig::X,
+        };
+
+        if reversed_props {
+            mixer.reversed()
+        } else {
+            mixer
+        }
+    }
+
+    /// Same frame with every prop spinning the other way.
+    pub const fn reversed(self) -> MixerConfig {
+        let mut motors: [MotorMix; 4] = self.motors;
+        let mut i: usize = 0;
+        while i < motors.len() {
+            motors[i].yaw = -motors[i].yaw;
+            i += 1;
+        }
+
+        MixerConfig { motors }
+    }
+}
+
+#[cfg(feature = "frame-x")]
+pub const DEFAULT_FRAME: FrameT = FrameT::X;
+#[cfg(not(feature = "frame-x"))]
+pub const DEFAULT_FRAME: FrameT = FrameT::Plus;
+
+pub const DEFAULT_PROPS_REVERSED: bool = cfg!(feature = "reversed-props");
+
 /// Input is in range [0, 2048]
 /// Output has to be in range [0, MAX_THRUST]
-pub fn motor_mapping(input: [u16; 4]) -> Result<[u16; 4], MappingError> {
+pub fn motor_mapping(input: [u16; 4], mixer: &MixerConfig) -> Result<[u16; 4], MappingError> {
     for i in input {
         if i > MAX_INPUT_COMMAND {
             return Err(MappingError::InputOutOfBounds);
@@ -74,13 +183,12 @@ pub fn motor_mapping(input: [u16; 4]) -> Result<[u16; 4], MappingError> {
     //     throttle, roll_modif, pitch_modif, yaw_modif
     // );
 
-    let ae1: i32 = throttle + pitch_modif + yaw_modif;
-    let ae2: i32 = throttle - roll_modif - yaw_modif;
-    let ae3: i32 = throttle - pitch_modif + yaw_modif;
-    let ae4: i32 = throttle + roll_modif - yaw_modif;
+    let mut mapping: [i32; 4] = [throttle; 4];
+    for (ae, motor) in mapping.iter_mut().zip(mixer.motors.iter()) {
+        *ae += motor.apply(roll_modif, pitch_modif, yaw_modif);
+    }
 
     // Convert the values back to u16
-    let mapping: [i32; 4] = [ae1, ae2, ae3, ae4];
     let mut converted: [u16; 4] = [0; 4];
 
     // First make sure all the values are positive
@@ -196,13 +304,16 @@ mod test {
     fn test_zero_state() {
         let zero_state: [u16; 4] = [0, 1024, 1024, 1024];
         let expected: [u16; 4] = [0, 0, 0, 0];
-        assert_eq!(motor_mapping(zero_state).unwrap(), expected);
+        assert_eq!(
+            motor_mapping(zero_state, &MixerConfig::PLUS).unwrap(),
+            expected
+        );
     }
 
     #[test]
     fn test_hover() {
         let zero_state: [u16; 4] = [2047, 1024, 1024, 1024];
-        let result = motor_mapping(zero_state).unwrap();
+        let result = motor_mapping(zero_state, &MixerConfig::PLUS).unwrap();
         assert_eq!(result[0], result[1]);
         assert_eq!(result[1], result[2]);
         assert_eq!(result[2], result[3]);
@@ -211,7 +322,7 @@ mod test {
     #[test]
     fn test_roll_left() {
         let state: [u16; 4] = [1024, 0, 1024, 1024];
-        let mapping: [u16; 4] = motor_mapping(state).unwrap();
+        let mapping: [u16; 4] = motor_mapping(state, &MixerConfig::PLUS).unwrap();
         assert!(mapping[1] > mapping[3]);
         assert_eq!(mapping[0], mapping[2]);
     }
@@ -219,7 +330,7 @@ mod test {
     #[test]
     fn test_roll_right() {
         let state: [u16; 4] = [1024, 2047, 1024, 1024];
-        let mapping: [u16; 4] = motor_mapping(state).unwrap();
+        let mapping: [u16; 4] = motor_mapping(state, &MixerConfig::PLUS).unwrap();
         assert!(mapping[1] < mapping[3]);
         assert_eq!(mapping[0], mapping[2]);
     }
@@ -227,7 +338,7 @@ mod test {
     #[test]
     fn test_pitch_forward() {
         let state: [u16; 4] = [1024, 1024, 0, 1024];
-        let mapping: [u16; 4] = motor_mapping(state).unwrap();
+        let mapping: [u16; 4] = motor_mapping(state, &MixerConfig::PLUS).unwrap();
         assert!(mapping[0] < mapping[2]);
         assert_eq!(mapping[1], mapping[3]);
     }
@@ -235,7 +346,7 @@ mod test {
     #[test]
     fn test_pitch_backward() {
         let state: [u16; 4] = [1024, 1024, 2047, 1024];
-        let mapping: [u16; 4] = motor_mapping(state).unwrap();
+        let mapping: [u16; 4] = motor_mapping(state, &MixerConfig::PLUS).unwrap();
         assert!(mapping[0] > mapping[2]);
         assert_eq!(mapping[1], mapping[3]);
     }
@@ -243,7 +354,7 @@ mod test {
     #[test]
     fn test_turn_diag_fl() {
         let state: [u16; 4] = [1024, 0, 0, 1024];
-        let mapping: [u16; 4] = motor_mapping(state).unwrap();
+        let mapping: [u16; 4] = motor_mapping(state, &MixerConfig::PLUS).unwrap();
         assert!(mapping[0] < mapping[2]);
         assert!(mapping[1] > mapping[3]);
     }
@@ -251,7 +362,7 @@ mod test {
     #[test]
     fn test_turn_diag_fr() {
         let state: [u16; 4] = [1024, 2047, 0, 1024];
-        let mapping: [u16; 4] = motor_mapping(state).unwrap();
+        let mapping: [u16; 4] = motor_mapping(state, &MixerConfig::PLUS).unwrap();
         assert!(mapping[0] < mapping[2]);
         assert!(mapping[1] < mapping[3]);
     }
@@ -259,7 +370,7 @@ mod test {
     #[test]
     fn test_turn_diag_br() {
         let state: [u16; 4] = [1024, 2047, 2047, 1024];
-        let mapping: [u16; 4] = motor_mapping(state).unwrap();
+        let mapping: [u16; 4] = motor_mapping(state, &MixerConfig::PLUS).unwrap();
         assert!(mapping[0] > mapping[2]);
         assert!(mapping[1] < mapping[3]);
     }
@@ -267,7 +378,7 @@ mod test {
     #[test]
     fn test_turn_diag_bl() {
         let state: [u16; 4] = [1024, 0, 2047, 1024];
-        let mapping: [u16; 4] = motor_mapping(state).unwrap();
+        let mapping: [u16; 4] = motor_mapping(state, &MixerConfig::PLUS).unwrap();
         assert!(mapping[0] > mapping[2]);
         assert!(mapping[1] > mapping[3]);
     }
@@ -277,25 +388,148 @@ mod test {
         let expected: [u16; 4] = [0, 0, 0, 0];
 
         let mut random_state: [u16; 4] = [0, 0, 0, 0];
-        assert_eq!(motor_mapping(random_state).unwrap(), expected);
+        assert_eq!(
+            motor_mapping(random_state, &MixerConfig::PLUS).unwrap(),
+            expected
+        );
 
         random_state = [0, 2047, 0, 0];
-        assert_eq!(motor_mapping(random_state).unwrap(), expected);
+        assert_eq!(
+            motor_mapping(random_state, &MixerConfig::PLUS).unwrap(),
+            expected
+        );
 
         random_state = [0, 2047, 2047, 0];
-        assert_eq!(motor_mapping(random_state).unwrap(), expected);
+        assert_eq!(
+            motor_mapping(random_state, &MixerConfig::PLUS).unwrap(),
+            expected
+        );
 
         random_state = [0, 0, 2047, 0];
-        assert_eq!(motor_mapping(random_state).unwrap(), expected);
+        assert_eq!(
+            motor_mapping(random_state, &MixerConfig::PLUS).unwrap(),
+            expected
+        );
 
         random_state = [0, 2047, 1000, 1000];
-        assert_eq!(motor_mapping(random_state).unwrap(), expected);
+        assert_eq!(
+            motor_mapping(random_state, &MixerConfig::PLUS).unwrap(),
+            expected
+        );
     }
 
     #[test]
     fn test_input_out_of_bounds() {
         let expected = MappingError::InputOutOfBounds;
         let random_state: [u16; 4] = [2048, 0, 0, 0];
-        assert_eq!(motor_mapping(random_state).err().unwrap(), expected);
+        assert_eq!(
+            motor_mapping(random_state, &MixerConfig::PLUS)
+                .err()
+                .unwrap(),
+            expected
+        );
+    }
+
+    // mapping of a command on one axis with half the throttle
+    fn mix(mixer: &MixerConfig, roll: u16, pitch: u16, yaw: u16) -> [u16; 4] {
+        motor_mapping([1024, roll, pitch, yaw], mixer).unwrap()
+    }
+
+    #[test]
+    fn test_plus_preset() {
+        let mixer: MixerConfig = MixerConfig::preset(FrameT::Plus, false);
+        assert_eq!(mixer, MixerConfig::PLUS);
+        let hover: [u16; 4] = mix(&mixer, 1024, 1024, 1024);
+
+        // roll right: left motor up, right motor down
+        let roll: [u16; 4] = mix(&mixer, 2047, 1024, 1024);
+        assert!(roll[3] > hover[3] && roll[1] < hover[1]);
+        assert_eq!([roll[0], roll[2]], [hover[0], hover[2]]);
+
+        // nose up: front motor up, back motor down
+        let pitch: [u16; 4] = mix(&mixer, 1024, 2047, 1024);
+        assert!(pitch[0] > hover[0] && pitch[2] < hover[2]);
+        assert_eq!([pitch[1], pitch[3]], [hover[1], hover[3]]);
+
+        let yaw: [u16; 4] = mix(&mixer, 1024, 1024, 2047);
+        assert!(yaw[0] > hover[0] && yaw[2] > hover[2]);
+        assert!(yaw[1] < hover[1] && yaw[3] < hover[3]);
+    }
+
+    #[test]
+    fn test_x_preset() {
+        let mixer: MixerConfig = MixerConfig::preset(FrameT::X, false);
+        assert_eq!(mixer, MixerConfig::X);
+        let hover: [u16; 4] = mix(&mixer, 1024, 1024, 1024);
+        assert!(hover.iter().all(|ae| *ae == hover[0]));
+
+        // roll right: both left motors up, both right motors down
+        let roll: [u16; 4] = mix(&mixer, 2047, 1024, 1024);
+        assert!(roll[2] > hover[2] && roll[3] > hover[3]);
+        assert!(roll[0] < hover[0] && roll[1] < hover[1]);
+
+        // nose up: both front motors up, both back motors down
+        let pitch: [u16; 4] = mix(&mixer, 1024, 2047, 1024);
+        assert!(pitch[0] > hover[0] && pitch[3] > hover[3]);
+        assert!(pitch[1] < hover[1] && pitch[2] < hover[2]);
+
+        // the diagonals spin the same way
+        let yaw: [u16; 4] = mix(&mixer, 1024, 1024, 2047);
+        assert!(yaw[0] > hover[0] && yaw[2] > hover[2]);
+        assert!(yaw[1] < hover[1] && yaw[3] < hover[3]);
+
+        // a diagonal command only moves the motors on the other diagonal
+        let diagonal: [u16; 4] = mix(&mixer, 2047, 2047, 1024);
+        assert_eq!([diagonal[0], diagonal[2]], [hover[0], hover[2]]);
+        assert!(diagonal[3] > hover[3] && diagonal[1] < hover[1]);
+    }
+
+    #[test]
+    fn test_reversed_props() {
+        for frame in FrameT::ALL {
+            let normal: MixerConfig = MixerConfig::preset(frame, false);
+            let reversed: MixerConfig = MixerConfig::preset(frame, true);
+            assert_eq!(reversed, normal.reversed());
+            assert_eq!(reversed.reversed(), normal);
+
+            // roll and pitch are unchanged, yaw is mirrored
+            for (roll, pitch) in [(2047, 1024), (1024, 2047)] {
+                assert_eq!(
+                    mix(&reversed, roll, pitch, 1024),
+                    mix(&normal, roll, pitch, 1024)
+                );
+            }
+            let yaw: [u16; 4] = mix(&reversed, 1024, 1024, 2047);
+            let mirrored: [u16; 4] = mix(&normal, 1024, 1024, 0);
+            assert_eq!(yaw, mirrored, "{:?}", frame);
+        }
+    }
+
+    #[test]
+    fn test_custom_mixer() {
+        // a single motor taking the whole pitch, e.g. a tricopter tail
+        let mut mixer: MixerConfig = MixerConfig::PLUS;
+        mixer.motors[2] = MotorMix {
+            roll: 0,
+            pitch: -50,
+            yaw: 0,
+        };
+
+        let plus: [u16; 4] = mix(&MixerConfig::PLUS, 1024, 2047, 1024);
+        let custom: [u16; 4] = mix(&mixer, 1024, 2047, 1024);
+        let hover: [u16; 4] = mix(&mixer, 1024, 1024, 1024);
+        assert_eq!(custom[0], plus[0]);
+        assert_eq!(hover[2] - custom[2], (hover[2] - plus[2]) / 2);
+    }
+
+    #[test]
+    fn test_frame_index() {
+        assert_eq!(FrameT::from_index(0), Some(FrameT::Plus));
+        assert_eq!(FrameT::from_index(1), Some(FrameT::X));
+        assert_eq!(FrameT::from_index(2), None);
+        assert_eq!(
+            MixerConfig::DEFAULT,
+            MixerConfig::preset(DEFAULT_FRAME, DEFAULT_PROPS_REVERSED)
+        );
     }
 }
@@ -0,0 +1,185 @@
+use crate::protocol::{ParamId, ParamInfoDT, ParamType, ParamValue};
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ParamErr {
+    Unknown,    // no parameter with this id
+    WrongType,  // the value is of another type than the parameter
+    OutOfRange, // the value is outside of `min..=max`
+}
+
+/// A parameter of `S`: how it is called on the wire, its bounds and default,
+/// and how it is read from and written to `S`.
+///
+/// `set` is only called with values that passed `check`, so it may assume the
+/// type and the bounds of the parameter.
+pub struct ParamDef<S> {
+    pub id: ParamId,
+    pub name: &'static str,
+    pub default: ParamValue,
+    pub min: ParamValue,
+    pub max: ParamValue,
+    // can only be changed in safe mode, e.g. the layout of the motors
+    pub safe_only: bool,
+
+    pub get: fn(&S) -> ParamValue,
+    pub set: fn(&mut S, ParamValue),
+}
+
+impl<S> ParamDef<S> {
+    pub fn kind(&self) -> ParamType {
+        self.default.kind()
+    }
+
+    /// Checks that `value` has the type of the parameter and is within its
+    /// bounds.
+    pub fn check(&self, value: ParamValue) -> Result<(), ParamErr> {
+        let in_range: bool = match (value, self.min, self.max) {
+            (ParamValue::Bool(_), ParamValue::Bool(_), ParamValue::Bool(_)) => true,
+            (ParamValue::U32(v), ParamValue::U32(min), ParamValue::U32(max)) => {
+                min <= v && v <= max
+            }
+            (ParamValue::Fixed(v), ParamValue::Fixed(min), ParamValue::Fixed(max)) => {
+                min <= v && v <= max
+            }
+            _ => return Err(ParamErr::WrongType),
+        };
+
+        if in_range {
+            Ok(())
+        } else {
+            Err(ParamErr::OutOfRange)
+        }
+    }
+
+    pub fn get(&self, owner: &S) -> ParamValue {
+        (self.get)(owner)
+    }
+
+    /// Writes `value` to `owner` if it passes `check`.
+    pub fn set(&self, owner: &mut S, value: ParamValue) -> Result<(), ParamErr> {
+        self.check(value)?;
+        (self.set)(owner, value);
+        Ok(())
+    }
+
+    /// Writes the default value to `owner`.
+    pub fn reset(&self, owner: &mut S) {
+        (self.set)(owner, self.default);
+    }
+
+    /// The definition as sent to the runner. Names longer than
+    /// `PARAM_NAME_CAP` are cut.
+    pub fn info(&self) -> ParamInfoDT {
+        let mut name: heapless::String<{ crate::protocol::PARAM_NAME_CAP }> =
+            heapless::String::new();
+        for c in self.name.chars() {
+            if name.push(c).is_err() {
+                break;
+            }
+        }
+
+        ParamInfoDT {
+            id: self.id,
+            name,
+            default: self.default,
+            min: self.min,
+            max: self.max,
+        }
+    }
+}
+
+/// Looks for the parameter `id` in `params`.
+pub fn find<S>(params: &[ParamDef<S>], id: ParamId) -> Result<&ParamDef<S>, ParamErr> {
+    match params.iter().find(|p| p.id == id) {
+        Some(param) => Ok(param),
+        None => Err(ParamErr::Unknown),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::param::*;
+    use fixed::types::I16F16;
+
+    struct Owner {
+        gain: I16F16,
+        period: u32,
+    }
+
+    const PARAMS: [ParamDef<Owner>; 2] = [
+        ParamDef {
+            id: 0,
+            name: "gain",
+            default: ParamValue::Fixed(I16F16::from_bits(5 << 16)),
+            min: ParamValue::Fixed(I16F16::ZERO),
+            max: ParamValue::Fixed(I16F16::from_bits(10 << 16)),
+            safe_only: false,
+            get: |o| ParamValue::Fixed(o.gain),
+            set: |o, v| {
+                if let ParamValue::Fixed(v) = v {
+                    o.gain = v;
+                }
+            },
+        },
+        ParamDef {
+            id: 7,
+            name: "a_rather_long_period",
+            default: ParamValue::U32(20),
+            min: ParamValue::U32(1),
+            max: ParamValue::U32(100),
+            safe_only: false,
+            get: |o| ParamValue::U32(o.period),
+            set: |o, v| {
+                if let ParamValue::U32(v) = v {
+                    o.period = v;
+                }
+            },
+        },
+    ];
+
+    #[test]
+    fn test_set_and_get() {
+        let mut owner: Owner = Owner {
+            gain: I16F16::ZERO,
+            period: 0,
+        };
+        for param in PARAMS.iter() {
+            param.reset(&mut owner);
+        }
+        assert_eq!(owner.gain, I16F16::from_num(5));
+        assert_eq!(owner.period, 20);
+
+        let period = find(&PARAMS, 7).unwrap();
+        assert_eq!(period.set(&mut owner, ParamValue::U32(40)), Ok(()));
+        assert_eq!(period.get(&owner), ParamValue::U32(40));
+        assert_eq!(find(&PARAMS, 3).err(), Some(ParamErr::Unknown));
+    }
+
+    #[test]
+    fn test_rejected_values() {
+        let mut owner: Owner = Owner {
+            gain: I16F16::ZERO,
+            period: 3,
+        };
+        let period = find(&PARAMS, 7).unwrap();
+
+        assert_eq!(
+            period.set(&mut owner, ParamValue::U32(0)),
+            Err(ParamErr::OutOfRange)
+        );
+        assert_eq!(
+            period.set(&mut owner, ParamValue::Fixed(I16F16::from_num(4))),
+            Err(ParamErr::WrongType)
+        );
+        assert_eq!(owner.period, 3);
+    }
+
+    #[test]
+    fn test_info() {
+        let info: ParamInfoDT = PARAMS[1].info();
+
+        assert_eq!(info.name.as_str(), "a_rather_lon");
+        assert_eq!(info.default.kind(), ParamType::U32);
+        assert_eq!(PARAMS[0].kind(), ParamType::Fixed);
+    }
+}
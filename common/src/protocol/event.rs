@@ -0,0 +1,74 @@
+//! text of the events of the drone, made where it is shown
+
+use core::fmt;
+
+use crate::io::ComErr;
+use crate::protocol::{EventCodeT, EventDT};
+
+impl fmt::Display for EventDT {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let [a, b] = self.args;
+
+        write!(f, "{:?}: ", self.source)?;
+        match self.code {
+            EventCodeT::SendFailed => write!(f, "sending failed ({})", ComErr::name_of(a)),
+            EventCodeT::ReceiveFailed => write!(f, "receiving failed ({})", ComErr::name_of(a)),
+
+            EventCodeT::LogStarted => write!(f, "logging started"),
+            EventCodeT::LogStopped => write!(f, "logging stopped after {} bytes", a),
+            EventCodeT::LogFull => write!(f, "log full after {} bytes, logging stopped", a),
+            EventCodeT::LogStopFailed => write!(f, "the end of the log could not be written"),
+            EventCodeT::ReportStarted => write!(f, "log report of {} bytes started", a),
+            EventCodeT::ReportStopped => write!(f, "log report stopped after {} bytes", a),
+            EventCodeT::ReportStartFailed => write!(f, "the end of the log could not be read"),
+            EventCodeT::ReportReadFailed => {
+                write!(f, "reading the log at {:#x} failed, report stopped", a)
+            }
+
+            EventCodeT::Calibrated => write!(f, "sensors calibrated"),
+
+            EventCodeT::DeadlineOverrun => write!(
+                f,
+                "{} tick(s) missed their deadline, worst took {} us",
+                a, b
+            ),
+
+            EventCodeT::BatteryLow => write!(f, "battery low ({}.{:02} V)", a / 100, a % 100),
+            EventCodeT::BatteryCritical => {
+                write!(f, "battery critical ({}.{:02} V)", a / 100, a % 100)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use alloc::string::ToString;
+
+    use crate::io::ComErr;
+    use crate::protocol::*;
+
+    #[test]
+    fn test_event_text() {
+        let full: EventDT = EventDT::new(EventCodeT::LogFull, [4096, 0]);
+        assert_eq!(
+            full.to_string(),
+            "Flash: log full after 4096 bytes, logging stopped"
+        );
+
+        let battery: EventDT = EventDT::new(EventCodeT::BatteryCritical, [1049, 0]);
+        assert_eq!(battery.to_string(), "Battery: battery critical (10.49 V)");
+
+        let send: EventDT = EventDT::new(EventCodeT::SendFailed, [ComErr::Busy.code(), 0]);
+        assert_eq!(send.to_string(), "Comms: sending failed (transport busy)");
+        assert!(send.code.is_error());
+        assert!(!EventCodeT::Calibrated.is_error());
+
+        let overrun: EventDT = EventDT::new(EventCodeT::DeadlineOverrun, [3, 12_500]);
+        assert_eq!(
+            overrun.to_string(),
+            "Timing: 3 tick(s) missed their deadline, worst took 12500 us"
+        );
+        assert!(overrun.code.is_error());
+    }
+}
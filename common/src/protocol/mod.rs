@@ -1,30 +1,73 @@
 use serde::{Deserialize, Serialize};
 
+use crate::utility::hash::fnv1a_32;
 use crate::{uart_com, DroneMode};
 use fixed::types::I16F16;
 
+mod event;
+// host tooling, kept out of the firmware
+#[cfg(any(test, feature = "schema"))]
+pub mod schema;
+
 pub const DEFAULT_CAP: usize = 32;
 
+/// Bytes of a large payload carried by one `FragmentDT`. A fragment frame fits
+/// in the 64 bytes of the drone buffers even if all of it gets escaped, with
+/// every checksum and encoding.
+pub const FRAGMENT_SIZE: usize = 20;
+
+/// Bytes of serialized messages carried by one `BatchDT`. Like a fragment, a
+/// batch frame fits in the 64 bytes of the drone buffers even if all of it
+/// gets escaped.
+pub const BATCH_SIZE: usize = 24;
+
+/// Room for encoded samples in one `SensorStreamDT`, an escaped byte counts
+/// twice (see `telemetry::SensorEncoder`). So a sensor stream frame fits in
+/// the 64 bytes of the drone buffers.
+pub const SENSOR_STREAM_SIZE: usize = 44;
+
+/// Capacity of the payloads of `LargeDataT`.
+pub const LARGE_CAP: usize = 256;
+
+/// Room for a serialized `LargeDataT` (the payload, its length and the tag).
+pub const LARGE_SERIALIZED_CAP: usize = LARGE_CAP + 8;
+
+/// Version of the protocol, bump it on every change of the messages and refresh
+/// the exported description of them (see `schema`).
+pub const PROTOCOL_VERSION: u16 = 16;
+
+/// Hash of the exported description of the messages (see `schema`), so only
+/// changes of the wire format matter. Binaries built from different
+/// definitions disagree on it even if someone forgot to bump
+/// `PROTOCOL_VERSION`. The schema tests fail while the exported description
+/// is out of date.
+pub const SCHEMA_HASH: u32 = fnv1a_32(include_str!("../../protocol_schema.json").as_bytes());
+
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub enum DataT {
+    // Duplex, MUST stay the first variant so that its encoding never changes
+    Handshake(HandshakeDT),
+
     // PC -> Drone
     Control(ControlDT),
     Mode(DroneMode),
-    Tuning(TuningDT),
 
     // Drone -> PC
     SensorData(SensorDT),
     HealthData(HealthDT),
     MotorsState(MotorsDT),
+    LinkStats(LinkStatsDT), // link health as seen by the drone
 
     // Error warning types used
     Warning(WarningDT),
-    //Error(ErrorDT),
+    Event(EventDT), // Drone -> PC, errors and notable events (see `EventCodeT`)
 
     // Duplex
     Message(heapless::String<DEFAULT_CAP>),
-    AckNack(u8), // 1 means ACK | 0 means NACK
-    KeepAlive,
+    AckNack(AckNackDT),     // answer to a frame sent on the reliable channel
+    KeepAlive,              // PC -> Drone, the runner is still connected
+    Heartbeat(HeartbeatDT), // Drone -> PC, the whole status of the drone
+    TimeSync(TimeSyncDT),   // asked by the runner, answered by the drone with its clock
 
     Empty, // no data, to be used for signaling nothing was read
 
@@ -36,21 +79,82 @@ pub enum DataT {
 
     MovementErrors(CalculatedErrors),
 
-    UpdateP(UpdatePDT),
-    UpdateP1P2(UpdateP1P2DT),
+    // parameters of the drone, see `param`
+    ParamGet(ParamId),        // PC -> Drone, answered with `ParamValue`
+    ParamSet(ParamValueDT),   // PC -> Drone, answered with `ParamValue`
+    ParamValue(ParamValueDT), // Drone -> PC, the current value
+    ParamList,                // PC -> Drone, answered with `ParamInfo` and `ParamValue` for all
+    ParamInfo(ParamInfoDT),   // Drone -> PC, the definition of a parameter
 
     StartLogging,
     StopLogging,
     StartLogReporting,
     StopLogReporting,
     SensorLog(SensorLogDT),
+    SensorStream(SensorStreamDT), // Drone -> PC, compact sensor samples (see `telemetry`)
+
+    // Duplex, a piece of a `LargeDataT` (see `io::Reassembler`)
+    Fragment(FragmentDT),
+
+    // Duplex, several small messages in one frame (see `BatchDT`)
+    Batch(BatchDT),
+
+    // commands whose outcome is reported back (see `RequestDT`)
+    Request(RequestDT),   // PC -> Drone
+    Response(ResponseDT), // Drone -> PC
+
+    // rates of the telemetry streams, see `telemetry::rates`
+    StreamSet(StreamRateDT),    // PC -> Drone, answered with `StreamRates`
+    StreamGet,                  // PC -> Drone, answered with `StreamRates`
+    StreamRates(StreamRatesDT), // Drone -> PC, the rates applied
+}
+
+/// Messages too large for a single frame, they are sent in fragments with
+/// `ComT::send_large_data`.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub enum LargeDataT {
+    Message(heapless::String<LARGE_CAP>),
+}
+
+impl LargeDataT {
+    pub fn to_bytes<const CAP: usize>(&self) -> Result<heapless::Vec<u8, CAP>, uart_com::Error> {
+        match postcard::to_vec::<LargeDataT, CAP>(self) {
+            Ok(serialized) => Ok(serialized),
+            Err(_) => Err(uart_com::Error::ENOMEM),
+        }
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<LargeDataT, uart_com::Error> {
+        match postcard::from_bytes::<LargeDataT>(bytes) {
+            Ok(data) => Ok(data),
+            Err(_) => Err(uart_com::Error::Des),
+        }
+    }
 }
 
 impl DataT {
-    pub fn to_packet<const CAP: usize>(&self) -> Result<heapless::Vec<u8, CAP>, uart_com::Error> {
+    pub fn to_packet<const CAP: usize>(
+        &self,
+        seq: u8,
+    ) -> Result<heapless::Vec<u8, CAP>, uart_com::Error> {
+        self.to_packet_with::<uart_com::DefaultEncoding, CAP>(seq)
+    }
+
+    /// Same as `to_packet`, but the frame is encoded with `E`.
+    pub fn to_packet_with<E: uart_com::Encoding, const CAP: usize>(
+        &self,
+        seq: u8,
+    ) -> Result<heapless::Vec<u8, CAP>, uart_com::Error> {
+        let serialized: heapless::Vec<u8, CAP> = self.to_payload()?;
+
+        uart_com::frame_with::<uart_com::DefaultChecksum, E, CAP>(serialized, seq)
+    }
+
+    /// Serializes the data without framing it.
+    pub fn to_payload<const CAP: usize>(&self) -> Result<heapless::Vec<u8, CAP>, uart_com::Error> {
         match postcard::to_vec::<DataT, CAP>(self) {
             Ok(serialized) => {
-                return uart_com::frame::<CAP>(serialized);
+                return Ok(serialized);
             }
             Err(_) => {
                 return Err(uart_com::Error::ENOMEM);
@@ -58,12 +162,22 @@ impl DataT {
         };
     }
 
+    /// Returns the sequence number of the frame together with the data.
     pub fn from_packet<const CAP: usize>(
         frame: heapless::Vec<u8, CAP>,
-    ) -> Result<DataT, uart_com::Error> {
-        let serialized = uart_com::unframe(frame)?;
+    ) -> Result<(u8, DataT), uart_com::Error> {
+        let mut frame: heapless::Vec<u8, CAP> = frame;
+        let (seq, payload) = uart_com::unframe_in_place::<
+            uart_com::DefaultChecksum,
+            uart_com::DefaultEncoding,
+        >(&mut frame)?;
+
+        return Ok((seq, DataT::from_payload(payload)?));
+    }
 
-        match postcard::from_bytes::<DataT>(&serialized) {
+    /// Deserializes an already unframed payload.
+    pub fn from_payload(payload: &[u8]) -> Result<DataT, uart_com::Error> {
+        match postcard::from_bytes::<DataT>(payload) {
             Ok(data) => {
                 return Ok(data);
             }
@@ -72,17 +186,299 @@ impl DataT {
             }
         };
     }
+
+    /// Critical messages change the state of the other side and must not be
+    /// lost, so they are sent over the reliable channel when it is enabled.
+    pub fn is_critical(&self) -> bool {
+        match self {
+            DataT::Mode(_)
+            | DataT::ParamSet(_)
+            | DataT::StreamSet(_)
+            | DataT::StartLogging
+            | DataT::StopLogging
+            | DataT::StartLogReporting
+            | DataT::StopLogReporting
+            | DataT::Request(_)
+            | DataT::Response(_) => true,
+
+            _ => false,
+        }
+    }
+}
+
+/// Exchanged right after connecting, the runner sends its own and the drone
+/// answers with its own, with the nonce of a new session.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+pub struct HandshakeDT {
+    pub version: u16,
+    pub schema_hash: u32,
+    pub nonce: u64, // issued by the drone, the authenticated frames are bound to it (see `ComT::set_session`)
+}
+
+impl HandshakeDT {
+    /// The handshake describing the protocol this binary was built with,
+    /// without a session.
+    pub fn local() -> Self {
+        Self {
+            version: PROTOCOL_VERSION,
+            schema_hash: SCHEMA_HASH,
+            nonce: crate::io::NO_SESSION,
+        }
+    }
+
+    /// Checks if the other side was built from the same protocol definition.
+    pub fn is_compatible(&self) -> bool {
+        self.version == PROTOCOL_VERSION && self.schema_hash == SCHEMA_HASH
+    }
 }
 
+/// Time on the drone, in microseconds since it started. It wraps around after
+/// about 71 minutes, `utility::clock::ClockSync` maps it to the runner clock.
+pub type Timestamp = u32;
+
+/// Time sync exchange, the drone answers a request with the same
+/// `host_time` and its own clock.
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
-pub struct UpdateP1P2DT {
-    pub p1: I16F16,
-    pub p2: I16F16,
+pub struct TimeSyncDT {
+    pub host_time: u64,        // when the runner sent the request, in its own clock
+    pub drone_time: Timestamp, // when the drone received it, 0 in the request
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct FragmentDT {
+    pub id: u8,                                 // same for all the fragments of a payload
+    pub index: u8,                              // position of the fragment, starting from 0
+    pub count: u8,                              // number of fragments of the payload
+    pub data: heapless::Vec<u8, FRAGMENT_SIZE>, // full, except for the last one
+}
+
+/// Small messages packed in one frame, they share the frame overhead (start
+/// and end bytes, header and checksum). `ComT::read_data` unpacks it and
+/// returns the messages one at a time.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
+pub struct BatchDT {
+    pub data: heapless::Vec<u8, BATCH_SIZE>, // serialized `DataT`s, one after the other
+}
+
+impl BatchDT {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends `data`, returns `false` if it does not fit. Critical messages
+    /// (they need their own sequence number), handshakes (they must be
+    /// readable by any version) and batches are never batched.
+    pub fn push(&mut self, data: &DataT) -> bool {
+        if data.is_critical() || matches!(data, DataT::Handshake(_) | DataT::Batch(_)) {
+            return false;
+        }
+
+        let serialized: heapless::Vec<u8, BATCH_SIZE> =
+            match postcard::to_vec::<DataT, BATCH_SIZE>(data) {
+                Ok(val) => val,
+                Err(_) => return false,
+            };
+
+        self.data.extend_from_slice(&serialized).is_ok()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// Deserializes the message starting at byte `pos`.
+    /// @return the message and the position of the next one, `None` at the end
+    /// of the batch OR error
+    pub fn get(&self, pos: usize) -> Option<Result<(DataT, usize), uart_com::Error>> {
+        if pos >= self.data.len() {
+            return None;
+        }
+
+        match postcard::take_from_bytes::<DataT>(&self.data[pos..]) {
+            Ok((data, rest)) => Some(Ok((data, self.data.len() - rest.len()))),
+            Err(_) => Some(Err(uart_com::Error::Des)),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+pub struct AckNackDT {
+    pub seq: u8,   // sequence number of the acknowledged frame
+    pub ack: bool, // true means ACK | false means NACK (received but rejected)
+}
+
+/// Identifier of a parameter, it never changes once given out.
+pub type ParamId = u8;
+
+/// Longest name of a parameter.
+pub const PARAM_NAME_CAP: usize = 12;
+
+/// Type of a parameter.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+pub enum ParamType {
+    Bool,
+    U32,
+    Fixed,
+}
+
+/// Value of a parameter, tagged with its type.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+pub enum ParamValue {
+    Bool(bool),
+    U32(u32),
+    Fixed(I16F16),
+}
+
+impl ParamValue {
+    pub fn kind(&self) -> ParamType {
+        match self {
+            ParamValue::Bool(_) => ParamType::Bool,
+            ParamValue::U32(_) => ParamType::U32,
+            ParamValue::Fixed(_) => ParamType::Fixed,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            ParamValue::Bool(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    pub fn as_u32(&self) -> Option<u32> {
+        match self {
+            ParamValue::U32(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    pub fn as_fixed(&self) -> Option<I16F16> {
+        match self {
+            ParamValue::Fixed(v) => Some(*v),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+pub struct ParamValueDT {
+    pub id: ParamId,
+    pub value: ParamValue,
+}
+
+/// Definition of a parameter, enough for the runner to show and edit it.
+/// A frame of it stays below the 64 bytes of the drone buffers.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct ParamInfoDT {
+    pub id: ParamId,
+    pub name: heapless::String<PARAM_NAME_CAP>,
+    pub default: ParamValue, // its variant is the type of the parameter
+    pub min: ParamValue,
+    pub max: ParamValue,
+}
+
+/// Identifier of a request, the response carries the same one. They are
+/// handed out by `io::Requests`.
+pub type RequestId = u8;
+
+/// Commands that can be sent as a request.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+pub enum CommandT {
+    Mode(DroneMode),
+    ParamSet(ParamValueDT),
+    StartLogging,
+    StopLogging,
+    StartLogReporting,
+    StopLogReporting,
+}
+
+impl CommandT {
+    /// The one-way message doing the same.
+    pub fn to_data(&self) -> DataT {
+        match self {
+            CommandT::Mode(mode) => DataT::Mode(*mode),
+            CommandT::ParamSet(param) => DataT::ParamSet(*param),
+            CommandT::StartLogging => DataT::StartLogging,
+            CommandT::StopLogging => DataT::StopLogging,
+            CommandT::StartLogReporting => DataT::StartLogReporting,
+            CommandT::StopLogReporting => DataT::StopLogReporting,
+        }
+    }
+
+    /// Brings the drone to a stop, it has to get through even a congested
+    /// link.
+    pub fn is_safety(&self) -> bool {
+        matches!(self, CommandT::Mode(DroneMode::Panic | DroneMode::Safe))
+    }
+}
+
+/// A command the drone answers with a `ResponseDT` of the same `id`, once it
+/// handled it.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+pub struct RequestDT {
+    pub id: RequestId,
+    pub command: CommandT,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct ResponseDT {
+    pub id: RequestId, // of the answered request
+    pub result: ResultT,
+}
+
+/// Outcome of a request.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub enum ResultT {
+    Ok,
+    Rejected(WarningDT),      // the command is refused for this reason
+    InvalidInMode(DroneMode), // the command means nothing in this mode
+}
+
+/// Number of telemetry streams, see `StreamT`.
+pub const STREAM_COUNT: usize = 7;
+
+/// Messages the drone sends periodically, each at its own rate.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+pub enum StreamT {
+    Heartbeat,    // `Heartbeat`
+    Health,       // `HealthData`
+    LinkStats,    // `LinkStats`
+    Motors,       // `MotorsState`
+    DebugInfo,    // debug messages of the current mode
+    LogReport,    // the flash log, one `SensorStream` frame at a time
+    SensorStream, // live sensor samples, packed in `SensorStream` frames
+}
+
+impl StreamT {
+    pub const ALL: [StreamT; STREAM_COUNT] = [
+        StreamT::Heartbeat,
+        StreamT::Health,
+        StreamT::LinkStats,
+        StreamT::Motors,
+        StreamT::DebugInfo,
+        StreamT::LogReport,
+        StreamT::SensorStream,
+    ];
+
+    /// Position of the stream in `StreamT::ALL` and `StreamRatesDT`.
+    pub fn index(&self) -> usize {
+        *self as usize
+    }
 }
 
+/// Rate of a telemetry stream, as the time between two of its messages.
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
-pub struct UpdatePDT {
-    pub p: I16F16,
+pub struct StreamRateDT {
+    pub stream: StreamT,
+    pub interval_ms: u16, // 0 switches the stream off, the heartbeat can not be
+}
+
+/// Rates of all the telemetry streams, in the order of `StreamT::ALL`.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
+pub struct StreamRatesDT {
+    pub interval_ms: [u16; STREAM_COUNT], // 0 when the stream is off
+    pub budget: u32,                      // bytes per second all the streams may use
+    pub used: u32,                        // bytes per second they use at these rates
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
@@ -120,13 +516,6 @@ pub struct ControlDT {
     pub yaw: u16,
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
-pub struct TuningDT {
-    pub p: I16F16,
-    pub i: I16F16,
-    pub d: I16F16,
-}
-
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct SensorDT {
     pub sp: u8,
@@ -139,13 +528,180 @@ pub struct SensorDT {
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct HealthDT {
-    pub bat: u16, // battery percentage
-    pub cpu: u8,  // CPU utilization as a percentage
-    pub pres: u8,
+    pub time: Timestamp,
+    pub bat: u16,      // battery percentage
+    pub cpu: u8,       // busy time of the control loop as a percentage of the tick period
+    pub pres: u32,     // barometric pressure, in 10^-5 bar
+    pub loop_avg: u16, // average execution time of the control loop, in microseconds
+    pub loop_max: u16, // worst-case execution time of the control loop, in microseconds
+    pub jitter: u16,   // largest deviation of the tick period, in microseconds
+    pub overruns: u16, // ticks that missed their deadline
+}
+
+/// Numeric arguments carried by an `EventDT`.
+pub const EVENT_ARGS: usize = 2;
+
+/// Part of the drone an event comes from.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub enum EventSourceT {
+    Comms,
+    Flash,
+    Sensor,
+    Timing,
+    Battery,
+}
+
+/// What happened, the comments give the meaning of the arguments.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub enum EventCodeT {
+    // comms
+    SendFailed,    // `ComErr::code` of the error
+    ReceiveFailed, // `ComErr::code` of the error
+
+    // flash
+    LogStarted,
+    LogStopped,        // bytes logged
+    LogFull,           // bytes logged, the logging stopped
+    LogStopFailed,     // the end of the log could not be written
+    ReportStarted,     // bytes to send
+    ReportStopped,     // bytes sent
+    ReportStartFailed, // the end of the log could not be read
+    ReportReadFailed,  // address that could not be read, the report stopped
+
+    // sensor
+    Calibrated,
+
+    // timing
+    DeadlineOverrun, // ticks that missed their deadline since the last report, worst execution time in microseconds
+
+    // battery
+    BatteryLow,      // reading, in 10 mV
+    BatteryCritical, // reading, in 10 mV
+}
+
+impl EventCodeT {
+    pub fn source(&self) -> EventSourceT {
+        match self {
+            EventCodeT::SendFailed | EventCodeT::ReceiveFailed => EventSourceT::Comms,
+
+            EventCodeT::LogStarted
+            | EventCodeT::LogStopped
+            | EventCodeT::LogFull
+            | EventCodeT::LogStopFailed
+            | EventCodeT::ReportStarted
+            | EventCodeT::ReportStopped
+            | EventCodeT::ReportStartFailed
+            | EventCodeT::ReportReadFailed => EventSourceT::Flash,
+
+            EventCodeT::Calibrated => EventSourceT::Sensor,
+
+            EventCodeT::DeadlineOverrun => EventSourceT::Timing,
+
+            EventCodeT::BatteryLow | EventCodeT::BatteryCritical => EventSourceT::Battery,
+        }
+    }
+
+    /// Checks if the event reports something that went wrong, the others
+    /// are for information.
+    pub fn is_error(&self) -> bool {
+        !matches!(
+            self,
+            EventCodeT::LogStarted
+                | EventCodeT::LogStopped
+                | EventCodeT::ReportStarted
+                | EventCodeT::ReportStopped
+                | EventCodeT::Calibrated
+        )
+    }
+}
+
+/// An error or a notable event on the drone, its text is made by the runner
+/// (see the `Display` implementation) so it costs a few bytes on the link.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+pub struct EventDT {
+    pub source: EventSourceT,
+    pub code: EventCodeT,
+    pub args: [u32; EVENT_ARGS], // unused ones are 0
+}
+
+impl EventDT {
+    pub fn new(code: EventCodeT, args: [u32; EVENT_ARGS]) -> Self {
+        Self {
+            source: code.source(),
+            code,
+            args,
+        }
+    }
+}
+
+/// Readings of the battery below which it is low or critical, in 10 mV. The
+/// drone does not fly on a critical battery.
+pub const BATTERY_LOW: u16 = 1100;
+pub const BATTERY_CRITICAL: u16 = 1050;
+
+/// Charge of the battery, coarse enough for the heartbeat.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+pub enum BatteryT {
+    Unchecked, // the battery checks are disabled, e.g. on the USB power
+    Critical,
+    Low,
+    Ok,
+}
+
+impl BatteryT {
+    pub fn from_reading(reading: u16) -> Self {
+        if reading < BATTERY_CRITICAL {
+            BatteryT::Critical
+        } else if reading < BATTERY_LOW {
+            BatteryT::Low
+        } else {
+            BatteryT::Ok
+        }
+    }
+}
+
+/// Bits of `HeartbeatDT::faults`.
+pub const FAULT_BATTERY: u8 = 1 << 0; // the battery is critical
+pub const FAULT_LINK: u8 = 1 << 1; // the runner was silent for too long
+pub const FAULT_HANDSHAKE: u8 = 1 << 2; // no runner with the same protocol yet
+pub const FAULT_LOG_FULL: u8 = 1 << 3; // the flash ran out of space while logging
+
+/// Status of the drone, sent periodically. It is enough to rebuild what the
+/// runner knows of the drone, e.g. after reconnecting or when a `Mode`
+/// message got lost.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+pub struct HeartbeatDT {
+    pub mode: DroneMode,
+    pub armed: bool,      // the mode drives the motors
+    pub calibrated: bool, // the sensors are calibrated
+    pub logging: bool,    // the sensors are logged to the flash
+    pub log_report: bool, // the log is being sent to the runner
+    pub battery: BatteryT,
+    pub faults: u8, // `FAULT_*` bits of the conditions present
+}
+
+impl HeartbeatDT {
+    pub fn has_fault(&self, fault: u8) -> bool {
+        self.faults & fault != 0
+    }
+}
+
+/// Running counters of a `ComT`, see `ComT::link_stats`.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy, Default)]
+pub struct LinkStatsDT {
+    pub frames_received: u32, // valid frames
+    pub frames_sent: u32,
+    pub crc_errors: u32,    // frames with a wrong checksum
+    pub des_errors: u32,    // frames that could not be decoded or deserialized
+    pub resyncs: u32,       // unterminated frames dropped to find the next one
+    pub skipped_bytes: u32, // bytes thrown away while looking for a frame
+    pub busy_sends: u32,    // frames not sent because `send_f` was busy
+    pub auth_errors: u32,   // frames rejected as forged or replayed (see `ComT::require_auth`)
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct MotorsDT {
+    pub time: Timestamp,
     pub ae1: u16,
     pub ae2: u16,
     pub ae3: u16,
@@ -156,10 +712,14 @@ pub struct MotorsDT {
 pub enum WarningDT {
     ControlNotNeutral,
     SensorNotCalibrated,
+    HandshakeMissing, // no compatible handshake received yet
+    ParamRejected,    // unknown parameter, wrong type or out of bounds
+    LogUnavailable,   // the flash is busy with the log or its report, or failed
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
 pub struct SensorLogDT {
+    pub time: Timestamp, // when the entry was logged
     pub gyro_x: i16,
     pub gyro_y: i16,
     pub gyro_z: i16,
@@ -170,3 +730,266 @@ pub struct SensorLogDT {
     pub pitch: I16F16,
     pub yaw: I16F16,
 }
+
+/// Sensor samples packed by `telemetry::SensorEncoder`, a keyframe followed by
+/// deltas. Every chunk can be decoded on its own.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
+pub struct SensorStreamDT {
+    pub live: bool, // sampled right now, otherwise read back from the flash log
+    pub data: heapless::Vec<u8, SENSOR_STREAM_SIZE>,
+}
+
+#[cfg(test)]
+mod test {
+    use crate::protocol::*;
+    use crate::uart_com::{Cobs, Crc16, Crc32, Crc8, Escape};
+
+    #[test]
+    fn test_handshake_is_first_variant() {
+        let serialized =
+            postcard::to_vec::<DataT, 16>(&DataT::Handshake(HandshakeDT::local())).unwrap();
+
+        // the variant index must never change, otherwise older binaries can
+        // not even tell that they are incompatible
+        assert_eq!(serialized[0], 0);
+    }
+
+    // longest frame of a message serialized to `len` bytes, whatever the
+    // features of `common` select: the start and end bytes, the sequence
+    // number, the checksum (a varint of up to 5 bytes for a CRC-32) and the
+    // length of the payload, then the payload. Every byte may be escaped,
+    // except the ones of a varint that have their high bit set. COBS adds
+    // less than that.
+    fn worst_frame_len(len: usize) -> usize {
+        2 + 2 + (4 + 2) + 2 + 2 * len
+    }
+
+    // frames `data` with every checksum and encoding the features can select
+    fn fits_drone_buffers(data: &DataT) -> bool {
+        let payload: heapless::Vec<u8, 64> = match data.to_payload() {
+            Ok(val) => val,
+            Err(_) => {
+                return false;
+            }
+        };
+
+        uart_com::frame_with::<Crc8, Escape, 64>(payload.clone(), u8::MAX).is_ok()
+            && uart_com::frame_with::<Crc16, Escape, 64>(payload.clone(), u8::MAX).is_ok()
+            && uart_com::frame_with::<Crc32, Escape, 64>(payload.clone(), u8::MAX).is_ok()
+            && uart_com::frame_with::<Crc8, Cobs, 64>(payload.clone(), u8::MAX).is_ok()
+            && uart_com::frame_with::<Crc16, Cobs, 64>(payload.clone(), u8::MAX).is_ok()
+            && uart_com::frame_with::<Crc32, Cobs, 64>(payload, u8::MAX).is_ok()
+    }
+
+    #[test]
+    fn test_fragment_fits_drone_buffers() {
+        // worst case, every byte of the fragment is escaped
+        let fragment: DataT = DataT::Fragment(FragmentDT {
+            id: u8::MAX,
+            index: u8::MAX,
+            count: u8::MAX,
+            data: heapless::Vec::from_slice(&[uart_com::START_BYTE; FRAGMENT_SIZE]).unwrap(),
+        });
+
+        assert!(fits_drone_buffers(&fragment));
+
+        let len: usize = fragment.to_payload::<64>().unwrap().len();
+        assert!(worst_frame_len(len) <= 64);
+    }
+
+    #[test]
+    fn test_batch_fits_drone_buffers() {
+        let mut batch: BatchDT = BatchDT::new();
+        while batch.push(&DataT::KeepAlive) {}
+        assert_eq!(batch.data.len(), BATCH_SIZE);
+
+        // worst case, every byte of the batch is escaped
+        batch.data.fill(uart_com::START_BYTE);
+        let batch: DataT = DataT::Batch(batch);
+        assert!(fits_drone_buffers(&batch));
+
+        let len: usize = batch.to_payload::<64>().unwrap().len();
+        assert!(worst_frame_len(len) <= 64);
+    }
+
+    #[test]
+    fn test_sensor_stream_fits_drone_buffers() {
+        // full, without and with escaped bytes
+        let mut stream: SensorStreamDT = SensorStreamDT {
+            live: true,
+            data: heapless::Vec::from_slice(&[0; SENSOR_STREAM_SIZE]).unwrap(),
+        };
+        assert!(fits_drone_buffers(&DataT::SensorStream(stream.clone())));
+
+        stream.data.truncate(SENSOR_STREAM_SIZE / 2);
+        stream.data.fill(uart_com::START_BYTE);
+        assert!(fits_drone_buffers(&DataT::SensorStream(stream.clone())));
+
+        // the samples already count their escaped bytes twice
+        stream.data.clear();
+        let len: usize = DataT::SensorStream(stream)
+            .to_payload::<64>()
+            .unwrap()
+            .len();
+        assert!(worst_frame_len(len) + SENSOR_STREAM_SIZE <= 64);
+    }
+
+    #[test]
+    fn test_param_info_fits_drone_buffers() {
+        // longest name, all of it escaped, and the longest values
+        let info: ParamInfoDT = ParamInfoDT {
+            id: uart_com::START_BYTE,
+            name: core::iter::repeat(uart_com::START_BYTE as char)
+                .take(PARAM_NAME_CAP)
+                .collect(),
+            default: ParamValue::U32(u32::MAX),
+            min: ParamValue::U32(u32::MAX),
+            max: ParamValue::U32(u32::MAX),
+        };
+
+        assert!(fits_drone_buffers(&DataT::ParamInfo(info)));
+    }
+
+    #[test]
+    fn test_health_fits_drone_buffers() {
+        let health: HealthDT = HealthDT {
+            time: Timestamp::MAX,
+            bat: u16::MAX,
+            cpu: 100,
+            pres: u32::MAX,
+            loop_avg: u16::MAX,
+            loop_max: u16::MAX,
+            jitter: u16::MAX,
+            overruns: u16::MAX,
+        };
+
+        assert!(fits_drone_buffers(&DataT::HealthData(health)));
+    }
+
+    #[test]
+    fn test_request_fits_drone_buffers() {
+        let request: DataT = DataT::Request(RequestDT {
+            id: uart_com::START_BYTE,
+            command: CommandT::ParamSet(ParamValueDT {
+                id: uart_com::START_BYTE,
+                value: ParamValue::U32(u32::MAX),
+            }),
+        });
+        assert!(fits_drone_buffers(&request));
+
+        let response: DataT = DataT::Response(ResponseDT {
+            id: uart_com::START_BYTE,
+            result: ResultT::Rejected(WarningDT::ParamRejected),
+        });
+        assert!(fits_drone_buffers(&response));
+    }
+
+    #[test]
+    fn test_safety_commands() {
+        assert!(CommandT::Mode(DroneMode::Panic).is_safety());
+        assert!(CommandT::Mode(DroneMode::Safe).is_safety());
+        assert!(!CommandT::Mode(DroneMode::Manual).is_safety());
+        assert!(!CommandT::StopLogging.is_safety());
+    }
+
+    #[test]
+    fn test_heartbeat_fits_drone_buffers() {
+        let heartbeat: HeartbeatDT = HeartbeatDT {
+            mode: DroneMode::FullControl,
+            armed: true,
+            calibrated: true,
+            logging: true,
+            log_report: true,
+            battery: BatteryT::Critical,
+            faults: u8::MAX,
+        };
+
+        assert!(fits_drone_buffers(&DataT::Heartbeat(heartbeat)));
+        assert!(heartbeat.has_fault(FAULT_LOG_FULL));
+    }
+
+    #[test]
+    fn test_event_fits_drone_buffers() {
+        let event: EventDT = EventDT::new(EventCodeT::ReportReadFailed, [u32::MAX; EVENT_ARGS]);
+
+        assert_eq!(event.source, EventSourceT::Flash);
+        assert!(fits_drone_buffers(&DataT::Event(event)));
+    }
+
+    #[test]
+    fn test_battery_buckets() {
+        assert_eq!(BatteryT::from_reading(0), BatteryT::Critical);
+        assert_eq!(BatteryT::from_reading(BATTERY_CRITICAL), BatteryT::Low);
+        assert_eq!(BatteryT::from_reading(BATTERY_LOW - 1), BatteryT::Low);
+        assert_eq!(BatteryT::from_reading(BATTERY_LOW), BatteryT::Ok);
+    }
+
+    #[test]
+    fn test_stream_rates_fit_drone_buffers() {
+        let rates: StreamRatesDT = StreamRatesDT {
+            interval_ms: [u16::MAX; STREAM_COUNT],
+            budget: u32::MAX,
+            used: u32::MAX,
+        };
+
+        assert!(fits_drone_buffers(&DataT::StreamRates(rates)));
+    }
+
+    #[test]
+    fn test_batch_round_trip() {
+        let sent: [DataT; 3] = [
+            DataT::KeepAlive,
+            DataT::HealthData(HealthDT {
+                time: 1_000_000,
+                bat: 1100,
+                cpu: 42,
+                pres: 101_325,
+                loop_avg: 4_200,
+                loop_max: 6_500,
+                jitter: 150,
+                overruns: 0,
+            }),
+            DataT::Message(heapless::String::from("ok")),
+        ];
+
+        let mut batch: BatchDT = BatchDT::new();
+        for data in sent.iter() {
+            assert!(batch.push(data));
+        }
+        assert!(!batch.push(&DataT::MotorsState(MotorsDT {
+            time: 1_010_000,
+            ae1: 300,
+            ae2: 310,
+            ae3: 320,
+            ae4: 330,
+        })));
+        assert!(!batch.push(&DataT::Batch(BatchDT::new())));
+
+        let mut pos: usize = 0;
+        for data in sent.iter() {
+            let (received, next) = batch.get(pos).unwrap().unwrap();
+            assert_eq!(&received, data);
+            pos = next;
+        }
+        assert!(batch.get(pos).is_none());
+    }
+
+    #[test]
+    fn test_handshake_compatibility() {
+        assert!(HandshakeDT::local().is_compatible());
+
+        // the session does not matter
+        let mut other = HandshakeDT::local();
+        other.nonce = u64::MAX;
+        assert!(other.is_compatible());
+        assert!(fits_drone_buffers(&DataT::Handshake(other)));
+
+        let mut other = HandshakeDT::local();
+        other.schema_hash ^= 1;
+        assert!(!other.is_compatible());
+
+        let mut other = HandshakeDT::local();
+        other.version += 1;
+        assert!(!other.is_compatible());
+    }
+}
@@ -0,0 +1,840 @@
+//! machine readable description of the messages, for tools written in other
+//! languages that decode the raw serial traffic
+//!
+//! The description is traced from the `Deserialize` implementations of the
+//! messages, so it can not drift from them: every struct with the order,
+//! names and types of its fields, and every enum with the index of each of
+//! its variants (the tag postcard puts in front of the content).
+
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt::{self, Write};
+
+use serde::de::{self, DeserializeSeed, IntoDeserializer, Visitor};
+use serde::Deserialize;
+
+use super::{DataT, LargeDataT, PROTOCOL_VERSION};
+
+/// A JSON array or object is written on one line up to this width.
+const JSON_LINE_WIDTH: usize = 100;
+
+// the `fixed` types only serialize their bits, under the name of the integer
+// holding them. I16F16 is the only one in the messages.
+const FIXED_NAMES: [(&str, &str); 1] = [("FixedI32", "I16F16")];
+
+/// Shape of a serialized value.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Format {
+    Unknown, // never traced, there is a bug in the tracer
+    Unit,
+    Bool,
+    U8,
+    U16,
+    U32,
+    U64,
+    I8,
+    I16,
+    I32,
+    I64,
+    F32,
+    F64,
+    Char,
+    Str,
+    Bytes,
+    Option(Box<Format>),
+    Seq(Box<Format>),
+    Tuple(Vec<Format>),
+    Named(&'static str), // a struct or an enum, see `Schema::types`
+}
+
+/// Content of a struct or of a variant of an enum.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Fields {
+    Unit,
+    Newtype(Format),
+    Tuple(Vec<Format>),
+    Struct(Vec<(&'static str, Format)>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Variant {
+    pub name: &'static str,
+    pub fields: Option<Fields>, // `None` until traced
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Container {
+    Struct(Fields),
+    Enum(Vec<Variant>), // in the order of their index
+}
+
+/// Description of a set of messages and of all the types they contain.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Schema {
+    pub roots: Vec<&'static str>,
+    pub types: BTreeMap<&'static str, Container>,
+}
+
+#[derive(Debug)]
+pub struct Error(String);
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl core::error::Error for Error {}
+
+impl de::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Error(msg.to_string())
+    }
+}
+
+/// Traces `DataT` and `LargeDataT`, the messages exchanged with the drone.
+pub fn trace_protocol() -> Result<Schema, Error> {
+    let mut schema: Schema = Schema::default();
+    schema.trace::<DataT>()?;
+    schema.trace::<LargeDataT>()?;
+
+    Ok(schema)
+}
+
+impl Schema {
+    /// Adds `T` to the roots and traces it with all the types it contains.
+    ///
+    /// A deserialization only goes through one variant of each enum, so `T`
+    /// is deserialized again until all the variants of all the enums it
+    /// contains are traced.
+    pub fn trace<'de, T: Deserialize<'de>>(&mut self) -> Result<(), Error> {
+        loop {
+            let mut format: Format = Format::Unknown;
+            let mut progress: bool = false;
+
+            T::deserialize(Tracer {
+                types: &mut self.types,
+                format: &mut format,
+                progress: &mut progress,
+            })?;
+
+            let root: &'static str = match format {
+                Format::Named(name) => name,
+                _ => return Err(Error("a root must be a struct or an enum".to_string())),
+            };
+
+            if is_complete(&self.types, root, &mut Vec::new()) {
+                self.roots.push(root);
+                return Ok(());
+            }
+            if !progress {
+                return Err(Error(alloc::format!("{} can not be traced", root)));
+            }
+        }
+    }
+
+    /// The schema as a JSON document, the types sorted by name.
+    pub fn to_json(&self) -> String {
+        let types: Vec<(&'static str, Json)> = self
+            .types
+            .iter()
+            .map(|(name, container)| (*name, container_json(container)))
+            .collect();
+
+        let document: Json = Json::Obj(Vec::from([
+            ("protocol_version", Json::Num(PROTOCOL_VERSION as u64)),
+            ("encoding", Json::Str("postcard".to_string())),
+            (
+                "roots",
+                Json::Arr(
+                    self.roots
+                        .iter()
+                        .map(|name| Json::Str(name.to_string()))
+                        .collect(),
+                ),
+            ),
+            ("types", Json::Obj(types)),
+        ]));
+
+        let mut ret: String = String::new();
+        document.write(&mut ret, 0);
+        ret.push('\n');
+        ret
+    }
+}
+
+// A deserializer that records the shape of what it is asked to deserialize
+// and feeds the visitors the simplest value of it.
+struct Tracer<'a> {
+    types: &'a mut BTreeMap<&'static str, Container>,
+    format: &'a mut Format,
+    progress: &'a mut bool, // a variant was traced for the first time
+}
+
+macro_rules! trace_primitive {
+    ($method:ident, $format:ident, $visit:ident, $value:expr) => {
+        fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+            *self.format = Format::$format;
+            visitor.$visit($value)
+        }
+    };
+}
+
+impl<'de, 'a> de::Deserializer<'de> for Tracer<'a> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Error> {
+        Err(Error("self describing types are not supported".to_string()))
+    }
+
+    trace_primitive!(deserialize_bool, Bool, visit_bool, false);
+    trace_primitive!(deserialize_u8, U8, visit_u8, 0);
+    trace_primitive!(deserialize_u16, U16, visit_u16, 0);
+    trace_primitive!(deserialize_u32, U32, visit_u32, 0);
+    trace_primitive!(deserialize_u64, U64, visit_u64, 0);
+    trace_primitive!(deserialize_i8, I8, visit_i8, 0);
+    trace_primitive!(deserialize_i16, I16, visit_i16, 0);
+    trace_primitive!(deserialize_i32, I32, visit_i32, 0);
+    trace_primitive!(deserialize_i64, I64, visit_i64, 0);
+    trace_primitive!(deserialize_f32, F32, visit_f32, 0.0);
+    trace_primitive!(deserialize_f64, F64, visit_f64, 0.0);
+    trace_primitive!(deserialize_char, Char, visit_char, '\0');
+    trace_primitive!(deserialize_str, Str, visit_str, "");
+    trace_primitive!(deserialize_string, Str, visit_str, "");
+    trace_primitive!(deserialize_bytes, Bytes, visit_bytes, &[]);
+    trace_primitive!(deserialize_byte_buf, Bytes, visit_bytes, &[]);
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        *self.format = Format::Unit;
+        visitor.visit_unit()
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let mut inner: Format = Format::Unknown;
+        let value = visitor.visit_some(Tracer {
+            types: self.types,
+            format: &mut inner,
+            progress: self.progress,
+        })?;
+
+        *self.format = Format::Option(Box::new(inner));
+        Ok(value)
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.types.insert(name, Container::Struct(Fields::Unit));
+        *self.format = Format::Named(name);
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        let mut inner: Format = Format::Unknown;
+        let value = visitor.visit_newtype_struct(Tracer {
+            types: &mut *self.types,
+            format: &mut inner,
+            progress: &mut *self.progress,
+        })?;
+
+        self.types
+            .insert(name, Container::Struct(Fields::Newtype(inner)));
+        *self.format = Format::Named(name);
+        Ok(value)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        // a single element is enough to know the type of all of them
+        let mut formats: Vec<Format> = Vec::new();
+        let value = visitor.visit_seq(SeqTracer {
+            types: self.types,
+            progress: self.progress,
+            remaining: 1,
+            formats: &mut formats,
+        })?;
+
+        *self.format = Format::Seq(Box::new(formats.pop().unwrap_or(Format::Unknown)));
+        Ok(value)
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value, Error> {
+        let mut formats: Vec<Format> = Vec::new();
+        let value = visitor.visit_seq(SeqTracer {
+            types: self.types,
+            progress: self.progress,
+            remaining: len,
+            formats: &mut formats,
+        })?;
+
+        *self.format = Format::Tuple(formats);
+        Ok(value)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        let mut formats: Vec<Format> = Vec::new();
+        let value = visitor.visit_seq(SeqTracer {
+            types: &mut *self.types,
+            progress: &mut *self.progress,
+            remaining: len,
+            formats: &mut formats,
+        })?;
+
+        self.types
+            .insert(name, Container::Struct(Fields::Tuple(formats)));
+        *self.format = Format::Named(name);
+        Ok(value)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Error> {
+        Err(Error("maps are not supported".to_string()))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        let mut formats: Vec<Format> = Vec::new();
+        let value = visitor.visit_seq(SeqTracer {
+            types: &mut *self.types,
+            progress: &mut *self.progress,
+            remaining: fields.len(),
+            formats: &mut formats,
+        })?;
+
+        let name: &'static str = fixed_name(name);
+        let fields: Vec<(&'static str, Format)> = fields.iter().copied().zip(formats).collect();
+        self.types
+            .insert(name, Container::Struct(Fields::Struct(fields)));
+        *self.format = Format::Named(name);
+        Ok(value)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.types.entry(name).or_insert_with(|| {
+            Container::Enum(
+                variants
+                    .iter()
+                    .map(|variant| Variant {
+                        name: variant,
+                        fields: None,
+                    })
+                    .collect(),
+            )
+        });
+        let index: usize = next_variant(self.types, name)?;
+
+        let mut fields: Option<Fields> = None;
+        let value = visitor.visit_enum(EnumTracer {
+            types: &mut *self.types,
+            progress: &mut *self.progress,
+            index: index as u32,
+            fields: &mut fields,
+        })?;
+
+        if let Some(Container::Enum(known)) = self.types.get_mut(name) {
+            if known[index].fields.is_none() {
+                known[index].fields = fields;
+                *self.progress = true;
+            }
+        }
+
+        *self.format = Format::Named(name);
+        Ok(value)
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Error> {
+        Err(Error("identifiers are only expected in enums".to_string()))
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Error> {
+        Err(Error("self describing types are not supported".to_string()))
+    }
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+}
+
+// the name of a fixed-point type with its fractional bits, see `FIXED_NAMES`
+fn fixed_name(name: &'static str) -> &'static str {
+    match FIXED_NAMES
+        .iter()
+        .find(|(serde_name, _)| *serde_name == name)
+    {
+        Some((_, fixed)) => fixed,
+        None => name,
+    }
+}
+
+// The variant of the enum `name` to trace next: the first one not traced yet,
+// else the first one that leads to something not traced yet.
+fn next_variant(
+    types: &BTreeMap<&'static str, Container>,
+    name: &'static str,
+) -> Result<usize, Error> {
+    let variants: &Vec<Variant> = match types.get(name) {
+        Some(Container::Enum(variants)) => variants,
+        _ => return Err(Error(alloc::format!("{} is a struct and an enum", name))),
+    };
+
+    if let Some(index) = variants.iter().position(|v| v.fields.is_none()) {
+        return Ok(index);
+    }
+
+    let index: Option<usize> = variants.iter().position(|v| match &v.fields {
+        Some(fields) => !fields_complete(types, fields, &mut Vec::from([name])),
+        None => true,
+    });
+
+    Ok(index.unwrap_or(0))
+}
+
+// `name` and all the types it contains are traced, `seen` are the types being
+// checked already (the types can be recursive)
+fn is_complete(
+    types: &BTreeMap<&'static str, Container>,
+    name: &'static str,
+    seen: &mut Vec<&'static str>,
+) -> bool {
+    if seen.contains(&name) {
+        return true;
+    }
+    seen.push(name);
+
+    match types.get(name) {
+        Some(Container::Struct(fields)) => fields_complete(types, fields, seen),
+        Some(Container::Enum(variants)) => variants.iter().all(|v| match &v.fields {
+            Some(fields) => fields_complete(types, fields, seen),
+            None => false,
+        }),
+        None => false,
+    }
+}
+
+fn fields_complete(
+    types: &BTreeMap<&'static str, Container>,
+    fields: &Fields,
+    seen: &mut Vec<&'static str>,
+) -> bool {
+    match fields {
+        Fields::Unit => true,
+        Fields::Newtype(format) => format_complete(types, format, seen),
+        Fields::Tuple(formats) => formats.iter().all(|f| format_complete(types, f, seen)),
+        Fields::Struct(fields) => fields.iter().all(|(_, f)| format_complete(types, f, seen)),
+    }
+}
+
+fn format_complete(
+    types: &BTreeMap<&'static str, Container>,
+    format: &Format,
+    seen: &mut Vec<&'static str>,
+) -> bool {
+    match format {
+        Format::Unknown => false,
+        Format::Option(inner) | Format::Seq(inner) => format_complete(types, inner, seen),
+        Format::Tuple(formats) => formats.iter().all(|f| format_complete(types, f, seen)),
+        Format::Named(name) => is_complete(types, name, seen),
+        _ => true,
+    }
+}
+
+// The fields of a struct or a tuple, or the elements of a sequence.
+struct SeqTracer<'a> {
+    types: &'a mut BTreeMap<&'static str, Container>,
+    progress: &'a mut bool,
+    remaining: usize,
+    formats: &'a mut Vec<Format>,
+}
+
+impl<'de, 'a> de::SeqAccess<'de> for SeqTracer<'a> {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Error> {
+        if self.remaining == 0 {
+            return Ok(None);
+        }
+        self.remaining -= 1;
+
+        let mut format: Format = Format::Unknown;
+        let value = seed.deserialize(Tracer {
+            types: &mut *self.types,
+            format: &mut format,
+            progress: &mut *self.progress,
+        })?;
+
+        self.formats.push(format);
+        Ok(Some(value))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.remaining)
+    }
+}
+
+// Picks the variant `index` of an enum and records its content.
+struct EnumTracer<'a> {
+    types: &'a mut BTreeMap<&'static str, Container>,
+    progress: &'a mut bool,
+    index: u32,
+    fields: &'a mut Option<Fields>,
+}
+
+impl<'de, 'a> de::EnumAccess<'de> for EnumTracer<'a> {
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Self), Error> {
+        let index: de::value::U32Deserializer<Error> = self.index.into_deserializer();
+        let variant = seed.deserialize(index)?;
+        Ok((variant, self))
+    }
+}
+
+impl<'de, 'a> de::VariantAccess<'de> for EnumTracer<'a> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<(), Error> {
+        *self.fields = Some(Fields::Unit);
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, Error> {
+        let mut inner: Format = Format::Unknown;
+        let value = seed.deserialize(Tracer {
+            types: self.types,
+            format: &mut inner,
+            progress: self.progress,
+        })?;
+
+        *self.fields = Some(Fields::Newtype(inner));
+        Ok(value)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value, Error> {
+        let mut formats: Vec<Format> = Vec::new();
+        let value = visitor.visit_seq(SeqTracer {
+            types: self.types,
+            progress: self.progress,
+            remaining: len,
+            formats: &mut formats,
+        })?;
+
+        *self.fields = Some(Fields::Tuple(formats));
+        Ok(value)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        let mut formats: Vec<Format> = Vec::new();
+        let value = visitor.visit_seq(SeqTracer {
+            types: self.types,
+            progress: self.progress,
+            remaining: fields.len(),
+            formats: &mut formats,
+        })?;
+
+        *self.fields = Some(Fields::Struct(
+            fields.iter().copied().zip(formats).collect(),
+        ));
+        Ok(value)
+    }
+}
+
+// Just enough JSON to write the schema.
+enum Json {
+    Null,
+    Num(u64),
+    Str(String),
+    Arr(Vec<Json>),
+    Obj(Vec<(&'static str, Json)>),
+}
+
+impl Json {
+    fn write(&self, out: &mut String, indent: usize) {
+        let mut line: String = String::new();
+        self.write_line(&mut line);
+        if indent + line.len() <= JSON_LINE_WIDTH {
+            out.push_str(&line);
+            return;
+        }
+
+        match self {
+            Json::Arr(items) => {
+                out.push('[');
+                for (i, item) in items.iter().enumerate() {
+                    out.push_str(if i == 0 { "\n" } else { ",\n" });
+                    push_indent(out, indent + 2);
+                    item.write(out, indent + 2);
+                }
+                out.push('\n');
+                push_indent(out, indent);
+                out.push(']');
+            }
+            Json::Obj(members) => {
+                out.push('{');
+                for (i, (key, value)) in members.iter().enumerate() {
+                    out.push_str(if i == 0 { "\n" } else { ",\n" });
+                    push_indent(out, indent + 2);
+                    let _ = write!(out, "\"{}\": ", key);
+                    value.write(out, indent + 2);
+                }
+                out.push('\n');
+                push_indent(out, indent);
+                out.push('}');
+            }
+            _ => out.push_str(&line),
+        }
+    }
+
+    fn write_line(&self, out: &mut String) {
+        match self {
+            Json::Null => out.push_str("null"),
+            Json::Num(num) => {
+                let _ = write!(out, "{}", num);
+            }
+            // only identifiers end up here, there is nothing to escape
+            Json::Str(text) => {
+                let _ = write!(out, "\"{}\"", text);
+            }
+            Json::Arr(items) => {
+                out.push('[');
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        out.push_str(", ");
+                    }
+                    item.write_line(out);
+                }
+                out.push(']');
+            }
+            Json::Obj(members) => {
+                out.push_str("{ ");
+                for (i, (key, value)) in members.iter().enumerate() {
+                    if i > 0 {
+                        out.push_str(", ");
+                    }
+                    let _ = write!(out, "\"{}\": ", key);
+                    value.write_line(out);
+                }
+                out.push_str(" }");
+            }
+        }
+    }
+}
+
+fn push_indent(out: &mut String, indent: usize) {
+    for _ in 0..indent {
+        out.push(' ');
+    }
+}
+
+fn format_json(format: &Format) -> Json {
+    let name: &str = match format {
+        Format::Unknown => "unknown",
+        Format::Unit => "unit",
+        Format::Bool => "bool",
+        Format::U8 => "u8",
+        Format::U16 => "u16",
+        Format::U32 => "u32",
+        Format::U64 => "u64",
+        Format::I8 => "i8",
+        Format::I16 => "i16",
+        Format::I32 => "i32",
+        Format::I64 => "i64",
+        Format::F32 => "f32",
+        Format::F64 => "f64",
+        Format::Char => "char",
+        Format::Str => "str",
+        Format::Bytes => "bytes",
+        Format::Named(name) => name,
+        Format::Option(inner) => return Json::Obj(Vec::from([("option", format_json(inner))])),
+        Format::Seq(inner) => return Json::Obj(Vec::from([("seq", format_json(inner))])),
+        Format::Tuple(formats) => {
+            return Json::Obj(Vec::from([(
+                "tuple",
+                Json::Arr(formats.iter().map(format_json).collect()),
+            )]))
+        }
+    };
+
+    Json::Str(name.to_string())
+}
+
+// the members describing `fields`, added to those of a struct or a variant
+fn fields_json(fields: &Fields, members: &mut Vec<(&'static str, Json)>) {
+    match fields {
+        Fields::Unit => {}
+        Fields::Newtype(format) => members.push(("type", format_json(format))),
+        Fields::Tuple(formats) => members.push((
+            "tuple",
+            Json::Arr(formats.iter().map(format_json).collect()),
+        )),
+        Fields::Struct(fields) => members.push((
+            "fields",
+            Json::Arr(
+                fields
+                    .iter()
+                    .map(|(name, format)| {
+                        Json::Obj(Vec::from([
+                            ("name", Json::Str(name.to_string())),
+                            ("type", format_json(format)),
+                        ]))
+                    })
+                    .collect(),
+            ),
+        )),
+    }
+}
+
+fn container_json(container: &Container) -> Json {
+    match container {
+        Container::Struct(fields) => {
+            let mut members: Vec<(&'static str, Json)> =
+                Vec::from([("kind", Json::Str("struct".to_string()))]);
+            fields_json(fields, &mut members);
+            Json::Obj(members)
+        }
+        Container::Enum(variants) => {
+            let variants: Vec<Json> = variants
+                .iter()
+                .enumerate()
+                .map(|(index, variant)| {
+                    let mut members: Vec<(&'static str, Json)> = Vec::from([
+                        ("index", Json::Num(index as u64)),
+                        ("name", Json::Str(variant.name.to_string())),
+                    ]);
+                    match &variant.fields {
+                        Some(fields) => fields_json(fields, &mut members),
+                        None => members.push(("type", Json::Null)),
+                    }
+                    Json::Obj(members)
+                })
+                .collect();
+
+            Json::Obj(Vec::from([
+                ("kind", Json::Str("enum".to_string())),
+                ("variants", Json::Arr(variants)),
+            ]))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::protocol::schema::*;
+    use crate::protocol::{HealthDT, ParamValue};
+
+    fn variants<'a>(schema: &'a Schema, name: &str) -> &'a [Variant] {
+        match schema.types.get(name) {
+            Some(Container::Enum(variants)) => variants,
+            _ => panic!("{} is not an enum", name),
+        }
+    }
+
+    #[test]
+    fn test_every_variant_is_traced() {
+        let schema: Schema = trace_protocol().unwrap();
+        assert_eq!(schema.roots, ["DataT", "LargeDataT"]);
+
+        for container in schema.types.values() {
+            if let Container::Enum(variants) = container {
+                assert!(variants.iter().all(|variant| variant.fields.is_some()));
+            }
+        }
+        assert!(!schema.to_json().contains("unknown"));
+    }
+
+    #[test]
+    fn test_struct_fields() {
+        let schema: Schema = trace_protocol().unwrap();
+
+        let Some(Container::Struct(Fields::Struct(fields))) = schema.types.get("HealthDT") else {
+            panic!("HealthDT is not a struct");
+        };
+        assert_eq!(fields[0], ("time", Format::U32));
+        assert_eq!(fields[3], ("pres", Format::U32));
+        assert_eq!(fields.len(), 8);
+
+        let Some(Container::Struct(Fields::Struct(fields))) = schema.types.get("FragmentDT") else {
+            panic!("FragmentDT is not a struct");
+        };
+        assert_eq!(fields[3], ("data", Format::Seq(Box::new(Format::U8))));
+    }
+
+    #[test]
+    fn test_fixed_point_name() {
+        let schema: Schema = trace_protocol().unwrap();
+
+        assert!(schema.types.contains_key("I16F16"));
+        assert!(!schema.types.contains_key("FixedI32"));
+        assert!(!schema.to_json().contains("FixedI32"));
+    }
+
+    // the description in `common/protocol_schema.json` is used by tools
+    // outside of this repository and hashed into `SCHEMA_HASH`, it must match
+    // the code
+    #[test]
+    fn test_exported_schema_is_up_to_date() {
+        let traced: String = trace_protocol().unwrap().to_json();
+
+        assert!(
+            traced == include_str!("../../protocol_schema.json"),
+            "common/protocol_schema.json is out of date, refresh it with \
+             `cargo run -p common --features schema --example export_schema \
+             --target x86_64-unknown-linux-gnu > common/protocol_schema.json`"
+        );
+    }
+
+    #[test]
+    fn test_variant_index_is_the_tag() {
+        let schema: Schema = trace_protocol().unwrap();
+
+        let health: DataT = DataT::HealthData(HealthDT {
+            time: 0,
+            bat: 0,
+            cpu: 0,
+            pres: 0,
+            loop_avg: 0,
+            loop_max: 0,
+            jitter: 0,
+            overruns: 0,
+        });
+        let tag: u8 = postcard::to_vec::<DataT, 32>(&health).unwrap()[0];
+        assert_eq!(variants(&schema, "DataT")[tag as usize].name, "HealthData");
+
+        let tag: u8 = postcard::to_vec::<DataT, 32>(&DataT::KeepAlive).unwrap()[0];
+        assert_eq!(variants(&schema, "DataT")[tag as usize].name, "KeepAlive");
+
+        let value: ParamValue = ParamValue::U32(7);
+        let tag: u8 = postcard::to_vec::<ParamValue, 8>(&value).unwrap()[0];
+        assert_eq!(
+            variants(&schema, "ParamValue")[tag as usize].fields,
+            Some(Fields::Newtype(Format::U32))
+        );
+    }
+}
@@ -0,0 +1,436 @@
+//! Compact encoding of the sensor samples for `DataT::SensorStream`.
+//!
+//! A chunk starts with a keyframe, the full sample, followed by the changes
+//! of the next samples to the previous one. All the values are zigzag encoded
+//! varints, so small changes take a single byte. Every chunk starts with a
+//! keyframe, so a lost frame only loses its own samples.
+
+use fixed::types::I16F16;
+
+use crate::protocol::{SensorLogDT, SensorStreamDT, Timestamp, SENSOR_STREAM_SIZE};
+use crate::uart_com;
+
+pub mod rates;
+
+/// Angles are sent in radians multiplied by this, which covers +-3.27 rad.
+pub const ANGLE_SCALE: i16 = 10000;
+
+const FIELDS: usize = 9;
+
+// a varint of the time takes at most 5 bytes and one of a field (or of the
+// change of a field) 3
+const MAX_SAMPLE_SIZE: usize = 5 + FIELDS * 3;
+
+// only the last byte of a varint can be escaped, the others have their high
+// bit set
+const MAX_SAMPLE_COST: usize = MAX_SAMPLE_SIZE + 1 + FIELDS;
+
+// a keyframe always fits in an empty chunk
+const _: () = assert!(MAX_SAMPLE_COST <= SENSOR_STREAM_SIZE);
+
+/// One sample of the IMU and of the attitude estimate, at the resolution it is
+/// sent with.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct SensorSample {
+    pub time: Timestamp,
+
+    pub gyro_x: i16,
+    pub gyro_y: i16,
+    pub gyro_z: i16,
+    pub accel_x: i16,
+    pub accel_y: i16,
+    pub accel_z: i16,
+
+    pub pitch: i16, // radians * ANGLE_SCALE
+    pub roll: i16,  // radians * ANGLE_SCALE
+    pub yaw: i16,   // radians * ANGLE_SCALE
+}
+
+impl SensorSample {
+    /// Scales an angle in radians, it saturates outside of +-3.27 rad.
+    pub fn angle_from_fixed(angle: I16F16) -> i16 {
+        angle
+            .saturating_mul(I16F16::from_num(ANGLE_SCALE))
+            .saturating_to_num::<i16>()
+    }
+
+    /// The angle in radians.
+    pub fn angle_to_fixed(angle: i16) -> I16F16 {
+        I16F16::from_num(angle) / I16F16::from_num(ANGLE_SCALE)
+    }
+
+    /// The same sample with full width angles.
+    pub fn to_log(&self) -> SensorLogDT {
+        SensorLogDT {
+            time: self.time,
+            gyro_x: self.gyro_x,
+            gyro_y: self.gyro_y,
+            gyro_z: self.gyro_z,
+            accel_x: self.accel_x,
+            accel_y: self.accel_y,
+            accel_z: self.accel_z,
+            pitch: Self::angle_to_fixed(self.pitch),
+            roll: Self::angle_to_fixed(self.roll),
+            yaw: Self::angle_to_fixed(self.yaw),
+        }
+    }
+
+    fn fields(&self) -> [i16; FIELDS] {
+        [
+            self.gyro_x,
+            self.gyro_y,
+            self.gyro_z,
+            self.accel_x,
+            self.accel_y,
+            self.accel_z,
+            self.pitch,
+            self.roll,
+            self.yaw,
+        ]
+    }
+
+    fn from_fields(time: Timestamp, fields: [i16; FIELDS]) -> Self {
+        Self {
+            time,
+            gyro_x: fields[0],
+            gyro_y: fields[1],
+            gyro_z: fields[2],
+            accel_x: fields[3],
+            accel_y: fields[4],
+            accel_z: fields[5],
+            pitch: fields[6],
+            roll: fields[7],
+            yaw: fields[8],
+        }
+    }
+}
+
+/// Packs samples into `SensorStreamDT` chunks.
+///
+/// The size of a chunk is limited by its worst case in a frame: an escaped
+/// byte (see `uart_com::is_special`) counts twice.
+pub struct SensorEncoder {
+    chunk: SensorStreamDT,
+    cost: usize, // size of the chunk in a frame, at most `SENSOR_STREAM_SIZE`
+    last: SensorSample,
+}
+
+impl SensorEncoder {
+    /// `live` is copied to every chunk, see `SensorStreamDT`.
+    pub fn new(live: bool) -> Self {
+        Self {
+            chunk: SensorStreamDT {
+                live,
+                data: heapless::Vec::new(),
+            },
+            cost: 0,
+            last: SensorSample::default(),
+        }
+    }
+
+    /// Appends `sample` to the chunk. Returns `false` if the chunk is full,
+    /// `take` it and push the sample again.
+    pub fn push(&mut self, sample: &SensorSample) -> bool {
+        let mut encoded: heapless::Vec<u8, MAX_SAMPLE_SIZE> = heapless::Vec::new();
+
+        if self.chunk.data.is_empty() {
+            write_varint(&mut encoded, sample.time);
+            for field in sample.fields() {
+                write_varint(&mut encoded, zigzag(field as i32));
+            }
+        } else {
+            write_varint(&mut encoded, sample.time.wrapping_sub(self.last.time));
+            for (field, last) in sample.fields().iter().zip(self.last.fields().iter()) {
+                write_varint(&mut encoded, zigzag(*field as i32 - *last as i32));
+            }
+        }
+
+        let cost: usize = encoded
+            .iter()
+            .map(|byte| if uart_com::is_special(*byte) { 2 } else { 1 })
+            .sum();
+        if self.cost + cost > SENSOR_STREAM_SIZE {
+            return false;
+        }
+
+        // the cost is never below the length
+        let _ = self.chunk.data.extend_from_slice(&encoded);
+        self.cost += cost;
+        self.last = *sample;
+        true
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chunk.data.is_empty()
+    }
+
+    /// Returns the chunk, `None` if it is empty. The next sample starts a new
+    /// chunk with a keyframe.
+    pub fn take(&mut self) -> Option<SensorStreamDT> {
+        if self.is_empty() {
+            return None;
+        }
+
+        self.cost = 0;
+        let empty: SensorStreamDT = SensorStreamDT {
+            live: self.chunk.live,
+            data: heapless::Vec::new(),
+        };
+        Some(core::mem::replace(&mut self.chunk, empty))
+    }
+}
+
+/// The samples of a chunk, in the order they were pushed. Decoding stops at
+/// the first error.
+pub struct SensorDecoder<'a> {
+    data: &'a [u8],
+    pos: usize,
+    last: Option<SensorSample>,
+}
+
+impl<'a> SensorDecoder<'a> {
+    pub fn new(chunk: &'a SensorStreamDT) -> Self {
+        Self {
+            data: &chunk.data,
+            pos: 0,
+            last: None,
+        }
+    }
+
+    fn decode(&mut self) -> Result<SensorSample, uart_com::Error> {
+        let time: u32 = self.read_varint()?;
+        let mut changes: [i32; FIELDS] = [0; FIELDS];
+        for change in changes.iter_mut() {
+            *change = unzigzag(self.read_varint()?);
+        }
+
+        // a keyframe is the change from zero
+        let (time, base) = match self.last {
+            Some(last) => (last.time.wrapping_add(time), last.fields()),
+            None => (time, [0; FIELDS]),
+        };
+
+        let mut fields: [i16; FIELDS] = [0; FIELDS];
+        for ((field, change), base) in fields.iter_mut().zip(changes.iter()).zip(base.iter()) {
+            *field = match i16::try_from(*base as i32 + *change) {
+                Ok(field) => field,
+                Err(_) => return Err(uart_com::Error::Des),
+            };
+        }
+
+        let sample: SensorSample = SensorSample::from_fields(time, fields);
+        self.last = Some(sample);
+        Ok(sample)
+    }
+
+    fn read_varint(&mut self) -> Result<u32, uart_com::Error> {
+        let mut ret: u32 = 0;
+
+        for i in 0..5 {
+            let byte: u8 = match self.data.get(self.pos) {
+                Some(byte) => *byte,
+                None => return Err(uart_com::Error::Des),
+            };
+            self.pos += 1;
+
+            ret |= ((byte & 0x7F) as u32) << (7 * i);
+            if byte & 0x80 == 0 {
+                return Ok(ret);
+            }
+        }
+
+        Err(uart_com::Error::Des)
+    }
+}
+
+impl<'a> Iterator for SensorDecoder<'a> {
+    type Item = Result<SensorSample, uart_com::Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.pos >= self.data.len() {
+            return None;
+        }
+
+        let ret: Result<SensorSample, uart_com::Error> = self.decode();
+        if ret.is_err() {
+            self.pos = self.data.len();
+        }
+
+        Some(ret)
+    }
+}
+
+// changes of a field take up to 17 bits, an i16 delta would overflow
+fn zigzag(value: i32) -> u32 {
+    ((value << 1) ^ (value >> 31)) as u32
+}
+
+fn unzigzag(value: u32) -> i32 {
+    ((value >> 1) as i32) ^ -((value & 1) as i32)
+}
+
+fn write_varint<const CAP: usize>(buf: &mut heapless::Vec<u8, CAP>, value: u32) {
+    let mut value: u32 = value;
+
+    // `MAX_SAMPLE_SIZE` leaves room for the longest varints
+    while value >= 0x80 {
+        let _ = buf.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    let _ = buf.push(value as u8);
+}
+
+#[cfg(test)]
+mod test {
+    use crate::protocol::DataT;
+    use crate::telemetry::*;
+
+    // a slowly moving drone sampled at 100 Hz
+    fn samples(count: usize) -> Vec<SensorSample> {
+        (0..count)
+            .map(|i| {
+                let i: i16 = i as i16;
+                SensorSample {
+                    time: 2_000_000 + i as u32 * 10_000,
+                    gyro_x: -1200 + i * 3,
+                    gyro_y: 62 - i,
+                    gyro_z: 3,
+                    accel_x: 16000 - i * 5,
+                    accel_y: -60 + i,
+                    accel_z: i % 4,
+                    pitch: 2500 - i * 7,
+                    roll: -15000 + i * 2,
+                    yaw: 31000,
+                }
+            })
+            .collect()
+    }
+
+    fn encode(samples: &[SensorSample]) -> Vec<SensorStreamDT> {
+        let mut encoder: SensorEncoder = SensorEncoder::new(false);
+        let mut chunks: Vec<SensorStreamDT> = Vec::new();
+
+        for sample in samples {
+            if !encoder.push(sample) {
+                chunks.push(encoder.take().unwrap());
+                assert!(encoder.push(sample));
+            }
+        }
+        chunks.extend(encoder.take());
+
+        chunks
+    }
+
+    fn decode(chunk: &SensorStreamDT) -> Vec<SensorSample> {
+        SensorDecoder::new(chunk).map(|s| s.unwrap()).collect()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let sent: Vec<SensorSample> = samples(40);
+        let chunks: Vec<SensorStreamDT> = encode(&sent);
+
+        // every chunk on its own
+        let received: Vec<SensorSample> = chunks.iter().flat_map(decode).collect();
+        assert_eq!(received, sent);
+    }
+
+    #[test]
+    fn test_extreme_values() {
+        let mut sent: Vec<SensorSample> = samples(4);
+        sent[1].gyro_x = i16::MIN;
+        sent[1].accel_z = i16::MAX;
+        sent[2].gyro_x = i16::MAX;
+        sent[2].accel_z = i16::MIN;
+        // the drone clock wraps around
+        sent[2].time = u32::MAX - 5;
+        sent[3].time = 10;
+
+        let received: Vec<SensorSample> = encode(&sent).iter().flat_map(decode).collect();
+        assert_eq!(received, sent);
+    }
+
+    #[test]
+    fn test_more_compact_than_sensor_log() {
+        let sent: Vec<SensorSample> = samples(40);
+        let chunks: Vec<SensorStreamDT> = encode(&sent);
+
+        // bytes on the wire, with the framing
+        let log_size: usize = sent
+            .iter()
+            .map(|s| {
+                DataT::SensorLog(s.to_log())
+                    .to_packet::<64>(0)
+                    .unwrap()
+                    .len()
+            })
+            .sum();
+        let stream_size: usize = chunks
+            .iter()
+            .map(|c| {
+                DataT::SensorStream(c.clone())
+                    .to_packet::<64>(0)
+                    .unwrap()
+                    .len()
+            })
+            .sum();
+
+        // a keyframe and at least two deltas per frame
+        assert!(
+            chunks.len() * 3 <= sent.len() + 2,
+            "{} chunks",
+            chunks.len()
+        );
+        assert!(
+            stream_size * 3 < log_size * 2,
+            "{} B instead of {} B",
+            stream_size,
+            log_size
+        );
+    }
+
+    #[test]
+    fn test_escaped_bytes_count_twice() {
+        // every value encodes to a single special byte
+        let special: SensorSample = SensorSample::from_fields(
+            uart_com::START_BYTE as u32,
+            [(uart_com::START_BYTE / 2) as i16; FIELDS],
+        );
+        let mut encoder: SensorEncoder = SensorEncoder::new(true);
+
+        assert!(encoder.push(&special));
+        assert!(encoder.push(&special));
+        let chunk: SensorStreamDT = encoder.take().unwrap();
+        assert!(chunk.live);
+
+        let cost: usize = chunk
+            .data
+            .iter()
+            .map(|b| if uart_com::is_special(*b) { 2 } else { 1 })
+            .sum();
+        assert!(cost <= SENSOR_STREAM_SIZE);
+        assert_eq!(decode(&chunk), [special, special]);
+    }
+
+    #[test]
+    fn test_truncated_chunk() {
+        let mut chunk: SensorStreamDT = encode(&samples(3)).remove(0);
+        chunk.data.truncate(chunk.data.len() - 1);
+
+        let received: Vec<Result<SensorSample, uart_com::Error>> =
+            SensorDecoder::new(&chunk).collect();
+        assert_eq!(received.len(), 3);
+        assert_eq!(received[2], Err(uart_com::Error::Des));
+    }
+
+    #[test]
+    fn test_angles() {
+        let angle: I16F16 = I16F16::from_num(-1.25);
+        assert_eq!(SensorSample::angle_from_fixed(angle), -12500);
+        assert_eq!(SensorSample::angle_to_fixed(-12500), angle);
+        assert_eq!(
+            SensorSample::angle_from_fixed(I16F16::from_num(4)),
+            i16::MAX
+        );
+    }
+}
@@ -0,0 +1,300 @@
+//! Rates of the telemetry streams of the drone, within a bandwidth budget.
+
+use crate::protocol::{StreamRatesDT, StreamT, STREAM_COUNT};
+
+// largest encoding of the checksum in the header of a frame
+#[cfg(feature = "crc32")]
+const CHECKSUM_SIZE: u32 = 5;
+#[cfg(all(feature = "crc16", not(feature = "crc32")))]
+const CHECKSUM_SIZE: u32 = 3;
+#[cfg(not(any(feature = "crc16", feature = "crc32")))]
+const CHECKSUM_SIZE: u32 = 1;
+
+/// The runner considers the link lost after this long without a heartbeat.
+pub const HEARTBEAT_TIMEOUT_MS: u32 = 2000;
+
+/// Longest time between two heartbeats, one lost heartbeat must not be
+/// taken for a lost link.
+pub const HEARTBEAT_INTERVAL_MAX_MS: u32 = HEARTBEAT_TIMEOUT_MS / 2;
+
+/// Longest period of the heartbeat in ticks, at `tick_frequency` ticks per
+/// second (see `HEARTBEAT_INTERVAL_MAX_MS`).
+pub const fn heartbeat_period_max(tick_frequency: u32) -> u32 {
+    HEARTBEAT_INTERVAL_MAX_MS * tick_frequency / 1000
+}
+
+impl StreamT {
+    /// Bytes a message of the stream takes on the wire, without escaped
+    /// bytes. The sensor samples share their frames, so it is the share of a
+    /// sample for the live sensor stream.
+    pub fn message_size(&self) -> u32 {
+        CHECKSUM_SIZE
+            + match self {
+                StreamT::Heartbeat => 12,
+                StreamT::Health => 30,
+                StreamT::LinkStats => 45,
+                StreamT::Motors => 22,
+                StreamT::DebugInfo => 38,
+                StreamT::LogReport => 51,
+                StreamT::SensorStream => 11,
+            }
+    }
+}
+
+/// Period of every telemetry stream, in ticks of the control loop (0 when it
+/// is off). The streams together never use more than `budget` bytes per
+/// second: a stream that does not fit in what the others leave is slowed
+/// down, or switched off when nothing is left.
+///
+/// The heartbeat tells the runner that the drone is alive, it is not counted
+/// in the budget, can not be switched off once it is on nor be slower than
+/// `HEARTBEAT_INTERVAL_MAX_MS`.
+pub struct StreamRates {
+    periods: [u32; STREAM_COUNT],
+    tick_frequency: u32, // ticks per second
+    budget: u32,         // bytes per second
+}
+
+impl StreamRates {
+    /// All the streams are off.
+    pub const fn new(tick_frequency: u32, budget: u32) -> Self {
+        Self {
+            periods: [0; STREAM_COUNT],
+            tick_frequency,
+            budget,
+        }
+    }
+
+    pub fn period(&self, stream: StreamT) -> u32 {
+        self.periods[stream.index()]
+    }
+
+    /// Sends `stream` once every `period` ticks, or never if 0.
+    /// @return the period applied, longer than `period` if the budget does
+    /// not allow it, the current one if 0 is asked for the heartbeat and at
+    /// most `heartbeat_period_max` for it
+    pub fn set_period(&mut self, stream: StreamT, period: u32) -> u32 {
+        if stream == StreamT::Heartbeat {
+            if period != 0 {
+                self.periods[stream.index()] =
+                    period.min(heartbeat_period_max(self.tick_frequency).max(1));
+            }
+            return self.period(stream);
+        }
+
+        self.periods[stream.index()] = 0;
+
+        let applied: u32 = if period == 0 {
+            0
+        } else {
+            match self.budget.checked_sub(self.used()) {
+                None | Some(0) => 0,
+                // the shortest period the rest of the budget allows
+                Some(left) => period.max(self.cost(stream, 1).div_ceil(left)),
+            }
+        };
+
+        self.periods[stream.index()] = applied;
+        applied
+    }
+
+    /// Same as `set_period`, with the time between two messages in
+    /// milliseconds.
+    pub fn set_interval_ms(&mut self, stream: StreamT, interval_ms: u16) -> u16 {
+        let period: u32 = match interval_ms {
+            0 => 0,
+            ms => (ms as u32 * self.tick_frequency).div_ceil(1000).max(1),
+        };
+
+        let applied: u32 = self.set_period(stream, period);
+        self.to_ms(applied)
+    }
+
+    /// Checks if `stream` has to be sent at the tick `iter_count`.
+    pub fn is_due(&self, stream: StreamT, iter_count: u32) -> bool {
+        match self.period(stream) {
+            0 => false,
+            period => iter_count.is_multiple_of(period),
+        }
+    }
+
+    /// Bytes per second used by the streams at their current rates, without
+    /// the heartbeat.
+    pub fn used(&self) -> u32 {
+        StreamT::ALL
+            .iter()
+            .filter(|stream| **stream != StreamT::Heartbeat)
+            .map(|stream| match self.period(*stream) {
+                0 => 0,
+                period => self.cost(*stream, period),
+            })
+            .sum()
+    }
+
+    /// The rates applied, to be reported to the runner.
+    pub fn report(&self) -> StreamRatesDT {
+        let mut report: StreamRatesDT = StreamRatesDT {
+            budget: self.budget,
+            used: self.used(),
+            ..StreamRatesDT::default()
+        };
+
+        for stream in StreamT::ALL.iter() {
+            report.interval_ms[stream.index()] = self.to_ms(self.period(*stream));
+        }
+
+        report
+    }
+
+    // bytes per second of `stream` sent once every `period` ticks
+    fn cost(&self, stream: StreamT, period: u32) -> u32 {
+        (stream.message_size() * self.tick_frequency).div_ceil(period)
+    }
+
+    fn to_ms(&self, period: u32) -> u16 {
+        (period as u64 * 1000 / self.tick_frequency.max(1) as u64).min(u16::MAX as u64) as u16
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::protocol::*;
+    use crate::telemetry::rates::*;
+    use crate::DroneMode;
+
+    #[test]
+    fn test_message_sizes() {
+        let heartbeat: DataT = DataT::Heartbeat(HeartbeatDT {
+            mode: DroneMode::FullControl,
+            armed: true,
+            calibrated: true,
+            logging: true,
+            log_report: true,
+            battery: BatteryT::Critical,
+            faults: u8::MAX,
+        });
+        let health: DataT = DataT::HealthData(HealthDT {
+            time: Timestamp::MAX,
+            bat: u16::MAX,
+            cpu: 100,
+            pres: 200_000,
+            loop_avg: u16::MAX,
+            loop_max: u16::MAX,
+            jitter: u16::MAX,
+            overruns: u16::MAX,
+        });
+        let stats: DataT = DataT::LinkStats(LinkStatsDT {
+            frames_received: u32::MAX,
+            frames_sent: u32::MAX,
+            crc_errors: u32::MAX,
+            des_errors: u32::MAX,
+            resyncs: u32::MAX,
+            skipped_bytes: u32::MAX,
+            busy_sends: u32::MAX,
+            auth_errors: u32::MAX,
+        });
+        let motors: DataT = DataT::MotorsState(MotorsDT {
+            time: Timestamp::MAX,
+            ae1: u16::MAX,
+            ae2: u16::MAX,
+            ae3: u16::MAX,
+            ae4: u16::MAX,
+        });
+        let debug: DataT =
+            DataT::Message(heapless::String::from("E r 0.00000000000000000000000001"));
+        let log: DataT = DataT::SensorStream(SensorStreamDT {
+            live: false,
+            data: heapless::Vec::from_slice(&[0; SENSOR_STREAM_SIZE]).unwrap(),
+        });
+
+        for (stream, data) in [
+            (StreamT::Heartbeat, heartbeat),
+            (StreamT::Health, health),
+            (StreamT::LinkStats, stats),
+            (StreamT::Motors, motors),
+            (StreamT::DebugInfo, debug),
+            (StreamT::LogReport, log),
+        ] {
+            let frame: heapless::Vec<u8, 128> = data.to_packet(0).unwrap();
+            assert!(frame.len() as u32 <= stream.message_size(), "{:?}", stream);
+        }
+    }
+
+    #[test]
+    fn test_rates() {
+        let mut rates: StreamRates = StreamRates::new(100, 10_000);
+        assert_eq!(rates.used(), 0);
+        assert!(!rates.is_due(StreamT::Health, 0));
+
+        assert_eq!(rates.set_interval_ms(StreamT::Health, 1000), 1000);
+        assert_eq!(rates.period(StreamT::Health), 100);
+        assert!(rates.is_due(StreamT::Health, 200));
+        assert!(!rates.is_due(StreamT::Health, 250));
+        assert_eq!(rates.used(), StreamT::Health.message_size());
+
+        // faster than the loop runs
+        assert_eq!(rates.set_interval_ms(StreamT::Heartbeat, 1), 10);
+
+        assert_eq!(rates.set_interval_ms(StreamT::Health, 0), 0);
+        assert_eq!(rates.used(), 0);
+        assert_eq!(rates.report().interval_ms, [10, 0, 0, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_heartbeat_is_never_off() {
+        let budget: u32 = 20 * StreamT::LogReport.message_size();
+        let mut rates: StreamRates = StreamRates::new(100, budget);
+
+        // the rest of the streams take the whole budget, the heartbeat is
+        // not slowed down
+        assert_eq!(rates.set_period(StreamT::LogReport, 1), 5);
+        assert_eq!(rates.set_period(StreamT::Heartbeat, 1), 1);
+        assert_eq!(rates.used(), budget);
+        assert_eq!(rates.set_period(StreamT::LogReport, 1), 5);
+
+        // switching it off is refused
+        assert_eq!(rates.set_period(StreamT::Heartbeat, 0), 1);
+        assert_eq!(rates.set_interval_ms(StreamT::Heartbeat, 0), 10);
+        assert!(rates.is_due(StreamT::Heartbeat, 7));
+    }
+
+    #[test]
+    fn test_heartbeat_is_never_too_slow() {
+        let mut rates: StreamRates = StreamRates::new(100, 0);
+
+        // one heartbeat a second at most, half of the timeout of the runner
+        assert_eq!(heartbeat_period_max(100), 100);
+        assert_eq!(rates.set_period(StreamT::Heartbeat, u32::MAX), 100);
+        assert_eq!(rates.set_interval_ms(StreamT::Heartbeat, 5000), 1000);
+        assert_eq!(rates.set_period(StreamT::Heartbeat, 100), 100);
+        assert_eq!(rates.set_period(StreamT::Heartbeat, 40), 40);
+    }
+
+    #[test]
+    fn test_budget_is_enforced() {
+        // a log report every 5 ticks
+        let budget: u32 = 20 * StreamT::LogReport.message_size();
+        let mut rates: StreamRates = StreamRates::new(100, budget);
+
+        // one every tick asked, the whole budget allows one every 5
+        assert_eq!(rates.set_period(StreamT::LogReport, 1), 5);
+        assert_eq!(rates.used(), budget);
+
+        // nothing left
+        assert_eq!(rates.set_period(StreamT::Health, 10), 0);
+        assert!(!rates.is_due(StreamT::Health, 0));
+
+        // a stream gets its own share back when it is set again
+        assert_eq!(rates.set_period(StreamT::LogReport, 10), 10);
+        assert_eq!(rates.set_period(StreamT::Health, 10), 10);
+
+        // slowed down to what is left
+        let period: u32 = rates.set_period(StreamT::Motors, 1);
+        assert!(period > 1);
+        assert!(rates.used() <= budget);
+
+        let report: StreamRatesDT = rates.report();
+        assert_eq!(report.budget, budget);
+        assert_eq!(report.used, rates.used());
+    }
+}
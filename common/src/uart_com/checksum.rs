@@ -0,0 +1,73 @@
+use crc::{Crc, CRC_16_IBM_3740, CRC_32_ISO_HDLC, CRC_8_LTE};
+use serde::{de::DeserializeOwned, Serialize};
+
+// CRC calculation objects (the tables end up in flash)
+const CRC8: Crc<u8> = Crc::<u8>::new(&CRC_8_LTE);
+const CRC16: Crc<u16> = Crc::<u16>::new(&CRC_16_IBM_3740);
+const CRC32: Crc<u32> = Crc::<u32>::new(&CRC_32_ISO_HDLC);
+
+/// Integrity check stored in the header of every frame. It covers the
+/// sequence number followed by the payload.
+pub trait Checksum {
+    /// Value stored in `HeaderT`.
+    type Value: Serialize + DeserializeOwned + PartialEq + Copy;
+
+    fn compute(seq: u8, data: &[u8]) -> Self::Value;
+}
+
+/// CRC-8/LTE, 1 byte per frame. Lets through about 1 in 256 corrupted frames.
+pub struct Crc8;
+
+/// CRC-16/CCITT-FALSE, 2 bytes per frame.
+pub struct Crc16;
+
+/// CRC-32 (ISO-HDLC, the Ethernet one), 4 bytes per frame.
+pub struct Crc32;
+
+impl Checksum for Crc8 {
+    type Value = u8;
+
+    #[inline]
+    fn compute(seq: u8, data: &[u8]) -> u8 {
+        let mut digest = CRC8.digest();
+        digest.update(&[seq]);
+        digest.update(data);
+        digest.finalize()
+    }
+}
+
+impl Checksum for Crc16 {
+    type Value = u16;
+
+    #[inline]
+    fn compute(seq: u8, data: &[u8]) -> u16 {
+        let mut digest = CRC16.digest();
+        digest.update(&[seq]);
+        digest.update(data);
+        digest.finalize()
+    }
+}
+
+impl Checksum for Crc32 {
+    type Value = u32;
+
+    #[inline]
+    fn compute(seq: u8, data: &[u8]) -> u32 {
+        let mut digest = CRC32.digest();
+        digest.update(&[seq]);
+        digest.update(data);
+        digest.finalize()
+    }
+}
+
+/// Checksum used by `frame` and `unframe`, selected with the `crc16` or
+/// `crc32` features of `common` (the strongest enabled one wins). The drone
+/// and the runner must be built with the same one.
+#[cfg(feature = "crc32")]
+pub type DefaultChecksum = Crc32;
+
+#[cfg(all(feature = "crc16", not(feature = "crc32")))]
+pub type DefaultChecksum = Crc16;
+
+#[cfg(not(any(feature = "crc16", feature = "crc32")))]
+pub type DefaultChecksum = Crc8;
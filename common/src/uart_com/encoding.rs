@@ -0,0 +1,269 @@
+use crate::uart_com::{is_special, Error, END_BYTE, ESCAPE_BYTE, MASK, START_BYTE};
+
+/// Turns a serialized packet into a frame that can be found again in a byte
+/// stream, and back.
+pub trait Encoding {
+    /// Byte every frame starts with. `None` if a frame simply starts after the
+    /// end of the previous one.
+    const START: Option<u8>;
+
+    /// Byte every frame ends with. It never appears inside a frame.
+    const END: u8;
+
+    /// @input data - byte vector
+    /// @return byte stream ready to be sent over the wire (a frame) OR error
+    fn encode<const CAP: usize>(
+        data: heapless::Vec<u8, CAP>,
+    ) -> Result<heapless::Vec<u8, CAP>, Error>;
+
+    /// Decodes the frame over itself, the decoded bytes are never longer than
+    /// the frame.
+    /// @input frame - a whole frame, including `START` and `END`
+    /// @return length of the byte stream ready to be deserialized, found at the
+    /// beginning of `frame` OR error
+    fn decode_in_place(frame: &mut [u8]) -> Result<usize, Error>;
+
+    /// @input frame - a whole frame, including `START` and `END`
+    /// @return byte stream ready to be deserialized OR error
+    #[inline]
+    fn decode<const CAP: usize>(
+        frame: heapless::Vec<u8, CAP>,
+    ) -> Result<heapless::Vec<u8, CAP>, Error> {
+        let mut frame: heapless::Vec<u8, CAP> = frame;
+
+        let len: usize = Self::decode_in_place(&mut frame)?;
+        frame.truncate(len);
+
+        return Ok(frame);
+    }
+}
+
+/// Frames enclosed between `START_BYTE` and `END_BYTE`, where the special
+/// bytes are escaped with `ESCAPE_BYTE` and masked. Up to twice the size of
+/// the data in the worst case.
+pub struct Escape;
+
+/// Consistent Overhead Byte Stuffing: one extra byte per 254 bytes of data and
+/// a zero delimiter at the end of every frame.
+pub struct Cobs;
+
+// COBS code of a full block (254 non-zero bytes, no zero after it)
+const COBS_FULL_BLOCK: u8 = 0xFF;
+const COBS_DELIMITER: u8 = 0;
+
+impl Encoding for Escape {
+    const START: Option<u8> = Some(START_BYTE);
+    const END: u8 = END_BYTE;
+
+    #[inline]
+    fn encode<const CAP: usize>(
+        data: heapless::Vec<u8, CAP>,
+    ) -> Result<heapless::Vec<u8, CAP>, Error> {
+        let mut ret: heapless::Vec<u8, CAP> = heapless::Vec::<u8, CAP>::new();
+
+        // insert the starting byte of the stream
+        check_OCS!(ret.push(START_BYTE));
+
+        // make sure the special bytes do not appear in the transmitted byte stream
+        for data_byte in data {
+            if is_special(data_byte) {
+                check_OCS!(ret.push(ESCAPE_BYTE));
+                check_OCS!(ret.push(data_byte ^ MASK));
+            } else {
+                check_OCS!(ret.push(data_byte));
+            }
+        }
+
+        // insert the ending byte of the stream
+        check_OCS!(ret.push(END_BYTE));
+
+        return Ok(ret);
+    }
+
+    #[inline]
+    fn decode_in_place(frame: &mut [u8]) -> Result<usize, Error> {
+        if frame.len() < 2 {
+            return Err(Error::Frame);
+        }
+
+        // the write position is always behind the read one
+        let mut len: usize = 0;
+
+        let mut escape: bool = false;
+        // i range takes care of START_BYTE and END_BYTE
+        for i in 1..(frame.len() - 1) {
+            if escape {
+                frame[len] = frame[i] ^ MASK;
+                len += 1;
+                escape = false;
+            } else if frame[i] == ESCAPE_BYTE {
+                escape = true;
+            } else {
+                frame[len] = frame[i];
+                len += 1;
+            }
+        }
+
+        return Ok(len);
+    }
+}
+
+impl Encoding for Cobs {
+    const START: Option<u8> = None;
+    const END: u8 = COBS_DELIMITER;
+
+    #[inline]
+    fn encode<const CAP: usize>(
+        data: heapless::Vec<u8, CAP>,
+    ) -> Result<heapless::Vec<u8, CAP>, Error> {
+        let mut ret: heapless::Vec<u8, CAP> = heapless::Vec::<u8, CAP>::new();
+
+        // position of the code byte of the current block and its value
+        let mut code_pos: usize = 0;
+        let mut code: u8 = 1;
+        check_OCS!(ret.push(0));
+
+        for data_byte in data {
+            if data_byte != 0 {
+                check_OCS!(ret.push(data_byte));
+                code += 1;
+            }
+
+            if data_byte == 0 || code == COBS_FULL_BLOCK {
+                // close the block and start a new one
+                ret[code_pos] = code;
+                code_pos = ret.len();
+                code = 1;
+                check_OCS!(ret.push(0));
+            }
+        }
+
+        ret[code_pos] = code;
+        check_OCS!(ret.push(COBS_DELIMITER));
+
+        return Ok(ret);
+    }
+
+    #[inline]
+    fn decode_in_place(frame: &mut [u8]) -> Result<usize, Error> {
+        // the shortest frame is an empty block and the delimiter
+        if frame.len() < 2 || frame[frame.len() - 1] != COBS_DELIMITER {
+            return Err(Error::Frame);
+        }
+
+        let encoded_len: usize = frame.len() - 1;
+        // every block is at least as long as what it decodes to, so the write
+        // position is never ahead of the read one
+        let mut len: usize = 0;
+
+        let mut i: usize = 0;
+        while i < encoded_len {
+            let code: u8 = frame[i];
+            let block_end: usize = i + code as usize;
+
+            if code == COBS_DELIMITER || block_end > encoded_len {
+                return Err(Error::Frame);
+            }
+
+            for j in (i + 1)..block_end {
+                if frame[j] == COBS_DELIMITER {
+                    return Err(Error::Frame);
+                }
+                frame[len] = frame[j];
+                len += 1;
+            }
+
+            // every block but the full ones and the last one stands for a zero
+            if code != COBS_FULL_BLOCK && block_end < encoded_len {
+                frame[len] = 0;
+                len += 1;
+            }
+
+            i = block_end;
+        }
+
+        return Ok(len);
+    }
+}
+
+/// Encoding used by `frame` and `unframe`, `Escape` unless the `cobs` feature
+/// of `common` is enabled. The drone and the runner must be built with the
+/// same one.
+#[cfg(not(feature = "cobs"))]
+pub type DefaultEncoding = Escape;
+
+#[cfg(feature = "cobs")]
+pub type DefaultEncoding = Cobs;
+
+#[cfg(test)]
+mod test {
+    use crate::uart_com::encoding::*;
+
+    fn round_trip<E: Encoding>(data: &[u8]) {
+        let data: heapless::Vec<u8, 600> = heapless::Vec::<u8, 600>::from_slice(data).unwrap();
+        let encoded: heapless::Vec<u8, 600> = E::encode(data.clone()).unwrap();
+
+        // the end byte must only appear at the end
+        assert_eq!(
+            encoded.iter().position(|b| *b == E::END),
+            Some(encoded.len() - 1)
+        );
+
+        assert_eq!(E::decode(encoded).unwrap(), data);
+    }
+
+    #[test]
+    fn test_cobs_known_values() {
+        let encoded: heapless::Vec<u8, 16> =
+            Cobs::encode(heapless::Vec::<u8, 16>::from_slice(&[0x11, 0x22, 0x00, 0x33]).unwrap())
+                .unwrap();
+        assert_eq!(&encoded[..], &[0x03, 0x11, 0x22, 0x02, 0x33, 0x00]);
+
+        let encoded: heapless::Vec<u8, 16> =
+            Cobs::encode(heapless::Vec::<u8, 16>::from_slice(&[0x00]).unwrap()).unwrap();
+        assert_eq!(&encoded[..], &[0x01, 0x01, 0x00]);
+
+        let encoded: heapless::Vec<u8, 16> = Cobs::encode(heapless::Vec::<u8, 16>::new()).unwrap();
+        assert_eq!(&encoded[..], &[0x01, 0x00]);
+    }
+
+    #[test]
+    fn test_round_trips() {
+        let mut long: [u8; 520] = [0; 520];
+        for (i, b) in long.iter_mut().enumerate() {
+            *b = (i % 255) as u8 + 1;
+        }
+
+        for data in [
+            &[][..],
+            &[0],
+            &[0, 0, 0],
+            &[1, 2, 0, 3, 0],
+            &[START_BYTE, END_BYTE, ESCAPE_BYTE, MASK],
+            &long[..253],
+            &long[..254],
+            &long[..255],
+            &long[..],
+        ] {
+            round_trip::<Escape>(data);
+            round_trip::<Cobs>(data);
+        }
+    }
+
+    #[test]
+    fn test_cobs_overhead_is_bounded() {
+        let data: heapless::Vec<u8, 256> =
+            heapless::Vec::<u8, 256>::from_slice(&[0xAB; 200]).unwrap();
+
+        // one code byte and the delimiter
+        assert_eq!(Cobs::encode(data).unwrap().len(), 202);
+    }
+
+    #[test]
+    fn test_cobs_malformed() {
+        for frame in [&[0x00][..], &[0x05, 0x11, 0x00], &[0x02, 0x11]] {
+            let frame: heapless::Vec<u8, 16> = heapless::Vec::<u8, 16>::from_slice(frame).unwrap();
+            assert_eq!(Cobs::decode(frame).err().unwrap(), Error::Frame);
+        }
+    }
+}
@@ -1,4 +1,3 @@
-use crc::{Crc, CRC_8_LTE};
 use serde::{Deserialize, Serialize};
 
 // MACROS that make life easyer
@@ -25,14 +24,28 @@ macro_rules! check_LibBug {
     };
 }
 
+mod checksum;
+mod encoding;
+
+pub use checksum::{Checksum, Crc16, Crc32, Crc8, DefaultChecksum};
+pub use encoding::{Cobs, DefaultEncoding, Encoding, Escape};
+
 // constants
 pub const START_BYTE: u8 = '<' as u8;
 pub const END_BYTE: u8 = '>' as u8;
 const ESCAPE_BYTE: u8 = '\\' as u8;
 const MASK: u8 = { 1 << 3 } as u8;
 
-// CRC calculation object
-const LTE: Crc<u8> = Crc::<u8>::new(&CRC_8_LTE);
+/// Checks if `byte` is escaped by `Escape`, it then takes two bytes of the
+/// frame.
+#[inline]
+pub fn is_special(byte: u8) -> bool {
+    byte == START_BYTE || byte == END_BYTE || byte == ESCAPE_BYTE
+}
+
+/// Sequence number of frames that are not tracked by the reliable channel
+/// (fire-and-forget).
+pub const NO_SEQ: u8 = 0;
 
 // ERROR codes
 #[derive(Debug, PartialEq)]
@@ -40,39 +53,60 @@ pub enum Error {
     CRC,    // CRC error in the packet
     Des,    // failed the deserialize opperation
     ENOMEM, // out of space (CAP too small most probably)
+    Frame,  // the frame is not encoded correctly
 
     LibBug, // shoud not happen, but who knows
 }
 
 // packet structure to be sent over UART
 #[derive(Serialize, Deserialize)]
-struct HeaderT {
-    crc: u8,
+struct HeaderT<V> {
+    seq: u8, // NO_SEQ for best-effort frames
+    crc: V,  // covers `seq` and the payload
 }
 
-#[derive(Serialize, Deserialize)]
-struct PacketT<const CAP: usize> {
-    header: HeaderT,
+#[derive(Serialize)]
+struct PacketT<V, const CAP: usize> {
+    header: HeaderT<V>,
     payload: heapless::Vec<u8, CAP>,
 }
 
+// same wire format as `PacketT`, but the payload borrows from the frame
+#[derive(Deserialize)]
+struct PacketRefT<'a, V> {
+    header: HeaderT<V>,
+    payload: &'a [u8],
+}
+
 /// Creates a frame ready to be sent over the wire.
 /// @input data - byte vector
+/// @input seq - sequence number of the frame (`NO_SEQ` if not tracked)
 /// @return byte stream ready to be sent over the wire (a frame) OR error
 #[inline]
 pub fn frame<const CAP: usize>(
     data: heapless::Vec<u8, CAP>,
+    seq: u8,
+) -> Result<heapless::Vec<u8, CAP>, Error> {
+    frame_with::<DefaultChecksum, DefaultEncoding, CAP>(data, seq)
+}
+
+/// Same as `frame`, but protected with the checksum `C` and encoded with `E`.
+#[inline]
+pub fn frame_with<C: Checksum, E: Encoding, const CAP: usize>(
+    data: heapless::Vec<u8, CAP>,
+    seq: u8,
 ) -> Result<heapless::Vec<u8, CAP>, Error> {
-    let packet: PacketT<CAP> = PacketT::<CAP> {
+    let packet: PacketT<C::Value, CAP> = PacketT::<C::Value, CAP> {
         header: HeaderT {
-            crc: LTE.checksum(&data),
+            seq,
+            crc: C::compute(seq, &data),
         },
         payload: data,
     };
 
-    match postcard::to_vec::<PacketT<CAP>, CAP>(&packet) {
+    match postcard::to_vec::<PacketT<C::Value, CAP>, CAP>(&packet) {
         Ok(serialized) => {
-            return prepare_frame::<CAP>(serialized);
+            return E::encode::<CAP>(serialized);
         }
         Err(_) => {
             return Err(Error::ENOMEM);
@@ -81,84 +115,57 @@ pub fn frame<const CAP: usize>(
 }
 
 /// Unwraps the frame that was sent over the wire.
-/// @input frame - must start with START_BYTE and end in END_BYTE
-/// @return data in byte format OR error
+/// @input frame - a whole frame of the default encoding
+/// @return the sequence number and the data in byte format OR error
 #[inline]
 pub fn unframe<const CAP: usize>(
     frame: heapless::Vec<u8, CAP>,
-) -> Result<heapless::Vec<u8, CAP>, Error> {
-    let serialized: heapless::Vec<u8, CAP> = extract_data::<CAP>(frame)?;
-
-    match postcard::from_bytes::<PacketT<CAP>>(&serialized) {
-        Ok(packet) => {
-            if packet.header.crc != LTE.checksum(&packet.payload) {
-                return Err(Error::CRC);
-            }
-
-            return Ok(packet.payload);
-        }
-        Err(_) => {
-            return Err(Error::Des);
-        }
-    };
+) -> Result<(u8, heapless::Vec<u8, CAP>), Error> {
+    unframe_with::<DefaultChecksum, DefaultEncoding, CAP>(frame)
 }
 
-/// Encapsulate the "data" between START_BYTE and END_BYTE and uniquifyes the
-/// middle part no not contain them
-/// @input data - byte vector
-///
-/// @return byte stream ready to be sent over the wire (a frame) OR error
+/// Same as `unframe`, for frames protected with the checksum `C` and encoded
+/// with `E`.
 #[inline]
-fn prepare_frame<const CAP: usize>(
-    data: heapless::Vec<u8, CAP>,
-) -> Result<heapless::Vec<u8, CAP>, Error> {
-    let mut ret: heapless::Vec<u8, CAP> = heapless::Vec::<u8, CAP>::new();
-
-    // insert the starting byte of the stream
-    check_OCS!(ret.push(START_BYTE));
-
-    // make sure the special bytes do not appear in the transmitted byte stream
-    for data_byte in data {
-        if data_byte == START_BYTE || data_byte == END_BYTE || data_byte == ESCAPE_BYTE {
-            check_OCS!(ret.push(ESCAPE_BYTE as u8));
-            check_OCS!(ret.push(data_byte ^ MASK));
-        } else {
-            check_OCS!(ret.push(data_byte));
-        }
-    }
+pub fn unframe_with<C: Checksum, E: Encoding, const CAP: usize>(
+    frame: heapless::Vec<u8, CAP>,
+) -> Result<(u8, heapless::Vec<u8, CAP>), Error> {
+    let mut frame: heapless::Vec<u8, CAP> = frame;
+    let (seq, payload) = unframe_in_place::<C, E>(&mut frame)?;
 
-    // insert the ending byte of the stream
-    check_OCS!(ret.push(END_BYTE));
+    // the payload is part of the frame, so it always fits
+    let mut ret: heapless::Vec<u8, CAP> = heapless::Vec::<u8, CAP>::new();
+    check_LibBug!(ret.extend_from_slice(payload));
 
-    return Ok(ret);
+    return Ok((seq, ret));
 }
 
-/// Extract the "data" from between START_BYTE and END_BYTE dis-uniquifying the
-/// middle part to get the original
-/// @input frame - must start with START_BYTE and end in END_BYTE
-
-/// @return byte stream ready to be deserialized OR error
+/// Unwraps a frame without copying it: the frame is decoded over itself and
+/// the payload borrows from it.
+/// @input frame - a whole frame protected with `C` and encoded with `E`, it
+/// is overwritten
+/// @return the sequence number and the payload OR error
 #[inline]
-fn extract_data<const CAP: usize>(
-    frame: heapless::Vec<u8, CAP>,
-) -> Result<heapless::Vec<u8, CAP>, Error> {
-    // TODO: make more memory efficient by repurposing "frame" instead of "ret"
-    let mut ret: heapless::Vec<u8, CAP> = heapless::Vec::<u8, CAP>::new();
+pub fn unframe_in_place<C: Checksum, E: Encoding>(frame: &mut [u8]) -> Result<(u8, &[u8]), Error> {
+    let len: usize = E::decode_in_place(frame)?;
 
-    let mut escape: bool = false;
-    // i range takes care of START_BYTE and END_BYTE
-    for i in 1..(frame.len() - 1) {
-        if escape {
-            check_LibBug!(ret.push(frame[i] ^ MASK));
-            escape = false;
-        } else if frame[i] == ESCAPE_BYTE as u8 {
-            escape = true;
-        } else {
-            check_LibBug!(ret.push(frame[i]));
-        }
-    }
+    match postcard::take_from_bytes::<PacketRefT<C::Value>>(&frame[..len]) {
+        Ok((packet, rest)) => {
+            // the length of the payload is not covered by the checksum, a
+            // packet that does not end with the frame has a corrupted one
+            if !rest.is_empty() {
+                return Err(Error::Des);
+            }
+            if packet.header.crc != C::compute(packet.header.seq, packet.payload) {
+                return Err(Error::CRC);
+            }
 
-    return Ok(ret);
+            return Ok((packet.header.seq, packet.payload));
+        }
+        Err(_) => {
+            return Err(Error::Des);
+        }
+    };
 }
 
 // --------------- TESTING ---------------
@@ -171,8 +178,8 @@ fn frame_and_unframe() {
     let reference = data.clone();
 
     assert_eq!(
-        unframe::<64>(frame::<64>(data).unwrap()).unwrap(),
-        reference
+        unframe::<64>(frame::<64>(data, NO_SEQ).unwrap()).unwrap(),
+        (NO_SEQ, reference)
     );
 }
 
@@ -182,15 +189,54 @@ fn frame_and_unframe_escape_bytes_only() {
         heapless::Vec::<u8, 64>::from_slice(&[START_BYTE, END_BYTE, ESCAPE_BYTE, MASK]).unwrap();
 
     assert_eq!(
-        unframe::<64>(frame::<64>(data.clone()).unwrap()).unwrap(),
-        data
+        unframe::<64>(frame::<64>(data.clone(), NO_SEQ).unwrap()).unwrap(),
+        (NO_SEQ, data)
+    );
+}
+
+#[test]
+fn frame_and_unframe_keeps_seq() {
+    let data: heapless::Vec<u8, 64> = heapless::Vec::<u8, 64>::from_slice(&[7, 8, 9]).unwrap();
+
+    assert_eq!(
+        unframe::<64>(frame::<64>(data.clone(), 42).unwrap()).unwrap(),
+        (42, data)
     );
 }
 
+#[test]
+fn unframe_corrupted_seq() {
+    let data: heapless::Vec<u8, 64> = heapless::Vec::<u8, 64>::from_slice(&[7, 8, 9]).unwrap();
+    let mut framed: heapless::Vec<u8, 64> = frame::<64>(data, 42).unwrap();
+
+    // the sequence number is the first serialized byte after START_BYTE
+    framed[1] = 43;
+
+    assert_eq!(unframe::<64>(framed).err().unwrap(), Error::CRC);
+}
+
+#[test]
+fn unframe_in_place_borrows_the_frame() {
+    let data: heapless::Vec<u8, 64> =
+        heapless::Vec::<u8, 64>::from_slice(&[1, START_BYTE, 3]).unwrap();
+    let mut framed: heapless::Vec<u8, 64> = frame::<64>(data, 9).unwrap();
+    let range = framed.as_ptr_range();
+
+    let (seq, payload) = unframe_in_place::<DefaultChecksum, DefaultEncoding>(&mut framed).unwrap();
+
+    assert_eq!((seq, payload), (9, &[1, START_BYTE, 3][..]));
+    assert!(range.contains(&payload.as_ptr()));
+}
+
 #[test]
 fn unframe_bad_data() {
     let data = heapless::Vec::<u8, 64>::from_slice(&[100, 22, 30, 30, 30, 30, 30, 20]).unwrap();
-    assert_eq!(unframe::<64>(data).err().unwrap(), Error::Des);
+    assert_eq!(
+        unframe_with::<DefaultChecksum, Escape, 64>(data)
+            .err()
+            .unwrap(),
+        Error::Des
+    );
 }
 
 #[test]
@@ -203,3 +249,63 @@ fn token_exclusivity() {
     assert_ne!(END_BYTE ^ MASK, START_BYTE);
     assert_ne!(END_BYTE ^ MASK, ESCAPE_BYTE);
 }
+
+// frames some data with `C`, checks the round trip and that a flipped payload
+// bit is caught
+#[cfg(test)]
+fn check_round_trip_and_corruption<C: Checksum>() {
+    let data: heapless::Vec<u8, 64> =
+        heapless::Vec::<u8, 64>::from_slice(&[1, 2, 3, 4, START_BYTE, 6]).unwrap();
+
+    let framed: heapless::Vec<u8, 64> = frame_with::<C, Escape, 64>(data.clone(), 42).unwrap();
+    assert_eq!(
+        unframe_with::<C, Escape, 64>(framed.clone()).unwrap(),
+        (42, data)
+    );
+
+    // the last byte before END_BYTE belongs to the payload and is not escaped
+    let mut corrupted: heapless::Vec<u8, 64> = framed;
+    let last: usize = corrupted.len() - 2;
+    corrupted[last] ^= 0x01;
+    assert_eq!(
+        unframe_with::<C, Escape, 64>(corrupted).err().unwrap(),
+        Error::CRC
+    );
+}
+
+#[test]
+fn crc8_round_trip_and_corruption() {
+    check_round_trip_and_corruption::<Crc8>();
+}
+
+#[test]
+fn crc16_round_trip_and_corruption() {
+    check_round_trip_and_corruption::<Crc16>();
+}
+
+#[test]
+fn crc32_round_trip_and_corruption() {
+    check_round_trip_and_corruption::<Crc32>();
+}
+
+#[test]
+fn checksum_mismatch_is_rejected() {
+    let data: heapless::Vec<u8, 64> = heapless::Vec::<u8, 64>::from_slice(&[7, 8, 9]).unwrap();
+
+    let framed: heapless::Vec<u8, 64> =
+        frame_with::<Crc32, DefaultEncoding, 64>(data.clone(), 1).unwrap();
+    assert!(unframe_with::<Crc8, DefaultEncoding, 64>(framed).is_err());
+
+    let framed: heapless::Vec<u8, 64> = frame_with::<Crc8, DefaultEncoding, 64>(data, 1).unwrap();
+    assert!(unframe_with::<Crc16, DefaultEncoding, 64>(framed).is_err());
+}
+
+#[test]
+fn default_checksum_is_used() {
+    let data: heapless::Vec<u8, 64> = heapless::Vec::<u8, 64>::from_slice(&[7, 8, 9]).unwrap();
+
+    assert_eq!(
+        frame::<64>(data.clone(), 3).unwrap(),
+        frame_with::<DefaultChecksum, DefaultEncoding, 64>(data, 3).unwrap()
+    );
+}
@@ -0,0 +1,253 @@
+//! mapping of the drone clock to the clock of the runner
+
+use crate::protocol::Timestamp;
+
+/// Number of time sync exchanges the estimate is based on.
+pub const CLOCK_SAMPLES: usize = 16;
+
+const PPB: i64 = 1_000_000_000;
+
+#[derive(Debug, Clone, Copy)]
+struct Sample {
+    drone: i64,  // unwrapped drone time, in microseconds
+    offset: i64, // host time minus drone time, in microseconds
+    rtt: i64,    // round trip time of the exchange, in microseconds
+}
+
+/// Estimates the offset and the drift of the drone clock from the time sync
+/// exchanges with it, in the style of NTP: the drone time is assumed to be
+/// taken halfway through the round trip.
+///
+/// The delays of the link are not symmetric, but the exchanges with the
+/// shortest round trip are the least affected by it. The offset is therefore
+/// taken from the fastest exchange of the last `CLOCK_SAMPLES`, and the drift
+/// from the fastest exchange of the older and of the newer half of them.
+///
+/// The host time is whatever monotonic clock the caller uses, in
+/// microseconds. The drone time wraps around (see `Timestamp`) and is
+/// unwrapped here, so the exchanges must be pushed at least every half an hour.
+pub struct ClockSync {
+    samples: heapless::Deque<Sample, CLOCK_SAMPLES>,
+
+    // last drone time pushed, raw and unwrapped
+    last: Option<(Timestamp, i64)>,
+
+    // current estimate
+    reference: Option<Sample>,
+    drift_ppb: i64,
+}
+
+impl ClockSync {
+    pub fn new() -> Self {
+        Self {
+            samples: heapless::Deque::new(),
+            last: None,
+            reference: None,
+            drift_ppb: 0,
+        }
+    }
+
+    /// Adds an exchange: the request left the host at `host_sent`, the drone
+    /// answered with `drone_time` and the answer came back at
+    /// `host_received`. Returns `false` if the exchange makes no sense and was
+    /// ignored.
+    pub fn push(&mut self, host_sent: u64, drone_time: Timestamp, host_received: u64) -> bool {
+        if host_received < host_sent {
+            return false;
+        }
+
+        let drone: i64 = self.unwrap(drone_time);
+        self.last = Some((drone_time, drone));
+
+        let host_sent: i64 = host_sent as i64;
+        let host_received: i64 = host_received as i64;
+
+        if self.samples.is_full() {
+            self.samples.pop_front();
+        }
+        // room was made above
+        let _ = self.samples.push_back(Sample {
+            drone,
+            offset: host_sent + (host_received - host_sent) / 2 - drone,
+            rtt: host_received - host_sent,
+        });
+
+        self.estimate();
+        true
+    }
+
+    /// Maps a drone time to host time, `None` until the first exchange.
+    ///
+    /// `drone_time` may be from before or after the last exchange, as long as
+    /// it is within half a wrap period of it.
+    pub fn to_host(&self, drone_time: Timestamp) -> Option<u64> {
+        let reference: Sample = self.reference?;
+        let drone: i64 = self.unwrap(drone_time);
+
+        let host: i64 = drone + reference.offset + (drone - reference.drone) * self.drift_ppb / PPB;
+        if host < 0 {
+            return None;
+        }
+
+        Some(host as u64)
+    }
+
+    /// Host time minus drone time at the last exchange, in microseconds.
+    pub fn offset(&self) -> Option<i64> {
+        let (_, drone) = self.last?;
+        let reference: Sample = self.reference?;
+
+        Some(reference.offset + (drone - reference.drone) * self.drift_ppb / PPB)
+    }
+
+    /// How much faster the host clock runs than the drone clock, in parts per
+    /// million.
+    pub fn drift_ppm(&self) -> i64 {
+        self.drift_ppb / 1000
+    }
+
+    /// Round trip time of the exchange the estimate is based on, in
+    /// microseconds.
+    pub fn rtt(&self) -> Option<u64> {
+        self.reference.map(|r| r.rtt as u64)
+    }
+
+    // the drone time closest to the last one pushed
+    fn unwrap(&self, drone_time: Timestamp) -> i64 {
+        match self.last {
+            Some((raw, unwrapped)) => unwrapped + drone_time.wrapping_sub(raw) as i32 as i64,
+            None => drone_time as i64,
+        }
+    }
+
+    fn estimate(&mut self) {
+        let fastest = |samples: &mut dyn Iterator<Item = &Sample>| -> Option<Sample> {
+            samples.min_by_key(|s| s.rtt).copied()
+        };
+
+        self.reference = fastest(&mut self.samples.iter());
+
+        let half: usize = self.samples.len() / 2;
+        if half < 2 {
+            return;
+        }
+
+        let older: Option<Sample> = fastest(&mut self.samples.iter().take(half));
+        let newer: Option<Sample> = fastest(&mut self.samples.iter().skip(half));
+        if let (Some(older), Some(newer)) = (older, newer) {
+            if newer.drone > older.drone {
+                self.drift_ppb = (newer.offset - older.offset) * PPB / (newer.drone - older.drone);
+            }
+        }
+    }
+}
+
+impl Default for ClockSync {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::utility::clock::*;
+
+    // a drone clock that started at host time `start` and runs `drift_ppb`
+    // slower than the host clock
+    struct Drone {
+        start: i64,
+        drift_ppb: i64,
+    }
+
+    impl Drone {
+        fn time(&self, host: u64) -> Timestamp {
+            let elapsed: i64 = host as i64 - self.start;
+            (elapsed - elapsed * self.drift_ppb / PPB) as u64 as Timestamp
+        }
+
+        // an exchange sent at `host` with the given delays on the way there
+        // and back
+        fn exchange(&self, clock: &mut ClockSync, host: u64, there: u64, back: u64) {
+            assert!(clock.push(host, self.time(host + there), host + there + back));
+        }
+    }
+
+    #[test]
+    fn test_offset() {
+        let drone: Drone = Drone {
+            start: 5_000_000,
+            drift_ppb: 0,
+        };
+        let mut clock: ClockSync = ClockSync::new();
+        assert_eq!(clock.to_host(0), None);
+
+        drone.exchange(&mut clock, 10_000_000, 2_000, 2_000);
+        assert_eq!(clock.offset(), Some(5_000_000));
+        assert_eq!(clock.rtt(), Some(4_000));
+        assert_eq!(clock.to_host(1_000), Some(5_001_000));
+    }
+
+    #[test]
+    fn test_fastest_exchange_is_used() {
+        let drone: Drone = Drone {
+            start: 0,
+            drift_ppb: 0,
+        };
+        let mut clock: ClockSync = ClockSync::new();
+
+        // slow and asymmetric, the estimate is 10 ms off
+        drone.exchange(&mut clock, 1_000_000, 1_000, 21_000);
+        assert_eq!(clock.offset(), Some(10_000));
+
+        drone.exchange(&mut clock, 2_000_000, 1_000, 1_000);
+        drone.exchange(&mut clock, 3_000_000, 30_000, 1_000);
+        assert_eq!(clock.offset(), Some(0));
+        assert_eq!(clock.rtt(), Some(2_000));
+    }
+
+    #[test]
+    fn test_drift() {
+        // 50 ppm, a typical crystal
+        let drone: Drone = Drone {
+            start: 0,
+            drift_ppb: 50_000,
+        };
+        let mut clock: ClockSync = ClockSync::new();
+
+        for i in 0..CLOCK_SAMPLES as u64 {
+            drone.exchange(&mut clock, i * 1_000_000, 1_000, 1_000);
+        }
+        assert_eq!(clock.drift_ppm(), 50);
+
+        // a minute later the mapping is still within a millisecond
+        let host: u64 = 75_000_000;
+        let error: i64 = clock.to_host(drone.time(host)).unwrap() as i64 - host as i64;
+        assert!(error.abs() < 1_000, "{}", error);
+    }
+
+    #[test]
+    fn test_wrap_around() {
+        let drone: Drone = Drone {
+            start: -(u32::MAX as i64) + 500_000,
+            drift_ppb: 0,
+        };
+        let mut clock: ClockSync = ClockSync::new();
+
+        // the drone clock wraps between the two exchanges
+        drone.exchange(&mut clock, 0, 1_000, 1_000);
+        drone.exchange(&mut clock, 1_000_000, 1_000, 1_000);
+        assert!(drone.time(1_000_000) < drone.time(0));
+
+        assert_eq!(clock.to_host(drone.time(1_500_000)), Some(1_500_000));
+        // from before the wrap
+        assert_eq!(clock.to_host(drone.time(200_000)), Some(200_000));
+    }
+
+    #[test]
+    fn test_bad_exchange() {
+        let mut clock: ClockSync = ClockSync::new();
+
+        assert!(!clock.push(2_000, 100, 1_000));
+        assert_eq!(clock.offset(), None);
+    }
+}
@@ -0,0 +1,43 @@
+//! compile time friendly hashing
+
+const FNV_OFFSET_BASIS: u32 = 0x811c_9dc5;
+const FNV_PRIME: u32 = 0x0100_0193;
+
+/// FNV-1a (32 bit) hash of `bytes`.
+pub const fn fnv1a_32(bytes: &[u8]) -> u32 {
+    fnv1a_32_continue(FNV_OFFSET_BASIS, bytes)
+}
+
+/// Continues an FNV-1a (32 bit) hash started with `fnv1a_32` over more bytes.
+pub const fn fnv1a_32_continue(hash: u32, bytes: &[u8]) -> u32 {
+    let mut hash: u32 = hash;
+    let mut i: usize = 0;
+
+    while i < bytes.len() {
+        hash ^= bytes[i] as u32;
+        hash = hash.wrapping_mul(FNV_PRIME);
+        i += 1;
+    }
+
+    hash
+}
+
+#[cfg(test)]
+mod test {
+    use crate::utility::hash::*;
+
+    #[test]
+    fn test_known_values() {
+        assert_eq!(fnv1a_32(b""), 0x811c_9dc5);
+        assert_eq!(fnv1a_32(b"a"), 0xe40c_292c);
+        assert_eq!(fnv1a_32(b"foobar"), 0xbf9c_f968);
+    }
+
+    #[test]
+    fn test_continue() {
+        assert_eq!(
+            fnv1a_32_continue(fnv1a_32(b"foo"), b"bar"),
+            fnv1a_32(b"foobar")
+        );
+    }
+}
@@ -0,0 +1,234 @@
+//! timing statistics of a periodic control loop
+
+/// Timing of a periodic loop over a reporting window, see `LoopStats::take`.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct LoopReport {
+    pub cpu: u8,       // busy time as a percentage of the elapsed time
+    pub avg_busy: u32, // average execution time of an iteration, in microseconds
+    pub max_busy: u32, // worst-case execution time of an iteration, in microseconds
+    pub jitter: u32,   // largest deviation of the period from the nominal one, in microseconds
+    pub overruns: u32, // iterations that took longer than the nominal period
+}
+
+/// Accumulates the timing of a loop that should run every `period`
+/// microseconds. For every iteration the caller measures how long it was busy
+/// and how much time passed since the start of the previous iteration.
+///
+/// The period measured at the first iteration includes whatever happened
+/// before the loop, it is not taken into account.
+pub struct LoopStats {
+    period: u32, // nominal period, in microseconds
+    started: bool,
+
+    // current window
+    iterations: u32,
+    busy: u64,    // total busy time, in microseconds
+    elapsed: u64, // total measured period, in microseconds
+    max_busy: u32,
+    jitter: u32,
+    overruns: u32,
+}
+
+impl LoopStats {
+    pub const fn new(period: u32) -> Self {
+        Self {
+            period,
+            started: false,
+            iterations: 0,
+            busy: 0,
+            elapsed: 0,
+            max_busy: 0,
+            jitter: 0,
+            overruns: 0,
+        }
+    }
+
+    /// Adds an iteration that was busy for `busy` microseconds and started
+    /// `period` microseconds after the previous one.
+    pub fn record(&mut self, busy: u32, period: u32) {
+        self.iterations += 1;
+        self.busy += busy as u64;
+        self.max_busy = self.max_busy.max(busy);
+
+        if busy > self.period {
+            self.overruns += 1;
+        }
+
+        if self.started {
+            self.elapsed += period as u64;
+            self.jitter = self.jitter.max(period.abs_diff(self.period));
+        } else {
+            // the loop only runs on time from the second iteration on
+            self.elapsed += self.period as u64;
+            self.started = true;
+        }
+    }
+
+    /// Returns the statistics of the iterations recorded since the last call
+    /// and starts a new window.
+    pub fn take(&mut self) -> LoopReport {
+        if self.iterations == 0 {
+            return LoopReport::default();
+        }
+
+        let report: LoopReport = LoopReport {
+            cpu: (self.busy * 100 / self.elapsed.max(1)).min(100) as u8,
+            avg_busy: (self.busy / self.iterations as u64) as u32,
+            max_busy: self.max_busy,
+            jitter: self.jitter,
+            overruns: self.overruns,
+        };
+
+        *self = Self {
+            started: self.started,
+            ..Self::new(self.period)
+        };
+
+        report
+    }
+}
+
+/// Decides when the iterations that missed their deadline are reported: the
+/// first one at once, then how many followed at most once every `interval`
+/// iterations, so a loop that keeps missing does not flood the link.
+pub struct OverrunReporter {
+    deadline: u32,            // in microseconds
+    missed: u32,              // iterations not reported yet
+    worst: u32,               // longest of them, in microseconds
+    reported_at: Option<u32>, // iteration of the last report
+}
+
+impl OverrunReporter {
+    pub const fn new(deadline: u32) -> Self {
+        Self {
+            deadline,
+            missed: 0,
+            worst: 0,
+            reported_at: None,
+        }
+    }
+
+    /// Adds the iteration `iter_count` that was busy for `busy` microseconds.
+    /// @return the iterations that missed their deadline since the last
+    /// report and the longest of them, when they are due to be reported
+    pub fn record(&mut self, iter_count: u32, busy: u32, interval: u32) -> Option<(u32, u32)> {
+        if busy > self.deadline {
+            self.missed += 1;
+            self.worst = self.worst.max(busy);
+        }
+
+        let due: bool = match self.reported_at {
+            Some(at) => iter_count.wrapping_sub(at) >= interval,
+            None => true,
+        };
+        if self.missed == 0 || !due {
+            return None;
+        }
+
+        let report: (u32, u32) = (self.missed, self.worst);
+        self.missed = 0;
+        self.worst = 0;
+        self.reported_at = Some(iter_count);
+        Some(report)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::utility::loop_stats::*;
+
+    #[test]
+    fn test_steady_loop() {
+        let mut stats: LoopStats = LoopStats::new(10_000);
+
+        for _ in 0..100 {
+            stats.record(2_500, 10_000);
+        }
+
+        assert_eq!(
+            stats.take(),
+            LoopReport {
+                cpu: 25,
+                avg_busy: 2_500,
+                max_busy: 2_500,
+                jitter: 0,
+                overruns: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn test_first_period_is_ignored() {
+        let mut stats: LoopStats = LoopStats::new(10_000);
+
+        // the loop started long after boot
+        stats.record(1_000, 3_000_000);
+        stats.record(1_000, 10_000);
+
+        let report: LoopReport = stats.take();
+        assert_eq!(report.jitter, 0);
+        assert_eq!(report.cpu, 10);
+    }
+
+    #[test]
+    fn test_overrun_and_jitter() {
+        let mut stats: LoopStats = LoopStats::new(10_000);
+
+        stats.record(4_000, 10_000);
+        // this iteration misses the deadline, the next one starts late
+        stats.record(14_000, 9_800);
+        stats.record(4_000, 20_000);
+        stats.record(4_000, 10_000);
+
+        assert_eq!(
+            stats.take(),
+            LoopReport {
+                cpu: 52, // 26 ms busy out of 49.8 ms
+                avg_busy: 6_500,
+                max_busy: 14_000,
+                jitter: 10_000,
+                overruns: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn test_take_starts_new_window() {
+        let mut stats: LoopStats = LoopStats::new(10_000);
+        assert_eq!(stats.take(), LoopReport::default());
+
+        stats.record(12_000, 10_000);
+        stats.record(12_000, 13_000);
+        assert_eq!(stats.take().overruns, 2);
+
+        stats.record(1_000, 10_500);
+        let report: LoopReport = stats.take();
+        assert_eq!(report.overruns, 0);
+        assert_eq!(report.max_busy, 1_000);
+        // the loop already runs, the period counts
+        assert_eq!(report.jitter, 500);
+    }
+
+    #[test]
+    fn test_overruns_are_rate_limited() {
+        let mut reporter: OverrunReporter = OverrunReporter::new(10_000);
+
+        assert_eq!(reporter.record(1, 4_000, 100), None);
+
+        // the first miss at once, the next ones once per interval
+        assert_eq!(reporter.record(2, 12_000, 100), Some((1, 12_000)));
+        for iter_count in 3..102 {
+            assert_eq!(reporter.record(iter_count, 11_000, 100), None);
+        }
+        assert_eq!(reporter.record(102, 15_000, 100), Some((100, 15_000)));
+
+        // the ones left are reported even if the loop is on time again
+        assert_eq!(reporter.record(103, 11_000, 100), None);
+        assert_eq!(reporter.record(150, 4_000, 100), None);
+        assert_eq!(reporter.record(202, 4_000, 100), Some((1, 11_000)));
+        assert_eq!(reporter.record(203, 4_000, 100), None);
+
+        // after a quiet interval a miss is reported at once again
+        assert_eq!(reporter.record(400, 13_000, 100), Some((1, 13_000)));
+    }
+}
@@ -1,2 +1,6 @@
+pub mod clock;
+pub mod hash;
 pub mod internal_error_enums;
+pub mod loop_stats;
+pub mod siphash;
 pub mod static_assert;
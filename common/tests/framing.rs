@@ -0,0 +1,216 @@
+//! Property-based tests of the framing and of the stream resynchronisation of
+//! `ComT::read_data`: arbitrary byte streams, corrupted and truncated frames
+//! and garbage between frames.
+//!
+//! Run with `cargo test -p common --target x86_64-unknown-linux-gnu`. The
+//! fuzz target in `common/fuzz` covers the same ground without a time limit.
+
+use common::io::{ComErr, ComT, Loopback, Transport};
+use common::protocol::{DataT, FragmentDT, MotorsDT, WarningDT, DEFAULT_CAP, FRAGMENT_SIZE};
+use common::uart_com::{self, Cobs, DefaultChecksum, Encoding, Escape};
+use proptest::prelude::*;
+
+const BUF_CAP: usize = 64;
+const STREAM_CAP: usize = 4096;
+const FRAME_CAP: usize = 64;
+
+type TestComT<E> = ComT<BUF_CAP, Loopback<STREAM_CAP>, E>;
+
+// every read consumes bytes or ends with `Empty`, so a stream of `n` bytes
+// never needs more than `n` reads
+fn read_all<E: Encoding>(com: &mut TestComT<E>, stream_len: usize) -> Vec<DataT> {
+    let mut received: Vec<DataT> = Vec::new();
+
+    for _ in 0..=stream_len + 1 {
+        match com.read_data::<FRAME_CAP>() {
+            Ok(data) => received.push(data),
+            Err(ComErr::Empty) => return received,
+            Err(_) => {}
+        }
+    }
+
+    panic!("read_data did not drain a stream of {} bytes", stream_len);
+}
+
+fn feed<E: Encoding>(stream: &[u8]) -> Vec<DataT> {
+    let mut com: TestComT<E> = ComT::with_transport(Loopback::new());
+    assert!(com.transport_mut().write(stream));
+
+    read_all(&mut com, stream.len())
+}
+
+fn encoded<E: Encoding>(data: &DataT) -> Vec<u8> {
+    data.to_packet_with::<E, FRAME_CAP>(uart_com::NO_SEQ)
+        .unwrap()
+        .to_vec()
+}
+
+// checks that `expected` appears in `received` in the same order, garbage may
+// still add messages that happen to pass the checksum
+fn is_subsequence(expected: &[DataT], received: &[DataT]) -> bool {
+    let mut received = received.iter();
+    expected.iter().all(|e| received.any(|r| r == e))
+}
+
+fn message() -> impl Strategy<Value = DataT> {
+    prop_oneof![
+        Just(DataT::KeepAlive),
+        Just(DataT::Warning(WarningDT::ControlNotNeutral)),
+        // the special bytes of the encodings are likely in here
+        proptest::collection::vec(
+            prop_oneof![Just('<'), Just('>'), Just('\\'), any::<char>()],
+            0..8
+        )
+        .prop_filter_map("too long", |chars| {
+            let text: String = chars.into_iter().collect();
+            let mut ret: heapless::String<DEFAULT_CAP> = heapless::String::new();
+            ret.push_str(&text).ok().map(|_| DataT::Message(ret))
+        }),
+        (any::<u32>(), any::<[u16; 4]>()).prop_map(|(time, ae)| DataT::MotorsState(MotorsDT {
+            time,
+            ae1: ae[0],
+            ae2: ae[1],
+            ae3: ae[2],
+            ae4: ae[3],
+        })),
+        (
+            any::<[u8; 3]>(),
+            proptest::collection::vec(any::<u8>(), 0..=FRAGMENT_SIZE)
+        )
+            .prop_map(|(ids, data)| DataT::Fragment(FragmentDT {
+                id: ids[0],
+                index: ids[1],
+                count: ids[2],
+                data: heapless::Vec::from_slice(&data).unwrap(),
+            })),
+    ]
+}
+
+fn garbage() -> impl Strategy<Value = Vec<u8>> {
+    proptest::collection::vec(
+        prop_oneof![
+            Just(uart_com::START_BYTE),
+            Just(uart_com::END_BYTE),
+            Just(0u8),
+            any::<u8>(),
+        ],
+        0..100,
+    )
+}
+
+// garbage, then the frames with more garbage in between: all the frames must
+// come through. COBS can not tell where a frame starts, so the garbage is
+// closed with `E::END`, which is also harmless for `Escape`.
+fn check_resync<E: Encoding>(messages: &[DataT], garbage: &[Vec<u8>]) {
+    let mut stream: Vec<u8> = Vec::new();
+
+    for (data, garbage) in messages.iter().zip(garbage.iter()) {
+        stream.extend_from_slice(garbage);
+        stream.push(E::END);
+        stream.extend_from_slice(&encoded::<E>(data));
+    }
+
+    let received: Vec<DataT> = feed::<E>(&stream);
+    assert!(
+        is_subsequence(messages, &received),
+        "sent {:?}\nreceived {:?}",
+        messages,
+        received
+    );
+}
+
+// the frame of `data` decoded, before its encoding is applied
+fn packet<E: Encoding>(data: &DataT) -> heapless::Vec<u8, FRAME_CAP> {
+    let frame: heapless::Vec<u8, FRAME_CAP> = data
+        .to_packet_with::<E, FRAME_CAP>(uart_com::NO_SEQ)
+        .unwrap();
+    E::decode(frame).unwrap()
+}
+
+// position in the packet of the length of the payload, right before it
+fn length_pos(data: &DataT) -> usize {
+    let payload_len: usize = postcard::to_vec::<DataT, FRAME_CAP>(data).unwrap().len();
+    packet::<Escape>(data).len() - payload_len - 1
+}
+
+// one byte of the packet (the header, the length of the payload or the
+// payload) is changed, the frame must be rejected and the next one received
+fn check_corruption<E: Encoding>(data: &DataT, pos: usize, flip: u8) -> Result<(), TestCaseError> {
+    let mut packet: heapless::Vec<u8, FRAME_CAP> = packet::<E>(data);
+
+    let pos: usize = pos % packet.len();
+    packet[pos] ^= flip;
+
+    let mut stream: Vec<u8> = E::encode(packet).unwrap().to_vec();
+    stream.extend_from_slice(&encoded::<E>(&DataT::KeepAlive));
+
+    // a single changed byte is a burst of at most 8 bits, every CRC catches
+    // it. The length is not covered by the checksum, but a frame with bytes
+    // left after the payload or missing some is rejected before
+    prop_assert_eq!(feed::<E>(&stream), vec![DataT::KeepAlive]);
+    Ok(())
+}
+
+proptest! {
+    #[test]
+    fn arbitrary_bytes_never_panic(stream in proptest::collection::vec(any::<u8>(), 0..600)) {
+        feed::<Escape>(&stream);
+        feed::<Cobs>(&stream);
+    }
+
+    #[test]
+    fn tiny_frames_never_panic(frame in proptest::collection::vec(any::<u8>(), 0..4)) {
+        // a frame too short for its start and end bytes must not underflow
+        let _ = uart_com::unframe_in_place::<DefaultChecksum, Escape>(&mut frame.clone());
+        let _ = uart_com::unframe_in_place::<DefaultChecksum, Cobs>(&mut frame.clone());
+
+        feed::<Escape>(&frame);
+        feed::<Cobs>(&frame);
+    }
+
+    #[test]
+    fn garbage_between_frames_resyncs(
+        messages in proptest::collection::vec(message(), 1..6),
+        garbage in proptest::collection::vec(garbage(), 6),
+    ) {
+        check_resync::<Escape>(&messages, &garbage);
+        check_resync::<Cobs>(&messages, &garbage);
+    }
+
+    #[test]
+    fn corrupted_frames_are_rejected(data in message(), pos in any::<usize>(), flip in 1..=u8::MAX) {
+        check_corruption::<Escape>(&data, pos, flip)?;
+        check_corruption::<Cobs>(&data, pos, flip)?;
+    }
+
+    #[test]
+    fn corrupted_lengths_are_rejected(data in message(), flip in 1..=u8::MAX) {
+        // a shorter payload must not be taken for another message
+        check_corruption::<Escape>(&data, length_pos(&data), flip)?;
+        check_corruption::<Cobs>(&data, length_pos(&data), flip)?;
+    }
+
+    #[test]
+    fn truncated_and_corrupted_streams_recover(
+        messages in proptest::collection::vec(message(), 1..6),
+        cut in any::<prop::sample::Index>(),
+        noise in proptest::collection::vec((any::<prop::sample::Index>(), any::<u8>()), 0..8),
+    ) {
+        let mut stream: Vec<u8> = messages.iter().flat_map(encoded::<Escape>).collect();
+
+        stream.truncate(cut.index(stream.len() + 1));
+        for (pos, byte) in noise.iter() {
+            if !stream.is_empty() {
+                let pos: usize = pos.index(stream.len());
+                stream[pos] = *byte;
+            }
+        }
+
+        // whatever is left, a frame sent afterwards must come through
+        let last: DataT = DataT::Warning(WarningDT::ControlNotNeutral);
+        stream.extend_from_slice(&encoded::<Escape>(&last));
+
+        let received: Vec<DataT> = feed::<Escape>(&stream);
+        prop_assert_eq!(received.last(), Some(&last));
+    }
+}
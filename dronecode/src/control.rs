@@ -3,7 +3,7 @@ use core::time::Duration;
 use tudelft_quadrupel::flash::flash_chip_erase;
 
 // Our libraries
-use common::protocol::DataT;
+use common::protocol::Timestamp;
 
 // TUDelft library
 use tudelft_quadrupel::motor::set_motor_max;
@@ -12,8 +12,16 @@ use tudelft_quadrupel::time::{set_tick_frequency, wait_for_next_tick, Instant};
 // This crate imports
 use crate::drone::state::DroneState;
 
+/// Frequency of the control loop, in Hz.
+pub(crate) const TICK_FREQUENCY: u32 = 100;
+
+/// Current drone time, see `Timestamp`.
+pub(crate) fn timestamp() -> Timestamp {
+    (Instant::now().ns_since_start() / 1000) as Timestamp
+}
+
 pub fn control_loop() -> ! {
-    set_tick_frequency(100);
+    set_tick_frequency(TICK_FREQUENCY as u64);
     set_motor_max(800);
     let mut last = Instant::now();
     let mut drone: DroneState = DroneState::new();
@@ -21,11 +29,13 @@ pub fn control_loop() -> ! {
 
     for i in 1.. {
         let delta_t: Duration = update_last_n_get_delta(&mut last);
-        if delta_t.as_millis() > 10 {
-            let err = DataT::Message(heapless::String::<32>::from("Exceeding deadline!"));
-            drone.send_data(err);
-        }
         drone.tick(i, delta_t);
+
+        // missed deadlines are counted in the health message and reported
+        // as events
+        let busy: Duration = Instant::now().duration_since(last);
+        drone.record_loop(i, busy, delta_t);
+
         wait_for_next_tick();
     }
     unreachable!();
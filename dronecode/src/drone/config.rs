@@ -1,3 +1,7 @@
+use common::io::{AuthKey, ReliableConfig, PRESHARED_KEY};
+use common::motor_control::{FrameT, DEFAULT_FRAME, DEFAULT_PROPS_REVERSED};
+use common::protocol::STREAM_COUNT;
+
 /// Configuration structure with default values for some variables. It can be
 /// used in the future to dnamically change parameters on the drone like PID
 /// values, telemetry periods etc.
@@ -5,38 +9,67 @@ pub struct DroneConfig {
     pub dead_margin: u16,
     pub panic_motor_reduction: u16,
 
-    // once how many ticks does the drone send aa keep alive
-    pub ka_tick_period: u32,
     // after how many ticks considers the drone the serial dropped
     pub max_ticks_no_ka: u32,
 
-    pub battery_printing_time: u32,
+    // retransmission of the critical messages (mode changes, gain updates)
+    pub reliable: ReliableConfig,
+
+    // frames from the runner are dropped unless authenticated with this key
+    pub auth_key: Option<AuthKey>,
+
     pub check_battery: bool, // TO enable and disable battery checks
 
-    pub log_report_send_period: u32,
+    // layout of the motors, see `DroneState::mixer`
+    pub frame: FrameT,
+    pub props_reversed: bool,
+
+    // bytes per second the telemetry streams may use together, their rates
+    // are chosen by the runner (see `common::telemetry::rates`)
+    pub telemetry_budget: u32,
+
+    // period of each stream in ticks until the runner asks for another, in
+    // the order of `StreamT::ALL` (0 when off)
+    pub stream_periods: [u32; STREAM_COUNT],
 
-    // debug print periods
-    pub debug_info_period: u32,
-    pub debug_motor_command_period: u32,
+    // outgoing bandwidth, the budget of a tick is derived from the baud rate
+    // and up to `tx_burst` bytes (the UART TX buffer) can be saved up
+    pub baud_rate: u32,
+    pub tx_burst: u32,
 }
 
 impl DroneConfig {
-    pub fn default() -> Self {
+    /// Also the defaults of the parameters backed by the configuration (see
+    /// `params::PARAMS`), hence `const`.
+    pub const fn default() -> Self {
         Self {
             dead_margin: 50,
             panic_motor_reduction: 2,
 
-            ka_tick_period: 40,
             max_ticks_no_ka: 120,
 
-            battery_printing_time: 100,
+            reliable: ReliableConfig {
+                retransmit_ticks: 20,
+                max_retries: 5,
+                dedup_ticks: 200,
+            },
+
+            auth_key: PRESHARED_KEY,
+
             check_battery: true,
 
-            log_report_send_period: 2,
+            frame: DEFAULT_FRAME,
+            props_reversed: DEFAULT_PROPS_REVERSED,
+
+            // about half of the UART bandwidth
+            telemetry_budget: 6000,
+
+            // heartbeat, health, link stats, motors, debug, log report and
+            // live sensors
+            stream_periods: [40, 100, 200, 20, 50, 2, 0],
 
-            // debug print periods
-            debug_info_period: 50,
-            debug_motor_command_period: 20,
+            baud_rate: 115200,
+            tx_burst: 256,
         }
     }
 }
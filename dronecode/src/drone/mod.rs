@@ -1,3 +1,5 @@
 pub(crate) mod config;
 pub(crate) mod controller;
+pub(crate) mod params;
 pub(crate) mod state; // decide if to keep or just use the config structure
+pub(crate) mod uart;
@@ -0,0 +1,162 @@
+use common::motor_control::FrameT;
+use common::param::ParamDef;
+use common::protocol::{ParamValue, StreamT};
+use common::telemetry::rates::heartbeat_period_max;
+
+use super::config::DroneConfig;
+use super::state::DroneState;
+use crate::control::TICK_FREQUENCY;
+
+type FP = fixed::types::I16F16;
+
+// bounds of the controller gains
+const GAIN_MIN: ParamValue = ParamValue::Fixed(FP::ZERO);
+const GAIN_MAX: ParamValue = ParamValue::Fixed(FP::from_bits(200 << 16));
+const GAIN_DEFAULT: ParamValue = ParamValue::Fixed(FP::from_bits(5 << 16));
+
+// bounds of the periods of the telemetry streams in ticks, the telemetry
+// budget may make them longer (see `DroneState::set_stream_period`)
+const PERIOD_MIN: ParamValue = ParamValue::U32(1);
+const PERIOD_MAX: ParamValue = ParamValue::U32(u32::MAX);
+
+// the runner takes the link for lost without heartbeats for long enough
+const HB_PERIOD_MAX: ParamValue = ParamValue::U32(heartbeat_period_max(TICK_FREQUENCY));
+
+// the parameters backed by the configuration start from its values
+const CONFIG: DroneConfig = DroneConfig::default();
+
+// the runner sends a keep alive every 0.5 s, the drone must not panic in
+// between nor fly on for long without a runner (ticks of the control loop)
+const KA_TIMEOUT_MIN: ParamValue = ParamValue::U32(100);
+const KA_TIMEOUT_MAX: ParamValue = ParamValue::U32(500);
+
+// the motors have to come down in panic mode, but not drop
+const PANIC_REDUCE_MIN: ParamValue = ParamValue::U32(1);
+const PANIC_REDUCE_MAX: ParamValue = ParamValue::U32(20);
+
+/// Number of parameters of the drone.
+pub(crate) const PARAM_COUNT: usize = 10;
+
+/// The parameters of the drone the runner can read and change at run time.
+/// Their ids are part of the protocol, never reuse or renumber them, append
+/// new ones instead (the missing ones belonged to removed parameters). The defaults are applied by `DroneState::new`.
+///
+/// The names must fit in `PARAM_NAME_CAP` characters, longer ones are cut.
+pub(crate) static PARAMS: [ParamDef<DroneState>; PARAM_COUNT] = [
+    // controller gains
+    ParamDef {
+        id: 0,
+        name: "yaw_p",
+        default: GAIN_DEFAULT,
+        min: GAIN_MIN,
+        max: GAIN_MAX,
+        safe_only: false,
+        get: |state| ParamValue::Fixed(state.P),
+        set: |state, value| state.P = value.as_fixed().unwrap_or_default(),
+    },
+    ParamDef {
+        id: 1,
+        name: "rp_p1",
+        default: GAIN_DEFAULT,
+        min: GAIN_MIN,
+        max: GAIN_MAX,
+        safe_only: false,
+        get: |state| ParamValue::Fixed(state.P1),
+        set: |state, value| state.P1 = value.as_fixed().unwrap_or_default(),
+    },
+    ParamDef {
+        id: 2,
+        name: "rp_p2",
+        default: GAIN_DEFAULT,
+        min: GAIN_MIN,
+        max: GAIN_MAX,
+        safe_only: false,
+        get: |state| ParamValue::Fixed(state.P2),
+        set: |state, value| state.P2 = value.as_fixed().unwrap_or_default(),
+    },
+    // heartbeat and keep alive, the rest of the streams have their rates set
+    // with `StreamSet`
+    ParamDef {
+        id: 3,
+        name: "hb_period",
+        default: ParamValue::U32(CONFIG.stream_periods[StreamT::Heartbeat as usize]),
+        min: PERIOD_MIN,
+        max: HB_PERIOD_MAX,
+        safe_only: false,
+        get: |state| ParamValue::U32(state.stream_period(StreamT::Heartbeat)),
+        set: |state, value| {
+            state.set_stream_period(StreamT::Heartbeat, value.as_u32().unwrap_or_default())
+        },
+    },
+    ParamDef {
+        id: 4,
+        name: "ka_timeout",
+        default: ParamValue::U32(CONFIG.max_ticks_no_ka),
+        min: KA_TIMEOUT_MIN,
+        max: KA_TIMEOUT_MAX,
+        safe_only: false,
+        get: |state| ParamValue::U32(state.config.max_ticks_no_ka),
+        set: |state, value| state.config.max_ticks_no_ka = value.as_u32().unwrap_or_default(),
+    },
+    // battery
+    ParamDef {
+        id: 5,
+        name: "check_batt",
+        default: ParamValue::Bool(CONFIG.check_battery),
+        min: ParamValue::Bool(false),
+        max: ParamValue::Bool(true),
+        safe_only: false,
+        get: |state| ParamValue::Bool(state.config.check_battery),
+        set: |state, value| state.config.check_battery = value.as_bool().unwrap_or_default(),
+    },
+    ParamDef {
+        id: 6,
+        name: "health_per",
+        default: ParamValue::U32(CONFIG.stream_periods[StreamT::Health as usize]),
+        min: PERIOD_MIN,
+        max: PERIOD_MAX,
+        safe_only: false,
+        get: |state| ParamValue::U32(state.stream_period(StreamT::Health)),
+        set: |state, value| {
+            state.set_stream_period(StreamT::Health, value.as_u32().unwrap_or_default())
+        },
+    },
+    // panic mode
+    ParamDef {
+        id: 11,
+        name: "panic_reduce",
+        default: ParamValue::U32(CONFIG.panic_motor_reduction as u32),
+        min: PANIC_REDUCE_MIN,
+        max: PANIC_REDUCE_MAX,
+        safe_only: false,
+        get: |state| ParamValue::U32(state.config.panic_motor_reduction as u32),
+        set: |state, value| {
+            state.config.panic_motor_reduction = value.as_u32().unwrap_or_default() as u16
+        },
+    },
+    // motor layout
+    ParamDef {
+        id: 13,
+        name: "frame",
+        default: ParamValue::U32(CONFIG.frame as u32),
+        min: ParamValue::U32(0),
+        max: ParamValue::U32(FrameT::ALL.len() as u32 - 1),
+        safe_only: true,
+        get: |state| ParamValue::U32(state.config.frame as u32),
+        set: |state, value| {
+            state.set_frame(
+                FrameT::from_index(value.as_u32().unwrap_or_default()).unwrap_or(CONFIG.frame),
+            )
+        },
+    },
+    ParamDef {
+        id: 14,
+        name: "props_rev",
+        default: ParamValue::Bool(CONFIG.props_reversed),
+        min: ParamValue::Bool(false),
+        max: ParamValue::Bool(true),
+        safe_only: true,
+        get: |state| ParamValue::Bool(state.config.props_reversed),
+        set: |state, value| state.set_props_reversed(value.as_bool().unwrap_or_default()),
+    },
+];
@@ -5,23 +5,37 @@ use core::time::Duration;
 use crate::calibrationdata::CalibrationData;
 use crate::sensors_dmp::SensorsDMP;
 use crate::sensors_raw::SensorsRaw;
-use common::io::{ComErr, ComT};
-use common::protocol::{ControlDT, DataT, SensorLogDT};
+use common::drone_link::{self, DroneHost, DroneLink, DRONE_PIPE_SIZE};
+use common::io::{bytes_per_tick, ComT, OutQueue, Priority, AUTH_KEY_SIZE, NO_SESSION};
+use common::motor_control::{FrameT, MixerConfig};
+use common::param::ParamDef;
+use common::protocol::{
+    BatteryT, ControlDT, DataT, EventCodeT, EventDT, HeartbeatDT, LinkStatsDT, ResultT, StreamT,
+    Timestamp, WarningDT, EVENT_ARGS, FAULT_BATTERY, FAULT_HANDSHAKE, FAULT_LINK, FAULT_LOG_FULL,
+};
+use common::telemetry::rates::StreamRates;
+use common::telemetry::{SensorEncoder, SensorSample};
+use common::utility::loop_stats::{LoopReport, LoopStats, OverrunReporter};
+use common::utility::siphash::SipHasher24;
 use common::DroneMode;
 
 // TUDelft library
+use tudelft_quadrupel::battery::read_battery;
 use tudelft_quadrupel::flash::FlashError;
 
 // This crate imports
+use crate::control::{timestamp, TICK_FREQUENCY};
 use crate::state_machine::*;
 
 // This module imports
 use super::config::DroneConfig;
-
-const PIPE_SIZE: usize = 128;
-const COM_BUF_SIZE: usize = 64;
+use super::params::PARAMS;
+use super::uart::UartTransport;
 
 const LOG_DATA_FIELD_NO: usize = 9;
+// an entry of the log is the drone time followed by the data fields
+const LOG_TIME_SIZE: usize = 4;
+const LOG_ENTRY_SIZE: usize = LOG_TIME_SIZE + LOG_DATA_FIELD_NO * 2;
 
 const ADDRESS_OF_LOG_REPORT_EOF: u32 = 0x00;
 
@@ -36,8 +50,8 @@ type FP = fixed::types::I16F16;
 ///
 /// Note: Only one instance of this object should be created.
 pub struct DroneState {
-    // communication double way pipe
-    pipe: ComT<PIPE_SIZE>,
+    // the pipe with the runner and the state of the protocol
+    link: DroneLink<UartTransport>,
 
     // the mode the drone is at the moment
     mode: DroneMode,
@@ -48,9 +62,16 @@ pub struct DroneState {
 
     // configurations
     pub config: DroneConfig,
+    mixer: MixerConfig, // of the frame and prop directions configured
 
     // misc
+    loop_stats: LoopStats, // timing of the control loop since the last health report
+    overruns: OverrunReporter, // missed deadlines, reported as events
     ticks_since_last_ka: u32,
+    session: u64, // nonce issued with the last handshake
+
+    // charge of the battery in the last heartbeat, a drop is reported as an event
+    battery: BatteryT,
 
     // To be used by Yaw control and stable mode
     pub calibrated_data: CalibrationData,
@@ -66,6 +87,11 @@ pub struct DroneState {
     log_report_eof: u32, // address at which the log report ends
     log_on: bool,
     log_report_on: bool,
+    log_full: bool, // the flash ran out of space, until the logging is started again
+
+    // packing of the sensor samples, read back from the log and live
+    log_encoder: SensorEncoder,
+    live_encoder: SensorEncoder,
 
     // debug stuff
     pub debug_info: DataT,
@@ -73,13 +99,27 @@ pub struct DroneState {
 
 impl DroneState {
     pub fn new() -> Self {
-        let pipe = ComT::<PIPE_SIZE>::new(
-            tudelft_quadrupel::uart::receive_bytes,
-            tudelft_quadrupel::uart::send_bytes,
+        let config: DroneConfig = DroneConfig::default();
+        let mixer: MixerConfig = MixerConfig::preset(config.frame, config.props_reversed);
+
+        let mut pipe = ComT::<DRONE_PIPE_SIZE, UartTransport>::with_transport(UartTransport);
+        pipe.enable_reliable(config.reliable);
+        if let Some(key) = config.auth_key {
+            pipe.require_auth(key);
+        }
+
+        let out_queue = OutQueue::new(
+            bytes_per_tick(config.baud_rate, TICK_FREQUENCY),
+            config.tx_burst,
         );
 
-        Self {
-            pipe: pipe,
+        let mut streams: StreamRates = StreamRates::new(TICK_FREQUENCY, config.telemetry_budget);
+        for stream in StreamT::ALL {
+            streams.set_period(stream, config.stream_periods[stream.index()]);
+        }
+
+        let mut state = Self {
+            link: DroneLink::new(pipe, out_queue, streams),
 
             mode: DroneMode::Safe,
 
@@ -92,9 +132,15 @@ impl DroneState {
             },
             motor_command: [0; 4],
 
-            config: DroneConfig::default(),
+            config,
+            mixer,
 
+            loop_stats: LoopStats::new(1_000_000 / TICK_FREQUENCY),
+            overruns: OverrunReporter::new(1_000_000 / TICK_FREQUENCY),
             ticks_since_last_ka: 0,
+            session: NO_SESSION,
+
+            battery: BatteryT::Unchecked,
 
             calibrated_data: CalibrationData::new(),
             sensors_dmp: SensorsDMP::new(),
@@ -107,26 +153,41 @@ impl DroneState {
             log_report_eof: ADDRESS_OF_LOG_REPORT_EOF + 0x04,
             log_on: false,
             log_report_on: false,
+            log_full: false,
+
+            log_encoder: SensorEncoder::new(false),
+            live_encoder: SensorEncoder::new(true),
 
             debug_info: DataT::KeepAlive,
+        };
+
+        for param in PARAMS.iter() {
+            param.reset(&mut state);
         }
+
+        state
     }
 
     /// Perform "tick" and update the state of the drone according to the mode.
     pub fn tick(&mut self, iter_count: u32, delta_t: Duration) {
         // check keep alive
         self.tick_time_since_keep_alive();
-        if (self.check_alive() == false)
-            && (self.mode != DroneMode::Safe)
-            && (self.mode != DroneMode::Panic)
+        if !self.check_alive() && (self.mode != DroneMode::Safe) && (self.mode != DroneMode::Panic)
         {
             self.mode = DroneMode::Panic
         }
 
+        // retransmit the unacknowledged critical messages
+        self.link.tick();
+
         self.log_if_enabled();
         self.log_report_if_enabled(iter_count);
+        drone_link::param_list_if_enabled(self);
+        self.sensor_stream_if_enabled(iter_count);
 
         self.dispatch_mode(iter_count, delta_t);
+
+        self.link.flush();
     }
 
     // TODO: do this with function pointer as parameter or something generic
@@ -173,12 +234,14 @@ impl DroneState {
             self.mode = new_mode;
 
             // inform the PC about the mode change
-            self.send_data(common::protocol::DataT::Mode(self.mode));
+            drone_link::mode_changed(self, new_mode);
 
             // perform the remaining operations (if any) in accordance with the
             // new mode
             self.dispatch_mode(iter_count, delta_t);
         }
+
+        drone_link::answer_request(self);
     }
 
     /// set received control command
@@ -201,51 +264,66 @@ impl DroneState {
         ]
     }
 
-    /// Tries to send `data` and returns if it succedded or not
+    /// Queues `data`, it is sent at the end of the tick if the UART bandwidth
+    /// allows it. Returns `false` if it was dropped.
+    ///
+    /// Messages of the safety class (see `Priority`) always go out before
+    /// telemetry and log data.
     pub fn send_data(&mut self, data: DataT) -> bool {
-        // TODO: do more error handling
-        if let Err(err) = self.pipe.send_data::<COM_BUF_SIZE>(data) {
-            #[allow(unused_must_use)]
-            {
-                // in case of any error, send message data packet with error
-                // if this fails, do not try again
-                self.pipe
-                    .send_data::<COM_BUF_SIZE>(DataT::Message(heapless::String::from(
-                        alloc::format!("E s {:#?}", err).as_str(),
-                    )));
-            }
+        self.link.send_data(data)
+    }
 
-            false
-        } else {
-            true
-        }
+    /// Reports an error or a notable event to the runner, see `EventCodeT`
+    /// for the meaning of `args`.
+    pub fn send_event(&mut self, code: EventCodeT, args: [u32; EVENT_ARGS]) {
+        self.send_data(DataT::Event(EventDT::new(code, args)));
     }
 
     /// Guarantees the return of some data, even in case of error. If error
-    /// returns `DataT::Empty`.
+    /// returns `DataT::Empty`, see `drone_link::read_data`.
     pub fn read_data(&mut self) -> DataT {
-        match self.pipe.read_data::<COM_BUF_SIZE>() {
-            Ok(data) => data,
-            // TODO: do more error handling
-            Err(err) => {
-                match err {
-                    ComErr::Empty => {}
-
-                    _ => {
-                        #[allow(unused_must_use)]
-                        {
-                            // send message packet with error if error not Empty
-                            // if this fails, do not try again
-                            self.pipe.send_data::<COM_BUF_SIZE>(DataT::Message(
-                                heapless::String::from(alloc::format!("E r {:#?}", err).as_str()),
-                            ));
-                        }
-                    }
-                };
+        drone_link::read_data(self)
+    }
 
-                DataT::Empty
-            }
-        }
+    /// Records the outcome of the request being handled, see
+    /// `DroneLink::set_request_result`.
+    fn set_request_result(&mut self, result: ResultT) {
+        self.link.set_request_result(result);
+    }
+
+    /// Checks if a message of `stream` has to be sent at this tick.
+    pub fn is_stream_due(&self, stream: StreamT, iter_count: u32) -> bool {
+        self.link.is_stream_due(stream, iter_count)
+    }
+
+    /// Period of `stream` in ticks, 0 when it is off.
+    pub fn stream_period(&self, stream: StreamT) -> u32 {
+        self.link.stream_period(stream)
+    }
+
+    /// Changes the period of `stream` in ticks, within the telemetry budget
+    /// (except the heartbeat, see `StreamRates`).
+    pub fn set_stream_period(&mut self, stream: StreamT, period: u32) {
+        self.link.set_stream_period(stream, period);
+    }
+
+    /// How the commands are mixed into the motors, for the frame and prop
+    /// directions configured.
+    pub fn mixer(&self) -> &MixerConfig {
+        &self.mixer
+    }
+
+    /// Changes the layout of the motors, only while they are off (see
+    /// `params::PARAMS`).
+    pub fn set_frame(&mut self, frame: FrameT) {
+        self.config.frame = frame;
+        self.mixer = MixerConfig::preset(frame, self.config.props_reversed);
+    }
+
+    /// Changes the spin direction of the props, only while they are off.
+    pub fn set_props_reversed(&mut self, reversed: bool) {
+        self.config.props_reversed = reversed;
+        self.mixer = MixerConfig::preset(self.config.frame, reversed);
     }
 
     #[inline]
@@ -284,34 +362,124 @@ impl DroneState {
         }
     }
 
-    /// Send keep alive packet to inform the runner the drone is still
-    /// connected.
-    pub fn send_alive(&mut self) {
-        self.send_data(DataT::KeepAlive);
+    /// Health of the link with the runner.
+    pub fn link_stats(&self) -> LinkStatsDT {
+        self.link.pipe().link_stats()
+    }
+
+    /// Records the timing of a tick of the control loop: how long it was busy
+    /// and how long after the previous one it started. Missed deadlines are
+    /// reported at the first one, then at most once per health period (once
+    /// a second while the health stream is off).
+    pub fn record_loop(&mut self, iter_count: u32, busy: Duration, period: Duration) {
+        let busy: u32 = busy.as_micros() as u32;
+        self.loop_stats.record(busy, period.as_micros() as u32);
+
+        let interval: u32 = match self.stream_period(StreamT::Health) {
+            0 => TICK_FREQUENCY,
+            period => period,
+        };
+        if let Some((missed, worst)) = self.overruns.record(iter_count, busy, interval) {
+            self.send_event(EventCodeT::DeadlineOverrun, [missed, worst]);
+        }
+    }
+
+    /// Timing of the control loop since the last call.
+    pub fn loop_report(&mut self) -> LoopReport {
+        self.loop_stats.take()
+    }
+
+    /// Checks if a runner built from the same protocol definition is
+    /// connected. Until then the drone must stay in safe mode.
+    pub fn is_handshake_ok(&self) -> bool {
+        self.link.is_handshake_ok()
+    }
+
+    /// Send the status of the drone, it also tells the runner the drone is
+    /// still connected.
+    pub fn send_heartbeat(&mut self) {
+        let heartbeat: HeartbeatDT = self.heartbeat();
+        self.send_data(DataT::Heartbeat(heartbeat));
+    }
+
+    fn heartbeat(&mut self) -> HeartbeatDT {
+        let (reading, battery): (u16, BatteryT) = if self.config.check_battery {
+            let reading: u16 = read_battery();
+            (reading, BatteryT::from_reading(reading))
+        } else {
+            (0, BatteryT::Unchecked)
+        };
+
+        // once per drop, not with every heartbeat
+        if battery != self.battery {
+            match battery {
+                BatteryT::Low => self.send_event(EventCodeT::BatteryLow, [reading as u32, 0]),
+                BatteryT::Critical => {
+                    self.send_event(EventCodeT::BatteryCritical, [reading as u32, 0])
+                }
+                BatteryT::Unchecked | BatteryT::Ok => {}
+            }
+            self.battery = battery;
+        }
+
+        let mut faults: u8 = 0;
+        if battery == BatteryT::Critical {
+            faults |= FAULT_BATTERY;
+        }
+        if !self.check_alive() {
+            faults |= FAULT_LINK;
+        }
+        if !self.link.is_handshake_ok() {
+            faults |= FAULT_HANDSHAKE;
+        }
+        if self.log_full {
+            faults |= FAULT_LOG_FULL;
+        }
+
+        HeartbeatDT {
+            mode: self.mode,
+            armed: matches!(
+                self.mode,
+                DroneMode::Manual
+                    | DroneMode::YawControl
+                    | DroneMode::FullControl
+                    | DroneMode::RawMode
+                    | DroneMode::Panic
+            ),
+            calibrated: self.calibrated_data.is_calibrated(),
+            logging: self.log_on,
+            log_report: self.log_report_on,
+            battery,
+            faults,
+        }
     }
 
     /// Enable the logging
     pub fn start_logging(&mut self) {
-        if self.log_on == true || self.log_report_on == true {
+        if self.log_on {
+            self.set_request_result(ResultT::Ok);
+            return;
+        }
+        if self.log_report_on {
+            self.send_data(DataT::Warning(WarningDT::LogUnavailable));
             return;
         }
 
         self.log_on = true;
+        self.log_full = false;
         self.flash_iterator = ADDRESS_OF_LOG_REPORT_EOF + 0x04;
 
-        self.send_data(DataT::Message(heapless::String::from(
-            alloc::format!("log start").as_str(),
-        )));
+        self.send_event(EventCodeT::LogStarted, [0, 0]);
+        self.set_request_result(ResultT::Ok);
     }
 
     /// Stop the logging and store the end of the logged data in the first 4 bytes
     pub fn stop_logging(&mut self) {
-        if self.log_on == false {
+        if !self.log_on {
+            self.set_request_result(ResultT::Ok);
             return;
         }
-        self.send_data(DataT::Message(heapless::String::from(
-            alloc::format!("log stop").as_str(),
-        )));
+        self.send_event(EventCodeT::LogStopped, [self.logged_bytes(), 0]);
 
         self.log_on = false;
         let bytes: [u8; 4] = [
@@ -327,54 +495,70 @@ impl DroneState {
             tudelft_quadrupel::flash::flash_write_bytes(ADDRESS_OF_LOG_REPORT_EOF, &bytes);
 
         match result {
-            Ok(_) => {}
+            Ok(_) => self.set_request_result(ResultT::Ok),
             Err(_) => {
-                self.send_data(DataT::Message(heapless::String::from(
-                    alloc::format!("e: log stop").as_str(),
-                )));
+                self.send_event(EventCodeT::LogStopFailed, [0, 0]);
+                self.send_data(DataT::Warning(WarningDT::LogUnavailable));
             }
         };
     }
 
+    /// Current sensor readings, as they are logged and streamed.
+    fn sensor_sample(&self) -> SensorSample {
+        // TODO: get data from the raw source that Panos is writing
+        let (accel, gyro) = self.sensors_raw.read(&self.calibrated_data);
+
+        SensorSample {
+            time: timestamp(),
+            gyro_x: gyro.x,
+            gyro_y: gyro.y,
+            gyro_z: gyro.z,
+            accel_x: accel.x,
+            accel_y: accel.y,
+            accel_z: accel.z,
+            pitch: SensorSample::angle_from_fixed(self.sensors_dmp.get_dmp_pitch_value(self)),
+            roll: SensorSample::angle_from_fixed(self.sensors_dmp.get_dmp_roll_value(self)),
+            yaw: SensorSample::angle_from_fixed(self.sensors_dmp.get_dmp_yaw_value(self)),
+        }
+    }
+
     /// The part that performs the actual logging
     fn log_if_enabled(&mut self) {
-        if self.log_on == false {
+        if !self.log_on {
             return;
         }
 
-        // TODO: get data from the raw source that Panos is writing
-        // prepare data
-        let sensor_data_raw = self.sensors_raw.read(&self.calibrated_data);
+        let sample: SensorSample = self.sensor_sample();
         let data: [i16; LOG_DATA_FIELD_NO] = [
-            sensor_data_raw.0.x,
-            sensor_data_raw.0.y,
-            sensor_data_raw.0.z,
-            sensor_data_raw.1.x,
-            sensor_data_raw.1.y,
-            sensor_data_raw.1.z,
-            (FP::from_num(10000) * self.sensors_dmp.get_dmp_pitch_value(self)).to_num::<i16>(),
-            (FP::from_num(10000) * self.sensors_dmp.get_dmp_roll_value(self)).to_num::<i16>(),
-            (FP::from_num(10000) * self.sensors_dmp.get_dmp_yaw_value(self)).to_num::<i16>(),
+            sample.gyro_x,
+            sample.gyro_y,
+            sample.gyro_z,
+            sample.accel_x,
+            sample.accel_y,
+            sample.accel_z,
+            sample.pitch,
+            sample.roll,
+            sample.yaw,
         ];
 
-        let mut bytes: [u8; LOG_DATA_FIELD_NO * 2] = [0u8; LOG_DATA_FIELD_NO * 2];
+        let mut bytes: [u8; LOG_ENTRY_SIZE] = [0u8; LOG_ENTRY_SIZE];
+        bytes[..LOG_TIME_SIZE].copy_from_slice(&sample.time.to_be_bytes());
         for i in 0..data.len() {
-            bytes[i * 2] = data[i].to_be_bytes()[0];
-            bytes[i * 2 + 1] = data[i].to_be_bytes()[1];
+            bytes[LOG_TIME_SIZE + i * 2] = data[i].to_be_bytes()[0];
+            bytes[LOG_TIME_SIZE + i * 2 + 1] = data[i].to_be_bytes()[1];
         }
 
         let result: Result<(), FlashError> =
             tudelft_quadrupel::flash::flash_write_bytes(self.flash_iterator, &bytes);
 
         match result {
-            Ok(_) => self.flash_iterator += (LOG_DATA_FIELD_NO as u32) * 2,
+            Ok(_) => self.flash_iterator += LOG_ENTRY_SIZE as u32,
             Err(e) => {
                 match e {
                     FlashError::SpiError(_) => {}
                     FlashError::OutOfSpace => {
-                        self.send_data(DataT::Message(heapless::String::from(
-                            alloc::format!("e: log").as_str(),
-                        )));
+                        self.log_full = true;
+                        self.send_event(EventCodeT::LogFull, [self.logged_bytes(), 0]);
                         self.stop_logging();
                     }
                 };
@@ -384,7 +568,12 @@ impl DroneState {
 
     /// Start log report to the PC
     pub fn log_report_start(&mut self) {
-        if self.log_on == true || self.log_report_on == true {
+        if self.log_report_on {
+            self.set_request_result(ResultT::Ok);
+            return;
+        }
+        if self.log_on {
+            self.send_data(DataT::Warning(WarningDT::LogUnavailable));
             return;
         }
 
@@ -398,94 +587,175 @@ impl DroneState {
                 self.log_report_on = true;
                 self.log_report_eof = u32::from_be_bytes(recv_buf);
                 self.flash_iterator = ADDRESS_OF_LOG_REPORT_EOF + 0x04;
+
+                let size: u32 = self
+                    .log_report_eof
+                    .saturating_sub(ADDRESS_OF_LOG_REPORT_EOF + 0x04);
+                self.send_event(EventCodeT::ReportStarted, [size, 0]);
+                self.set_request_result(ResultT::Ok);
             }
             Err(_) => {
                 // well, unlucky
-                self.send_data(DataT::Message(heapless::String::from(
-                    alloc::format!("e: log report start").as_str(),
-                )));
+                self.send_event(EventCodeT::ReportStartFailed, [0, 0]);
+                self.send_data(DataT::Warning(WarningDT::LogUnavailable));
             }
         };
+    }
 
-        self.send_data(DataT::Message(heapless::String::from(
-            alloc::format!("log report start").as_str(),
-        )));
+    // bytes between the start of the log and the cursor
+    fn logged_bytes(&self) -> u32 {
+        self.flash_iterator
+            .saturating_sub(ADDRESS_OF_LOG_REPORT_EOF + 0x04)
     }
 
     /// Stop the sending of the log report and announced the PC it did this
     /// through a DataT::StopLogReporting message
     pub fn log_report_stop(&mut self) {
-        if self.log_report_on == false {
+        if !self.log_report_on {
+            self.set_request_result(ResultT::Ok);
             return;
         }
 
+        // the last entries, before the end of the report
+        self.send_log_stream();
         self.send_data(DataT::StopLogReporting);
 
         self.log_report_on = false;
+        self.send_event(EventCodeT::ReportStopped, [self.logged_bytes(), 0]);
         self.flash_iterator = ADDRESS_OF_LOG_REPORT_EOF + 0x04;
-
-        self.send_data(DataT::Message(heapless::String::from(
-            alloc::format!("log report stop").as_str(),
-        )));
+        self.set_request_result(ResultT::Ok);
     }
 
-    /// Send a data entry from the log
+    /// Send the next entries of the log, as many as fit in one sensor stream
+    /// frame
     fn log_report_if_enabled(&mut self, iter_count: u32) {
-        if self.log_report_on == false {
+        if !self.log_report_on {
             return;
         }
 
-        if self.flash_iterator >= self.log_report_eof {
-            self.log_report_stop();
+        // wait until the previous entries went out, also the end of the report
+        // needs a place in the queue
+        if self.link.room(Priority::Log) < 2 {
             return;
         }
 
-        if iter_count % self.config.log_report_send_period != 0 {
+        if !self.link.is_stream_due(StreamT::LogReport, iter_count) {
             return;
         }
 
-        let mut recv_buf: [u8; LOG_DATA_FIELD_NO * 2] = [0; LOG_DATA_FIELD_NO * 2];
+        while self.flash_iterator < self.log_report_eof {
+            let mut entry: [u8; LOG_ENTRY_SIZE] = [0; LOG_ENTRY_SIZE];
 
-        let result: Result<(), FlashError> =
-            tudelft_quadrupel::flash::flash_read_bytes(self.flash_iterator, &mut recv_buf);
-        self.flash_iterator += (LOG_DATA_FIELD_NO * 2) as u32;
-
-        match result {
-            Ok(_) => {
-                let log_report_data: DataT = DataT::SensorLog(SensorLogDT {
-                    gyro_x: i16::from_be_bytes([recv_buf[0], recv_buf[1]]),
-                    gyro_y: i16::from_be_bytes([recv_buf[2], recv_buf[3]]),
-                    gyro_z: i16::from_be_bytes([recv_buf[4], recv_buf[5]]),
-
-                    accel_x: i16::from_be_bytes([recv_buf[6], recv_buf[7]]),
-                    accel_y: i16::from_be_bytes([recv_buf[8], recv_buf[9]]),
-                    accel_z: i16::from_be_bytes([recv_buf[10], recv_buf[11]]),
-
-                    pitch: FP::from_num(i16::from_be_bytes([recv_buf[12], recv_buf[13]]))
-                        / FP::from_num(10000),
-                    roll: FP::from_num(i16::from_be_bytes([recv_buf[14], recv_buf[15]]))
-                        / FP::from_num(10000),
-                    yaw: FP::from_num(i16::from_be_bytes([recv_buf[16], recv_buf[17]]))
-                        / FP::from_num(10000),
-                });
-
-                //     self.sensors_raw.get_pitch_value().to_num::<i16>(),
-                // self.sensors_raw.get_roll_value().to_num::<i16>(),
-                // self.sensors_raw.get_yaw_value_der().to_num::<i16>(),
-                // self.sensors_raw.get_pitch_der().to_num::<i16>(),
-                // self.sensors_raw.get_roll_der().to_num::<i16>(),
-                // self.sensors_dmp.get_dmp_pitch_value(self).to_num::<i16>(),
-                // self.sensors_dmp.get_dmp_roll_value(self).to_num::<i16>(),
-                // self.sensors_dmp.get_dmp_yaw_value(self).to_num::<i16>(),
-
-                self.send_data(log_report_data);
-            }
-            Err(_) => {
-                self.send_data(DataT::Message(heapless::String::from(
-                    alloc::format!("e: log report").as_str(),
-                )));
+            let result: Result<(), FlashError> =
+                tudelft_quadrupel::flash::flash_read_bytes(self.flash_iterator, &mut entry);
+            if result.is_err() {
+                self.send_event(EventCodeT::ReportReadFailed, [self.flash_iterator, 0]);
                 self.log_report_stop();
+                return;
             }
-        };
+            self.flash_iterator += LOG_ENTRY_SIZE as u32;
+
+            let field = |i: usize| -> i16 {
+                i16::from_be_bytes([
+                    entry[LOG_TIME_SIZE + i * 2],
+                    entry[LOG_TIME_SIZE + i * 2 + 1],
+                ])
+            };
+            let sample: SensorSample = SensorSample {
+                time: u32::from_be_bytes([entry[0], entry[1], entry[2], entry[3]]),
+                gyro_x: field(0),
+                gyro_y: field(1),
+                gyro_z: field(2),
+                accel_x: field(3),
+                accel_y: field(4),
+                accel_z: field(5),
+                pitch: field(6),
+                roll: field(7),
+                yaw: field(8),
+            };
+
+            if !self.log_encoder.push(&sample) {
+                // the frame is full, the entry starts the next one
+                self.send_log_stream();
+                self.log_encoder.push(&sample);
+                return;
+            }
+        }
+
+        self.log_report_stop();
+    }
+
+    // sends the log entries packed so far
+    fn send_log_stream(&mut self) {
+        if let Some(chunk) = self.log_encoder.take() {
+            self.send_data(DataT::SensorStream(chunk));
+        }
+    }
+
+    /// Streams the current sensor readings, at the rate of the
+    /// `StreamT::SensorStream` stream.
+    fn sensor_stream_if_enabled(&mut self, iter_count: u32) {
+        if !self.link.is_stream_due(StreamT::SensorStream, iter_count) {
+            return;
+        }
+
+        let sample: SensorSample = self.sensor_sample();
+        if !self.live_encoder.push(&sample) {
+            if let Some(chunk) = self.live_encoder.take() {
+                self.send_data(DataT::SensorStream(chunk));
+            }
+            self.live_encoder.push(&sample);
+        }
+    }
+}
+
+impl DroneHost for DroneState {
+    type Transport = UartTransport;
+
+    fn link(&mut self) -> &mut DroneLink<UartTransport> {
+        &mut self.link
+    }
+
+    fn params(&self) -> &'static [ParamDef<Self>] {
+        &PARAMS
+    }
+
+    fn mode(&self) -> DroneMode {
+        self.mode
+    }
+
+    fn now(&self) -> Timestamp {
+        timestamp()
+    }
+
+    /// Draws the nonce of a new session. There is no random number generator,
+    /// it is hashed from the time of the handshake (in microseconds since
+    /// boot), the noise of the sensors and the battery and the previous
+    /// nonce, so it is unlikely to repeat also across restarts.
+    fn new_session(&mut self) -> u64 {
+        let sample: SensorSample = self.sensor_sample();
+        let key: [u8; AUTH_KEY_SIZE] = self.config.auth_key.unwrap_or([0; AUTH_KEY_SIZE]);
+
+        let mut hasher: SipHasher24 = SipHasher24::new(&key);
+        hasher.write(&self.session.to_le_bytes());
+        hasher.write(&timestamp().to_le_bytes());
+        hasher.write(&read_battery().to_le_bytes());
+        for value in [
+            sample.gyro_x,
+            sample.gyro_y,
+            sample.gyro_z,
+            sample.accel_x,
+            sample.accel_y,
+            sample.accel_z,
+            sample.pitch,
+            sample.roll,
+            sample.yaw,
+        ] {
+            hasher.write(&value.to_le_bytes());
+        }
+
+        // `NO_SESSION` is not a session
+        self.session = hasher.finish().max(NO_SESSION + 1);
+        self.session
     }
 }
@@ -0,0 +1,17 @@
+use common::io::Transport;
+use tudelft_quadrupel::uart;
+
+/// The UART of the drone, it has to be initialized already.
+pub struct UartTransport;
+
+impl Transport for UartTransport {
+    #[inline]
+    fn read(&mut self, buf: &mut [u8]) -> usize {
+        uart::receive_bytes(buf)
+    }
+
+    #[inline]
+    fn write(&mut self, data: &[u8]) -> bool {
+        uart::send_bytes(data)
+    }
+}
@@ -79,9 +79,8 @@ fn panic(info: &PanicInfo) -> ! {
     use tudelft_quadrupel::uart::send_bytes;
 
     if uart::is_initialized() {
-        let mut pipe = common::io::ComT::<8>::new(
-            tudelft_quadrupel::uart::receive_bytes,
-            tudelft_quadrupel::uart::send_bytes,
+        let mut pipe = common::io::ComT::<8, drone::uart::UartTransport>::with_transport(
+            drone::uart::UartTransport,
         );
 
         let mut msg: &str = "panic!";
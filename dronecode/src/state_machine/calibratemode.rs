@@ -6,6 +6,7 @@ use tudelft_quadrupel::led::Led::{Green, Red, Yellow};
 
 // Our libraries
 use common::protocol::DataT::{Empty, KeepAlive, Mode};
+use common::protocol::EventCodeT;
 use common::DroneMode;
 
 use crate::drone::state::DroneState;
@@ -35,6 +36,7 @@ impl ModeTrait for CalibrateMode {
         } else {
             // state.sensors.calibrate(SAMPLE_SIZE);
             state.calibrated_data.calibrate(SAMPLE_SIZE);
+            state.send_event(EventCodeT::Calibrated, [0, 0]);
         }
 
         DroneMode::Panic
@@ -4,8 +4,7 @@ use crate::drone::controller::{pitch_control_dmp, roll_control_dmp, yaw_control_
 use crate::drone::state::DroneState;
 use crate::state_machine::ModeTrait;
 use common::motor_control::motor_mapping;
-use common::protocol::DataT::{Control, Empty, KeepAlive, Mode, UpdateP, UpdateP1P2};
-use common::protocol::{DataT, UpdateP1P2DT, UpdatePDT};
+use common::protocol::DataT::{Control, Empty, KeepAlive, Mode};
 use common::DroneMode;
 use tudelft_quadrupel::led::Led::{Green, Red, Yellow};
 
@@ -58,19 +57,6 @@ impl ModeTrait for FullControlMode {
                     // TODO: decide if to handle wrong mode transitions or quietly ignore them?
                 }
 
-                UpdateP(updatedPidValues) => {
-                    state.P = updatedPidValues.p;
-                    state.send_data(DataT::UpdateP(UpdatePDT { p: state.P }));
-                }
-                UpdateP1P2(updatedFullControlPIDValues) => {
-                    state.P1 = updatedFullControlPIDValues.p1;
-                    state.P2 = updatedFullControlPIDValues.p2;
-                    state.send_data(DataT::UpdateP1P2(UpdateP1P2DT {
-                        p1: state.P1,
-                        p2: state.P2,
-                    }));
-                }
-
                 KeepAlive => {
                     state.got_keep_alive();
                 }
@@ -103,7 +89,7 @@ impl ModeTrait for FullControlMode {
         //     alloc::format!("y{}", response).as_str(),
         // ));
 
-        let mapped_motor_value = motor_mapping(cc);
+        let mapped_motor_value = motor_mapping(cc, state.mixer());
 
         state.set_motors(mapped_motor_value.unwrap());
     }
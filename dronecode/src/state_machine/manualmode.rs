@@ -82,7 +82,7 @@ impl ModeTrait for ManualMode {
 
     fn do_motor_control(state: &mut DroneState, delta_t: Duration) {
         // TODO: handle errors
-        let motor_comman: [u16; 4] = motor_mapping(state.get_cc_as_vec()).unwrap();
+        let motor_comman: [u16; 4] = motor_mapping(state.get_cc_as_vec(), state.mixer()).unwrap();
 
         state.set_motors(motor_comman);
     }
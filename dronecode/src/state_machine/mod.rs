@@ -7,16 +7,19 @@ pub(crate) mod safemode;
 pub(crate) mod yawcontrolmode;
 
 // Rust libraries
-use common::protocol::{HealthDT, MotorsDT};
+use common::protocol::{HealthDT, MotorsDT, StreamT, BATTERY_CRITICAL};
 use core::time::Duration;
 
 // TUDelft library
+use tudelft_quadrupel::barometer::read_pressure;
 use tudelft_quadrupel::battery::read_battery;
 use tudelft_quadrupel::led::Red;
 
 // Our libraries
+use common::utility::loop_stats::LoopReport;
 use common::DroneMode;
 
+use crate::control::timestamp;
 use crate::drone::state::DroneState;
 
 /*
@@ -63,7 +66,7 @@ pub trait ModeTrait {
             battery_value = read_battery();
         }
 
-        if battery_value < 1050 {
+        if battery_value < BATTERY_CRITICAL {
             // Send Battery Low Message Here
             return true;
         }
@@ -73,32 +76,40 @@ pub trait ModeTrait {
 
     /// A function called by every mode in order to perform periodic actions.
     fn do_periodic(state: &mut DroneState, iter_count: u32) {
-        // send ACK periodically
-        if iter_count % state.config.ka_tick_period == 0 {
-            state.send_alive();
+        // send the status periodically
+        if state.is_stream_due(StreamT::Heartbeat, iter_count) {
+            state.send_heartbeat();
         }
 
         if state.config.check_battery {
-            if iter_count % state.config.battery_printing_time == 0 {
+            if state.is_stream_due(StreamT::Health, iter_count) {
+                let timing: LoopReport = state.loop_report();
+
                 state.send_data(common::protocol::DataT::HealthData(HealthDT {
+                    time: timestamp(),
                     bat: read_battery(),
-                    cpu: 0,
-                    pres: 0,
+                    cpu: timing.cpu,
+                    pres: read_pressure(),
+                    loop_avg: saturate(timing.avg_busy),
+                    loop_max: saturate(timing.max_busy),
+                    jitter: saturate(timing.jitter),
+                    overruns: saturate(timing.overruns),
                 }));
             }
         }
 
-        if iter_count % 10 == 0 {
-            let d = state.debug_info.clone();
-            &state.send_data(d);
+        if state.is_stream_due(StreamT::LinkStats, iter_count) {
+            let stats = state.link_stats();
+            state.send_data(common::protocol::DataT::LinkStats(stats));
         }
 
         //#[cfg(debug_assertions)]
         {
-            if iter_count % state.config.debug_motor_command_period == 0 {
+            if state.is_stream_due(StreamT::Motors, iter_count) {
                 let mc: [u16; 4] = state.get_motors();
 
                 state.send_data(common::protocol::DataT::MotorsState(MotorsDT {
+                    time: timestamp(),
                     ae1: mc[0],
                     ae2: mc[1],
                     ae3: mc[2],
@@ -113,10 +124,15 @@ pub trait ModeTrait {
             //     )));
             // }
             // debug periodic message
-            if iter_count % state.config.debug_info_period == 0 {
+            if state.is_stream_due(StreamT::DebugInfo, iter_count) {
                 let d = state.debug_info.clone();
                 state.send_data(d);
             }
         }
     }
 }
+
+// the loop timing is sent as u16, larger values are capped
+fn saturate(value: u32) -> u16 {
+    value.min(u16::MAX as u32) as u16
+}
@@ -4,8 +4,8 @@ use core::time::Duration;
 // TUDelft library
 use tudelft_quadrupel::led::Led::{Green, Red, Yellow};
 // Our libraries
+use common::drone_link::safe_mode_transition;
 use common::protocol::DataT::*;
-use common::protocol::{ControlDT, DataT, WarningDT};
 use common::DroneMode;
 
 // This crate imports
@@ -57,43 +57,20 @@ impl ModeTrait for SafeMode {
                 }
 
                 Mode(mode) => {
-                    // Check if the last control package received was {thrust = 0 & p/r/y = 1024}
-                    if mode == DroneMode::Panic {
-                        ret = DroneMode::Panic
-                    } else if !is_control_neutral(state.get_cc()) {
-                        // warn PC we can not change mode because controls are not neutral
-                        state.send_data(DataT::Warning(WarningDT::ControlNotNeutral));
-
-                        return ret;
-                    } else if mode == DroneMode::Manual {
-                        // Transition to Manual Mode
-                        ret = DroneMode::Manual;
-                    } else if mode == DroneMode::Calibrate {
-                        // Transition to Manual Mode
-                        ret = DroneMode::Calibrate;
-                    } else if mode == DroneMode::YawControl {
-                        if !state.calibrated_data.is_calibrated() {
-                            state.send_data(Warning(WarningDT::SensorNotCalibrated));
-                            ret = DroneMode::Safe;
-                        } else {
-                            // Transition to Yaw control Mode
-                            ret = DroneMode::YawControl;
-                        }
-                    } else if mode == DroneMode::FullControl {
-                        if !state.calibrated_data.is_calibrated() {
-                            state.send_data(Warning(WarningDT::SensorNotCalibrated));
-                            ret = DroneMode::Safe;
-                        } else {
-                            // Transition to Yaw control Mode
-                            ret = DroneMode::FullControl;
-                        }
-                    } else if mode == DroneMode::RawMode {
-                        if !state.calibrated_data.is_calibrated() {
-                            state.send_data(Warning(WarningDT::SensorNotCalibrated));
-                            ret = DroneMode::Safe;
-                        } else {
-                            // Transition to Yaw control Mode
-                            ret = DroneMode::RawMode;
+                    match safe_mode_transition(
+                        mode,
+                        state.is_handshake_ok(),
+                        state.get_cc(),
+                        state.config.dead_margin,
+                        state.calibrated_data.is_calibrated(),
+                    ) {
+                        Ok(next) => ret = next,
+                        Err(warning) => {
+                            // warn PC we can not change mode, e.g. because
+                            // the controls are not neutral
+                            state.send_data(Warning(warning));
+
+                            return ret;
                         }
                     }
                 }
@@ -136,23 +113,3 @@ impl ModeTrait for SafeMode {
         DroneMode::Safe
     }
 }
-
-fn is_control_neutral(ctrl: ControlDT) -> bool {
-    let dead_margin: u16 = 50;
-    let upper = 1024 + dead_margin;
-    let lower = 1024 - dead_margin;
-
-    if ctrl.lift > 0 {
-        return false;
-    }
-    if ctrl.roll > upper || ctrl.roll < lower {
-        return false;
-    }
-    if ctrl.pitch > upper || ctrl.pitch < lower {
-        return false;
-    }
-    if ctrl.yaw > upper || ctrl.yaw < lower {
-        return false;
-    }
-    return true;
-}
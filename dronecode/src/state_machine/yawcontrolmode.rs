@@ -6,8 +6,7 @@ use tudelft_quadrupel::led::Led::{Green, Red, Yellow};
 
 // Our libraries
 use common::motor_control::motor_mapping;
-use common::protocol::DataT;
-use common::protocol::{DataT::*, UpdatePDT};
+use common::protocol::DataT::*;
 use common::DroneMode;
 
 use crate::drone::controller::yaw_control_dmp;
@@ -65,11 +64,6 @@ impl ModeTrait for YawControlMode {
                     // TODO: decide if to handle wrong mode transitions or quietly ignore them?
                 }
 
-                UpdateP(updatedPidValues) => {
-                    state.P = updatedPidValues.p;
-                    state.send_data(DataT::UpdateP(UpdatePDT { p: state.P }));
-                }
-
                 KeepAlive => {
                     state.got_keep_alive();
                 }
@@ -99,7 +93,7 @@ impl ModeTrait for YawControlMode {
         //     alloc::format!("y{}", response).as_str(),
         // ));
 
-        let mapped_motor_value = motor_mapping(cc);
+        let mapped_motor_value = motor_mapping(cc, state.mixer());
 
         state.set_motors(mapped_motor_value.unwrap());
     }
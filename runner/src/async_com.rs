@@ -0,0 +1,457 @@
+// Rust libraries
+use std::collections::VecDeque;
+use std::future::{poll_fn, Future};
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// Our libraries
+use common::io::{ComErr, ComT, Transport};
+use common::protocol::DataT;
+
+use crate::serial_wrapper::SerialTransport;
+use crate::utils::constants::{RUNNER_INBOX_CAP, RUNNER_SEND_RETRIES};
+
+// bytes read from the serial and not decoded yet, with the task waiting for them
+struct InboxT {
+    bytes: VecDeque<u8>,
+    waker: Option<Waker>,
+}
+
+type Inbox = Arc<Mutex<InboxT>>;
+
+/// Transport of an `AsyncComT`: the bytes come from the inbox filled by the
+/// reader thread, they are written to the link directly.
+pub struct InboxTransport<T: Transport> {
+    inbox: Inbox,
+    link: T,
+}
+
+impl<T: Transport> Transport for InboxTransport<T> {
+    fn read(&mut self, bytes: &mut [u8]) -> usize {
+        let mut inbox = self.inbox.lock().unwrap();
+
+        let len: usize = bytes.len().min(inbox.bytes.len());
+        for (byte, received) in bytes.iter_mut().zip(inbox.bytes.drain(..len)) {
+            *byte = received;
+        }
+
+        len
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> bool {
+        self.link.write(bytes)
+    }
+
+    fn flush(&mut self) {
+        self.link.flush();
+    }
+}
+
+/// `ComT` for an async runner. A thread reads the serial and wakes the task
+/// waiting in `recv`, so the messages of the drone are decoded as soon as
+/// their bytes arrive instead of once per tick.
+///
+/// The synchronous API of `ComT` stays available through `Deref`, it never
+/// blocks on reading.
+pub struct AsyncComT<const BUF_CAP: usize, T: Transport = SerialTransport> {
+    pipe: ComT<BUF_CAP, InboxTransport<T>>,
+    inbox: Inbox,
+}
+
+impl<const BUF_CAP: usize> AsyncComT<BUF_CAP> {
+    /// Starts reading `serial` in the background.
+    pub fn new(serial: SerialTransport) -> Self {
+        let com: Self = Self::with_link(serial.clone());
+
+        let reader_inbox: Inbox = com.inbox.clone();
+        thread::spawn(move || read_serial(serial, reader_inbox));
+
+        com
+    }
+}
+
+impl<const BUF_CAP: usize, T: Transport> AsyncComT<BUF_CAP, T> {
+    // writes to `link`, nothing fills the inbox yet
+    pub(crate) fn with_link(link: T) -> Self {
+        let inbox: Inbox = Arc::new(Mutex::new(InboxT {
+            bytes: VecDeque::new(),
+            waker: None,
+        }));
+
+        Self {
+            pipe: ComT::with_transport(InboxTransport {
+                inbox: inbox.clone(),
+                link,
+            }),
+            inbox,
+        }
+    }
+
+    // what the reader thread of `new` does with the bytes it read
+    #[cfg(test)]
+    pub(crate) fn deliver(&self, bytes: &[u8]) {
+        deliver(&self.inbox, bytes);
+    }
+
+    /// Next message of the drone, waits until one arrives. Errors are
+    /// returned like `ComT::read_data` does, except `ComErr::Empty`.
+    ///
+    /// Dropping the future before it completes loses nothing.
+    pub async fn recv<const CAP: usize>(&mut self) -> Result<DataT, ComErr> {
+        poll_fn(|cx: &mut Context<'_>| match self.pipe.read_data::<CAP>() {
+            Err(ComErr::Empty) => {
+                let mut inbox = self.inbox.lock().unwrap();
+
+                // bytes that arrived since the read are not missed
+                if inbox.bytes.is_empty() {
+                    inbox.waker = Some(cx.waker().clone());
+                } else {
+                    cx.waker().wake_by_ref();
+                }
+                Poll::Pending
+            }
+            result => Poll::Ready(result),
+        })
+        .await
+    }
+
+    /// Same as `ComT::send_data`, but a busy serial lets the other tasks run
+    /// before trying again.
+    pub async fn send<const CAP: usize>(&mut self, data: DataT) -> Result<usize, ComErr> {
+        let mut retries: u32 = 0;
+
+        loop {
+            match self.pipe.send_data::<CAP>(data.clone()) {
+                Err(ComErr::Busy) if retries < RUNNER_SEND_RETRIES => {
+                    retries += 1;
+                    yield_now().await;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+impl<const BUF_CAP: usize, T: Transport> Deref for AsyncComT<BUF_CAP, T> {
+    type Target = ComT<BUF_CAP, InboxTransport<T>>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.pipe
+    }
+}
+
+impl<const BUF_CAP: usize, T: Transport> DerefMut for AsyncComT<BUF_CAP, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.pipe
+    }
+}
+
+// body of the reader thread, the read timeout of the serial bounds each wait
+fn read_serial(mut serial: SerialTransport, inbox: Inbox) {
+    let mut buf: [u8; 64] = [0; 64];
+
+    loop {
+        let read: usize = serial.read(&mut buf);
+        if read == 0 {
+            continue;
+        }
+
+        deliver(&inbox, &buf[..read]);
+    }
+}
+
+// puts the bytes read in the inbox and wakes the task waiting for them
+fn deliver(inbox: &Inbox, bytes: &[u8]) {
+    let mut inbox = inbox.lock().unwrap();
+    inbox.bytes.extend(bytes);
+
+    // nobody reads, keep the latest bytes
+    let excess: usize = inbox.bytes.len().saturating_sub(RUNNER_INBOX_CAP);
+    if excess > 0 {
+        inbox.bytes.drain(..excess);
+        log::error!("[ERROR]: {} bytes from the drone were dropped", excess);
+    }
+
+    if let Some(waker) = inbox.waker.take() {
+        waker.wake();
+    }
+}
+
+// ticks that elapsed and were not waited for yet, with the task waiting for them
+struct TicksT {
+    due: u32,
+    waker: Option<Waker>,
+}
+
+/// Periodic timer for async tasks, a thread counts the ticks.
+pub struct Interval {
+    ticks: Arc<Mutex<TicksT>>,
+}
+
+impl Interval {
+    /// The first tick is one `period` from now.
+    pub fn new(period: Duration) -> Self {
+        let ticks: Arc<Mutex<TicksT>> = Arc::new(Mutex::new(TicksT {
+            due: 0,
+            waker: None,
+        }));
+
+        let counter: Arc<Mutex<TicksT>> = ticks.clone();
+        thread::spawn(move || {
+            // the deadlines do not drift with the time spent waking the task
+            let mut next: Instant = Instant::now() + period;
+            loop {
+                thread::sleep(next.saturating_duration_since(Instant::now()));
+                next += period;
+
+                let mut ticks = counter.lock().unwrap();
+                ticks.due += 1;
+                if let Some(waker) = ticks.waker.take() {
+                    waker.wake();
+                }
+            }
+        });
+
+        Self { ticks }
+    }
+
+    /// Waits for the next tick.
+    /// @return how many ticks elapsed since the last call, more than 1 if the
+    /// task was late
+    pub async fn tick(&mut self) -> u32 {
+        poll_fn(|cx: &mut Context<'_>| {
+            let mut ticks = self.ticks.lock().unwrap();
+
+            match ticks.due {
+                0 => {
+                    ticks.waker = Some(cx.waker().clone());
+                    Poll::Pending
+                }
+                due => {
+                    ticks.due = 0;
+                    Poll::Ready(due)
+                }
+            }
+        })
+        .await
+    }
+}
+
+/// Polls `first` then `second`, completes with the output of the first one
+/// ready. The other one is dropped.
+pub async fn race<A, B, T>(first: A, second: B) -> T
+where
+    A: Future<Output = T>,
+    B: Future<Output = T>,
+{
+    let mut first = std::pin::pin!(first);
+    let mut second = std::pin::pin!(second);
+
+    poll_fn(|cx: &mut Context<'_>| {
+        if let Poll::Ready(output) = first.as_mut().poll(cx) {
+            return Poll::Ready(output);
+        }
+        second.as_mut().poll(cx)
+    })
+    .await
+}
+
+// lets the executor run the other tasks once
+async fn yield_now() {
+    let mut yielded: bool = false;
+
+    poll_fn(|cx: &mut Context<'_>| {
+        if yielded {
+            return Poll::Ready(());
+        }
+
+        yielded = true;
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    })
+    .await
+}
+
+#[cfg(test)]
+mod test {
+    use std::future::{pending, ready};
+    use std::pin::{pin, Pin};
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    use crate::async_com::*;
+    use common::io::{duplex, DuplexEnd, FaultConfig};
+
+    type Link = DuplexEnd<64>;
+
+    // remembers that the task polled by hand was woken
+    struct WokenT(AtomicBool);
+
+    impl std::task::Wake for WokenT {
+        fn wake(self: Arc<Self>) {
+            self.0.store(true, Ordering::SeqCst);
+        }
+    }
+
+    impl WokenT {
+        fn new() -> Arc<Self> {
+            Arc::new(Self(AtomicBool::new(false)))
+        }
+
+        // if it was woken since the last call
+        fn take(&self) -> bool {
+            self.0.swap(false, Ordering::SeqCst)
+        }
+    }
+
+    fn poll<F: Future>(future: Pin<&mut F>, woken: &Arc<WokenT>) -> Poll<F::Output> {
+        let waker: Waker = Waker::from(woken.clone());
+        future.poll(&mut Context::from_waker(&waker))
+    }
+
+    // polls `future` until it is ready, waiting for its wake-ups in between
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let woken: Arc<WokenT> = WokenT::new();
+        let mut future = pin!(future);
+
+        loop {
+            if let Poll::Ready(output) = poll(future.as_mut(), &woken) {
+                return output;
+            }
+            while !woken.take() {
+                thread::sleep(Duration::from_millis(1));
+            }
+        }
+    }
+
+    // an `AsyncComT` linked to a drone, with a clone of its end of the link to
+    // play the reader thread
+    fn linked() -> (AsyncComT<64, Link>, Link, ComT<64, Link>) {
+        let (runner, drone) = duplex::<64>(FaultConfig::none(), FaultConfig::none());
+        let reader: Link = runner.clone();
+
+        (
+            AsyncComT::with_link(runner),
+            reader,
+            ComT::with_transport(drone),
+        )
+    }
+
+    // what the reader thread does with at most `len` bytes on the wire
+    fn pump(reader: &mut Link, inbox: &Inbox, len: usize) {
+        let mut buf: [u8; 64] = [0; 64];
+        let read: usize = reader.read(&mut buf[..len]);
+        if read > 0 {
+            deliver(inbox, &buf[..read]);
+        }
+    }
+
+    #[test]
+    fn test_recv_wakes_up_on_a_whole_frame() {
+        let (mut com, mut reader, mut drone) = linked();
+        let inbox: Inbox = com.inbox.clone();
+        let woken: Arc<WokenT> = WokenT::new();
+
+        drone.send_data::<64>(DataT::KeepAlive).unwrap();
+        let mut recv = pin!(com.recv::<64>());
+        assert!(poll(recv.as_mut(), &woken).is_pending());
+
+        // half a frame wakes the task, which waits again for the rest
+        pump(&mut reader, &inbox, 2);
+        assert!(woken.take());
+        assert!(poll(recv.as_mut(), &woken).is_pending());
+        assert!(!woken.take());
+
+        pump(&mut reader, &inbox, 64);
+        assert!(woken.take());
+        assert!(matches!(
+            poll(recv.as_mut(), &woken),
+            Poll::Ready(Ok(DataT::KeepAlive))
+        ));
+    }
+
+    #[test]
+    fn test_dropped_recv_loses_nothing() {
+        let (mut com, mut reader, mut drone) = linked();
+        let inbox: Inbox = com.inbox.clone();
+        let woken: Arc<WokenT> = WokenT::new();
+
+        {
+            let mut recv = pin!(com.recv::<64>());
+            assert!(poll(recv.as_mut(), &woken).is_pending());
+        }
+
+        drone.send_data::<64>(DataT::StartLogging).unwrap();
+        pump(&mut reader, &inbox, 64);
+
+        assert!(matches!(
+            block_on(com.recv::<64>()),
+            Ok(DataT::StartLogging)
+        ));
+    }
+
+    #[test]
+    fn test_interval_counts_missed_ticks() {
+        let period: Duration = Duration::from_millis(10);
+        let mut interval: Interval = Interval::new(period);
+        let start: Instant = Instant::now();
+
+        assert!(block_on(interval.tick()) >= 1);
+        assert!(start.elapsed() >= period);
+
+        // a late task gets the ticks it missed at once
+        thread::sleep(6 * period);
+        assert!(block_on(interval.tick()) >= 4);
+        let woken: Arc<WokenT> = WokenT::new();
+        assert!(poll(pin!(interval.tick()), &woken).is_pending());
+    }
+
+    #[test]
+    fn test_race() {
+        assert_eq!(block_on(race(ready(1), ready(2))), 1);
+        assert_eq!(block_on(race(pending(), ready(2))), 2);
+
+        // woken by the second one
+        let mut interval: Interval = Interval::new(Duration::from_millis(5));
+        assert!(block_on(race(pending(), interval.tick())) >= 1);
+    }
+
+    #[test]
+    fn test_send_retries_a_busy_link() {
+        let (mut com, mut reader, mut drone) = linked();
+        let woken: Arc<WokenT> = WokenT::new();
+
+        // the link to the drone is almost full
+        assert!(reader.write(&[0; 60]));
+        let mut send = pin!(com.send::<64>(DataT::KeepAlive));
+        assert!(poll(send.as_mut(), &woken).is_pending());
+        // the other tasks run, then it tries again
+        assert!(woken.take());
+        assert!(poll(send.as_mut(), &woken).is_pending());
+
+        let mut buf: [u8; 64] = [0; 64];
+        assert_eq!(drone.transport_mut().read(&mut buf), 60);
+        assert!(matches!(poll(send.as_mut(), &woken), Poll::Ready(Ok(_))));
+        assert!(matches!(drone.read_data::<64>(), Ok(DataT::KeepAlive)));
+    }
+
+    #[test]
+    fn test_send_gives_up() {
+        let (mut com, mut reader, _drone) = linked();
+        let woken: Arc<WokenT> = WokenT::new();
+
+        assert!(reader.write(&[0; 60]));
+        let mut send = pin!(com.send::<64>(DataT::KeepAlive));
+        for _ in 0..RUNNER_SEND_RETRIES {
+            assert!(poll(send.as_mut(), &woken).is_pending());
+            assert!(woken.take());
+        }
+
+        assert!(matches!(
+            poll(send.as_mut(), &woken),
+            Poll::Ready(Err(ComErr::Busy))
+        ));
+    }
+}
@@ -1,10 +1,25 @@
 use eframe::egui;
 use std::sync::{Arc, Mutex};
 
-#[derive(Clone)]
+/// Parameter request from the GUI, handled by the logic thread.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParamRequest {
+    List,                                // ask the drone for all its parameters again
+    Set { name: String, value: String }, // the value is parsed for the type of the parameter
+}
+
+/// Telemetry stream request from the GUI, handled by the logic thread.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StreamRequest {
+    Get,                                    // ask the drone for the rates it applies
+    Set { name: String, interval: String }, // interval in milliseconds, 0 switches it off
+}
+
+#[derive(Clone, Default)]
 pub struct GuiParams {
     pub(crate) status: Arc<Mutex<u8>>,
     pub(crate) drone_mode: Arc<Mutex<String>>,
+    pub(crate) heartbeat: Arc<Mutex<String>>, // status of the drone, from its last heartbeat
     pub(crate) last_keyboard_key_pressed: Arc<Mutex<String>>,
     pub(crate) joystick_roll_input: Arc<Mutex<i32>>,
     pub(crate) joystick_pitch_input: Arc<Mutex<i32>>,
@@ -14,13 +29,24 @@ pub struct GuiParams {
     pub(crate) motor_2_value: Arc<Mutex<u16>>,
     pub(crate) motor_3_value: Arc<Mutex<u16>>,
     pub(crate) motor_4_value: Arc<Mutex<u16>>,
-    pub(crate) yaw_p: Arc<Mutex<i32>>,
-    pub(crate) rp_p1: Arc<Mutex<i32>>,
-    pub(crate) rp_p2: Arc<Mutex<i32>>,
+    pub(crate) params: Arc<Mutex<String>>, // the parameters of the drone, one per line
+    pub(crate) param_name_input: Arc<Mutex<String>>,
+    pub(crate) param_value_input: Arc<Mutex<String>>,
+    pub(crate) param_request: Arc<Mutex<Option<ParamRequest>>>,
+    pub(crate) streams: Arc<Mutex<String>>, // rates of the telemetry streams of the drone
+    pub(crate) stream_name_input: Arc<Mutex<String>>,
+    pub(crate) stream_interval_input: Arc<Mutex<String>>,
+    pub(crate) stream_request: Arc<Mutex<Option<StreamRequest>>>,
     pub(crate) last_message_received: Arc<Mutex<String>>,
     pub(crate) debug_prints_from_drone: Arc<Mutex<String>>,
     pub(crate) is_battery_weak: Arc<Mutex<bool>>,
     pub(crate) battery_health: Arc<Mutex<u16>>,
+    pub(crate) loop_health: Arc<Mutex<String>>, // timing of the drone control loop
+    pub(crate) events: Arc<Mutex<String>>,      // errors and events reported by the drone
+    pub(crate) link_stats_runner: Arc<Mutex<String>>,
+    pub(crate) link_stats_drone: Arc<Mutex<String>>,
+    pub(crate) clock_sync: Arc<Mutex<String>>, // estimate of the drone clock
+    pub(crate) last_request: Arc<Mutex<String>>, // outcome of the last command sent to the drone
 }
 
 pub fn gui_terminal_init(drone_status: GuiParams) -> Result<(), eframe::Error> {
@@ -37,6 +63,7 @@ pub fn gui_terminal_init(drone_status: GuiParams) -> Result<(), eframe::Error> {
             Box::new(GuiParams {
                 status: drone_status.status,
                 drone_mode: drone_status.drone_mode,
+                heartbeat: drone_status.heartbeat,
                 last_keyboard_key_pressed: drone_status.last_keyboard_key_pressed,
                 joystick_roll_input: drone_status.joystick_roll_input,
                 joystick_pitch_input: drone_status.joystick_pitch_input,
@@ -49,10 +76,21 @@ pub fn gui_terminal_init(drone_status: GuiParams) -> Result<(), eframe::Error> {
                 last_message_received: drone_status.last_message_received,
                 debug_prints_from_drone: drone_status.debug_prints_from_drone,
                 is_battery_weak: drone_status.is_battery_weak,
-                yaw_p: drone_status.yaw_p,
-                rp_p1: drone_status.rp_p1,
-                rp_p2: drone_status.rp_p2,
+                params: drone_status.params,
+                param_name_input: drone_status.param_name_input,
+                param_value_input: drone_status.param_value_input,
+                param_request: drone_status.param_request,
+                streams: drone_status.streams,
+                stream_name_input: drone_status.stream_name_input,
+                stream_interval_input: drone_status.stream_interval_input,
+                stream_request: drone_status.stream_request,
                 battery_health: drone_status.battery_health,
+                loop_health: drone_status.loop_health,
+                events: drone_status.events,
+                link_stats_runner: drone_status.link_stats_runner,
+                link_stats_drone: drone_status.link_stats_drone,
+                clock_sync: drone_status.clock_sync,
+                last_request: drone_status.last_request,
             })
         }),
     )
@@ -106,6 +144,17 @@ impl eframe::App for GuiParams {
                 "Motor_4_Value: {:?}",
                 self.motor_4_value.lock().unwrap()
             ));
+
+            ui.heading("LINK");
+            ui.label(format!(
+                "Runner side: {}",
+                self.link_stats_runner.lock().unwrap()
+            ));
+            ui.label(format!(
+                "Drone side: {}",
+                self.link_stats_drone.lock().unwrap()
+            ));
+            ui.label(format!("Clock: {}", self.clock_sync.lock().unwrap()));
         });
         // egui::TopBottomPanel::Top("Left").show(ctx, |ui| {
         //     egui::widgets::global_dark_light_mode_buttons(ui);
@@ -116,6 +165,7 @@ impl eframe::App for GuiParams {
             ui.heading("Drone STUFF");
 
             ui.heading(format!("Drone Mode: {:?}", self.drone_mode.lock().unwrap()));
+            ui.heading(format!("Drone status: {}", self.heartbeat.lock().unwrap()));
             ui.heading(format!(
                 "Battery weak: {:?}",
                 self.is_battery_weak.lock().unwrap()
@@ -126,18 +176,70 @@ impl eframe::App for GuiParams {
                 self.last_message_received.lock().unwrap()
             ));
 
+            ui.heading(format!(
+                "Last command: {}",
+                self.last_request.lock().unwrap()
+            ));
+
             ui.heading(format!(
                 "Debug prints values: {:?}",
                 self.debug_prints_from_drone.lock().unwrap()
             ));
 
-            ui.heading(format!("Yaw P: {:?}", self.yaw_p.lock().unwrap()));
-            ui.heading(format!("FC P1: {:?}", self.rp_p1.lock().unwrap()));
-            ui.heading(format!("FC P2: {:?}", self.rp_p2.lock().unwrap()));
             ui.heading(format!(
                 "Battery Value: {:?}",
                 self.battery_health.lock().unwrap()
             ));
+            ui.heading(format!(
+                "Control loop: {}",
+                self.loop_health.lock().unwrap()
+            ));
+
+            ui.heading("EVENTS");
+            ui.label(self.events.lock().unwrap().as_str());
+
+            ui.heading("PARAMETERS");
+            ui.label(self.params.lock().unwrap().as_str());
+            ui.horizontal(|ui| {
+                ui.label("Name:");
+                ui.text_edit_singleline(&mut *self.param_name_input.lock().unwrap());
+                ui.label("Value:");
+                ui.text_edit_singleline(&mut *self.param_value_input.lock().unwrap());
+
+                if ui.button("Set").clicked() {
+                    *self.param_request.lock().unwrap() = Some(ParamRequest::Set {
+                        name: self.param_name_input.lock().unwrap().trim().to_string(),
+                        value: self.param_value_input.lock().unwrap().trim().to_string(),
+                    });
+                }
+                if ui.button("Refresh").clicked() {
+                    *self.param_request.lock().unwrap() = Some(ParamRequest::List);
+                }
+            });
+
+            ui.heading("TELEMETRY STREAMS");
+            ui.label(self.streams.lock().unwrap().as_str());
+            ui.horizontal(|ui| {
+                ui.label("Stream:");
+                ui.text_edit_singleline(&mut *self.stream_name_input.lock().unwrap());
+                ui.label("Interval (ms, 0 = off):");
+                ui.text_edit_singleline(&mut *self.stream_interval_input.lock().unwrap());
+
+                if ui.button("Subscribe").clicked() {
+                    *self.stream_request.lock().unwrap() = Some(StreamRequest::Set {
+                        name: self.stream_name_input.lock().unwrap().trim().to_string(),
+                        interval: self
+                            .stream_interval_input
+                            .lock()
+                            .unwrap()
+                            .trim()
+                            .to_string(),
+                    });
+                }
+                if ui.button("Refresh").clicked() {
+                    *self.stream_request.lock().unwrap() = Some(StreamRequest::Get);
+                }
+            });
 
             ui.ctx().request_repaint();
             if ui.button("Exit").clicked() {
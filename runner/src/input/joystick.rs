@@ -4,7 +4,7 @@ use std::task::Poll::{self, Pending};
 
 use common::DroneMode;
 // Other crates
-use common::protocol::DataT;
+use common::protocol::CommandT;
 use lazy_static::lazy_static;
 use pasts::Loop;
 use stick::{Controller, Event, Listener};
@@ -30,7 +30,7 @@ lazy_static! {
         throttle_trim: Arc::new(Mutex::new(0)),
         yaw_trim: Arc::new(Mutex::new(0)),
         data_logging_state: Arc::new(Mutex::new(false)),
-        data_logging_action: Arc::new(Mutex::new(DataT::StopLogReporting)),
+        data_logging_action: Arc::new(Mutex::new(CommandT::StopLogReporting)),
     };
 }
 
@@ -12,7 +12,7 @@ use termion::raw::IntoRawMode;
 
 // Our libraries
 use crate::gui::GuiParams;
-use common::protocol::DataT;
+use common::protocol::CommandT;
 use common::DroneMode;
 
 // This crate imports
@@ -36,7 +36,7 @@ lazy_static! {
         throttle_trim: Arc::new(Mutex::new(0)),
         yaw_trim: Arc::new(Mutex::new(0)),
         data_logging_state: Arc::new(Mutex::new(false)),
-        data_logging_action: Arc::new(Mutex::new(DataT::StopLogReporting)),
+        data_logging_action: Arc::new(Mutex::new(CommandT::StopLogReporting)),
     };
 }
 
@@ -244,28 +244,28 @@ pub(crate) fn key_input(gui_params_1: GuiParams) {
                 *gui_params_1.last_keyboard_key_pressed.lock().unwrap() =
                     "Start Logging".to_string();
                 *INPUT_STATE_KB.data_logging_state.lock().unwrap() = true;
-                *INPUT_STATE_KB.data_logging_action.lock().unwrap() = DataT::StartLogging;
+                *INPUT_STATE_KB.data_logging_action.lock().unwrap() = CommandT::StartLogging;
             }
 
             Key::Char('v') => {
                 *gui_params_1.last_keyboard_key_pressed.lock().unwrap() =
                     "Stop Logging".to_string();
                 *INPUT_STATE_KB.data_logging_state.lock().unwrap() = true;
-                *INPUT_STATE_KB.data_logging_action.lock().unwrap() = DataT::StopLogging;
+                *INPUT_STATE_KB.data_logging_action.lock().unwrap() = CommandT::StopLogging;
             }
 
             Key::Char('b') => {
                 *gui_params_1.last_keyboard_key_pressed.lock().unwrap() =
                     "Start Log Reporting".to_string();
                 *INPUT_STATE_KB.data_logging_state.lock().unwrap() = true;
-                *INPUT_STATE_KB.data_logging_action.lock().unwrap() = DataT::StartLogReporting;
+                *INPUT_STATE_KB.data_logging_action.lock().unwrap() = CommandT::StartLogReporting;
             }
 
             Key::Char('n') => {
                 *gui_params_1.last_keyboard_key_pressed.lock().unwrap() =
                     "Stop Log Reporting".to_string();
                 *INPUT_STATE_KB.data_logging_state.lock().unwrap() = true;
-                *INPUT_STATE_KB.data_logging_action.lock().unwrap() = DataT::StopLogReporting;
+                *INPUT_STATE_KB.data_logging_action.lock().unwrap() = CommandT::StopLogReporting;
             }
 
             Key::Char('r') => reset_keyboard_values(),
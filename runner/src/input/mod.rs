@@ -4,7 +4,7 @@ use std::cmp;
 pub mod joystick;
 pub mod keyboard;
 
-use common::protocol::DataT;
+use common::protocol::CommandT;
 use common::DroneMode;
 // Other crates
 use joystick::INPUT_STATE_JS;
@@ -23,7 +23,7 @@ pub struct InputState {
     yaw_p: Arc<Mutex<i32>>,
     roll_pitch_p1: Arc<Mutex<i32>>,
     roll_pitch_p2: Arc<Mutex<i32>>,
-    pub(crate) data_logging_action: Arc<Mutex<DataT>>,
+    pub(crate) data_logging_action: Arc<Mutex<CommandT>>,
     pub(crate) data_logging_state: Arc<Mutex<bool>>,
     pub(crate) is_new_mode_request_received: Arc<Mutex<bool>>,
     pub(crate) is_pid_updated: Arc<Mutex<bool>>,
@@ -0,0 +1,777 @@
+//! End-to-end tests of the protocol over an in-memory link (`io::duplex`):
+//! `Logic` on one end, the drone end of the protocol (`drone_link`) on the
+//! other, exchanging control, mode changes, gain updates and log reports
+//! through lossy, corrupting and slow links.
+//!
+//! Only the modes of the drone are stood in for, they need its hardware.
+
+use std::future::Future;
+use std::pin::pin;
+use std::task::{Context, Poll, Waker};
+use std::time::Duration;
+
+use common::drone_link::{
+    self, safe_mode_transition, DroneHost, DroneLink, DRONE_OUT_SLOTS, DRONE_PIPE_SIZE,
+};
+use common::param::ParamDef;
+use common::protocol::MotorsDT;
+use common::telemetry::rates::{heartbeat_period_max, StreamRates};
+use common::telemetry::{SensorEncoder, SensorSample};
+use common::utility::loop_stats::OverrunReporter;
+
+use crate::logic::*;
+
+const LINK_CAP: usize = 4096;
+
+type Link = DuplexEnd<LINK_CAP>;
+
+const GAIN_ID: ParamId = 1;
+
+// longer than the retransmissions of the reliable channels can take
+const MAX_TICKS: u32 = 500;
+
+// the sticks of the ground station, a mode change needs them neutral
+const NEUTRAL: ControlDT = ControlDT {
+    lift: 0,
+    roll: 1024,
+    pitch: 1024,
+    yaw: 1024,
+};
+
+// the sticks count as centered this close to the middle
+const DEAD_MARGIN: u16 = 30;
+
+const FRAME_ID: ParamId = 2;
+const HB_PERIOD_ID: ParamId = 3;
+
+// ticks per second of the stand in, as on the drone
+const TICK_FREQUENCY: u32 = 100;
+
+// missed deadlines are reported at most once per health period, in ticks
+const HEALTH_PERIOD: u32 = 100;
+
+static PARAMS: [ParamDef<Drone>; 3] = [
+    ParamDef {
+        id: GAIN_ID,
+        name: "gain",
+        default: ParamValue::Fixed(I16F16::from_bits(5 << 16)),
+        min: ParamValue::Fixed(I16F16::ZERO),
+        max: ParamValue::Fixed(I16F16::from_bits(100 << 16)),
+        safe_only: false,
+        get: |drone| ParamValue::Fixed(drone.gain),
+        set: |drone, value| drone.gain = value.as_fixed().unwrap_or_default(),
+    },
+    ParamDef {
+        id: FRAME_ID,
+        name: "frame",
+        default: ParamValue::U32(0),
+        min: ParamValue::U32(0),
+        max: ParamValue::U32(1),
+        safe_only: true,
+        get: |drone| ParamValue::U32(drone.frame),
+        set: |drone, value| drone.frame = value.as_u32().unwrap_or_default(),
+    },
+    ParamDef {
+        id: HB_PERIOD_ID,
+        name: "hb_period",
+        default: ParamValue::U32(40),
+        min: ParamValue::U32(1),
+        max: ParamValue::U32(heartbeat_period_max(TICK_FREQUENCY)),
+        safe_only: false,
+        get: |drone| ParamValue::U32(drone.link.stream_period(StreamT::Heartbeat)),
+        set: |drone, value| {
+            drone
+                .link
+                .set_stream_period(StreamT::Heartbeat, value.as_u32().unwrap_or_default())
+        },
+    },
+];
+
+fn lossy(seed: u32) -> FaultConfig {
+    FaultConfig {
+        loss: 15,
+        delay: 2,
+        seed,
+        ..FaultConfig::none()
+    }
+}
+
+fn corrupting(seed: u32) -> FaultConfig {
+    FaultConfig {
+        corruption: 15,
+        delay: 1,
+        seed,
+        ..FaultConfig::none()
+    }
+}
+
+fn slow() -> FaultConfig {
+    FaultConfig {
+        delay: 5,
+        ..FaultConfig::none()
+    }
+}
+
+// the drone end of the protocol, with modes that act like the ones of
+// `DroneState` without moving any motor
+struct Drone {
+    link: DroneLink<Link>,
+    mode: DroneMode,
+    controls: Vec<ControlDT>, // as received, the last one applies
+    gain: I16F16,
+    frame: u32,
+    mode_changes: u32, // executed, a retransmitted request must count once
+    session: u64,
+
+    // how long each tick takes, in microseconds, and the ticks so far
+    busy: u32,
+    ticks: u32,
+    overruns: OverrunReporter,
+
+    // the log, sent while reporting
+    log: Vec<SensorSample>,
+    report: Option<usize>, // next entry of the log to send
+    encoder: SensorEncoder,
+}
+
+impl DroneHost for Drone {
+    type Transport = Link;
+
+    fn link(&mut self) -> &mut DroneLink<Link> {
+        &mut self.link
+    }
+
+    fn params(&self) -> &'static [ParamDef<Self>] {
+        &PARAMS
+    }
+
+    fn mode(&self) -> DroneMode {
+        self.mode
+    }
+
+    fn now(&self) -> Timestamp {
+        0
+    }
+
+    fn new_session(&mut self) -> u64 {
+        self.session += 1;
+        self.session
+    }
+}
+
+impl Drone {
+    fn new(end: Link) -> Self {
+        let mut pipe: ComT<DRONE_PIPE_SIZE, Link> = ComT::with_transport(end);
+        pipe.enable_reliable(ReliableConfig {
+            retransmit_ticks: 4,
+            max_retries: 20,
+            dedup_ticks: 400,
+        });
+        if let Some(key) = PRESHARED_KEY {
+            pipe.require_auth(key);
+        }
+
+        let link: DroneLink<Link> = DroneLink::new(
+            pipe,
+            OutQueue::<DRONE_OUT_SLOTS>::new(LINK_CAP as u32, LINK_CAP as u32),
+            StreamRates::new(TICK_FREQUENCY, 0),
+        );
+
+        let mut drone: Self = Self {
+            link,
+            mode: DroneMode::Safe,
+            controls: Vec::new(),
+            gain: I16F16::ZERO,
+            frame: 0,
+            mode_changes: 0,
+            session: NO_SESSION,
+            busy: 0,
+            ticks: 0,
+            overruns: OverrunReporter::new(1_000_000 / TICK_FREQUENCY),
+            log: Vec::new(),
+            report: None,
+            encoder: SensorEncoder::new(false),
+        };
+        for param in PARAMS.iter() {
+            param.reset(&mut drone);
+        }
+
+        drone
+    }
+
+    // one tick of the control loop, in the order of `DroneState::tick`
+    fn tick(&mut self) {
+        self.link.pipe_mut().transport_mut().tick();
+        self.link.tick();
+
+        loop {
+            match drone_link::read_data(self) {
+                DataT::Control(control) => self.controls.push(control),
+                DataT::Mode(mode) => self.change_mode(mode),
+                DataT::StartLogReporting => self.start_report(),
+                DataT::Empty => break,
+                _ => {}
+            }
+        }
+        drone_link::answer_request(self);
+
+        if let Some(control) = self.controls.last().copied() {
+            self.link.send_data(DataT::MotorsState(MotorsDT {
+                time: 0,
+                ae1: control.lift,
+                ae2: control.roll,
+                ae3: control.pitch,
+                ae4: control.yaw,
+            }));
+        }
+        self.report_log();
+
+        drone_link::param_list_if_enabled(self);
+        self.link.flush();
+
+        // like `DroneState::record_loop`, after the tick
+        self.ticks += 1;
+        if let Some((missed, worst)) = self.overruns.record(self.ticks, self.busy, HEALTH_PERIOD) {
+            let overrun: EventDT = EventDT::new(EventCodeT::DeadlineOverrun, [missed, worst]);
+            self.link.send_data(DataT::Event(overrun));
+        }
+    }
+
+    // safe mode checks the transition, the others only go back to safety
+    fn change_mode(&mut self, requested: DroneMode) {
+        let next: DroneMode = match self.mode {
+            DroneMode::Safe => match safe_mode_transition(
+                requested,
+                self.link.is_handshake_ok(),
+                self.controls.last().copied().unwrap_or(NEUTRAL),
+                DEAD_MARGIN,
+                true,
+            ) {
+                Ok(next) => next,
+                Err(warning) => {
+                    self.link.send_data(DataT::Warning(warning));
+                    return;
+                }
+            },
+            _ if matches!(requested, DroneMode::Safe | DroneMode::Panic) => requested,
+            _ => return,
+        };
+
+        if next != self.mode {
+            self.mode = next;
+            self.mode_changes += 1;
+            drone_link::mode_changed(self, next);
+        }
+    }
+
+    // like `DroneState::log_report_start`, only on the ground
+    fn start_report(&mut self) {
+        if self.mode == DroneMode::Safe {
+            self.report = Some(0);
+            self.link.set_request_result(ResultT::Ok);
+        }
+    }
+
+    // one chunk of the log per tick, then the end of the report
+    fn report_log(&mut self) {
+        let mut next: usize = match self.report {
+            Some(next) => next,
+            None => return,
+        };
+        if self.link.room(Priority::Log) < 2 {
+            return;
+        }
+
+        while next < self.log.len() && self.encoder.push(&self.log[next]) {
+            next += 1;
+        }
+        if let Some(chunk) = self.encoder.take() {
+            self.link.send_data(DataT::SensorStream(chunk));
+        }
+
+        if next == self.log.len() {
+            self.link.send_data(DataT::StopLogReporting);
+            self.report = None;
+        } else {
+            self.report = Some(next);
+        }
+    }
+}
+
+// `Logic` on the other end of the drone, its reader thread played by `tick`
+struct Runner {
+    logic: Logic<Link>,
+    reader: Link,
+    gui: GuiParams,
+    iter_count: u32,
+
+    // what the drone sent, as `Logic` got it
+    received: Vec<DataT>,
+}
+
+impl Runner {
+    fn new(end: Link) -> Self {
+        let reader: Link = end.clone();
+
+        Self {
+            logic: Logic::with_pipe(AsyncComT::with_link(end), None),
+            reader,
+            gui: GuiParams::default(),
+            iter_count: 1,
+            received: Vec::new(),
+        }
+    }
+
+    // the messages that arrived, then a tick of `Logic::run`
+    fn tick(&mut self) {
+        self.receive();
+        self.tick_logic();
+    }
+
+    fn receive(&mut self) {
+        self.reader.tick();
+        let mut buf: [u8; 64] = [0; 64];
+        loop {
+            let read: usize = self.reader.read(&mut buf);
+            if read == 0 {
+                break;
+            }
+            self.logic.pipe.deliver(&buf[..read]);
+        }
+
+        loop {
+            match self.logic.pipe.read_data::<BUF_CAP>() {
+                Ok(data) => {
+                    self.received.push(data.clone());
+                    self.logic.handle_message(data, self.gui.clone());
+                }
+                Err(ComErr::Empty) => break,
+                Err(e) => self.logic.handle_read_error(e),
+            }
+        }
+    }
+
+    fn tick_logic(&mut self) {
+        block_on(self.logic.tick(
+            self.iter_count,
+            &Duration::from_millis(10),
+            self.gui.clone(),
+        ));
+        self.iter_count = self.iter_count.wrapping_add(1);
+    }
+
+    // the ground station holds the sticks at `control`
+    fn hold(&mut self, control: ControlDT) {
+        self.logic.gcs_control = Some((control, self.iter_count));
+    }
+
+    fn request(&mut self, command: CommandT) -> RequestId {
+        block_on(self.logic.send_request(command)).unwrap()
+    }
+
+    fn samples(&self) -> Vec<SensorSample> {
+        let mut samples: Vec<SensorSample> = Vec::new();
+        for data in &self.received {
+            if let DataT::SensorStream(chunk) = data {
+                samples.extend(SensorDecoder::new(chunk).map(|sample| sample.unwrap()));
+            }
+        }
+        samples
+    }
+
+    fn report_done(&self) -> bool {
+        self.received
+            .iter()
+            .any(|data| matches!(data, DataT::StopLogReporting))
+    }
+}
+
+// nothing waits on the in-memory link, a busy one only makes `AsyncComT::send`
+// yield a few times before giving up
+fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = pin!(future);
+    let mut cx: Context<'_> = Context::from_waker(Waker::noop());
+
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+    }
+}
+
+fn link(runner_to_drone: FaultConfig, drone_to_runner: FaultConfig) -> (Runner, Drone) {
+    let (runner, drone) = duplex::<LINK_CAP>(runner_to_drone, drone_to_runner);
+    (Runner::new(runner), Drone::new(drone))
+}
+
+fn step(runner: &mut Runner, drone: &mut Drone) {
+    runner.hold(NEUTRAL);
+    runner.tick();
+    drone.tick();
+}
+
+// ticks both ends until the drone accepted the runner
+fn handshake(runner: &mut Runner, drone: &mut Drone) {
+    runner.logic.start_handshake();
+    for _ in 0..MAX_TICKS {
+        step(runner, drone);
+        if runner.logic.handshake == HandshakeState::Done && drone.link.is_handshake_ok() {
+            return;
+        }
+    }
+
+    panic!("no handshake");
+}
+
+// ticks both ends until the request `id` is answered, before `Logic` reports
+// and forgets it
+fn wait_for(runner: &mut Runner, drone: &mut Drone, id: RequestId) -> Outcome {
+    for _ in 0..MAX_TICKS {
+        // the sticks stay where they are
+        if let Some((control, _)) = runner.logic.gcs_control {
+            runner.hold(control);
+        }
+        drone.tick();
+        runner.receive();
+
+        match runner.logic.requests.poll(id) {
+            Some(Outcome::Pending) => {}
+            Some(outcome) => return outcome,
+            None => panic!("request {} timed out or is not tracked", id),
+        }
+        runner.tick_logic();
+    }
+
+    panic!("request {} still pending", id);
+}
+
+fn log(len: u32) -> Vec<SensorSample> {
+    (0..len)
+        .map(|i| SensorSample {
+            time: 1_000 + 10 * i,
+            gyro_x: i as i16,
+            accel_z: -(i as i16) * 3,
+            yaw: (i * 7) as i16,
+            ..SensorSample::default()
+        })
+        .collect()
+}
+
+#[test]
+fn test_control_both_ways() {
+    let (mut runner, mut drone) = link(slow(), slow());
+    handshake(&mut runner, &mut drone);
+    let control: ControlDT = ControlDT {
+        lift: 600,
+        roll: 1024,
+        pitch: 1000,
+        yaw: 1100,
+    };
+
+    for _ in 0..20 {
+        runner.hold(control);
+        runner.tick();
+        drone.tick();
+    }
+
+    assert_eq!(drone.controls.last(), Some(&control));
+    assert_eq!(*runner.gui.motor_1_value.lock().unwrap(), 600);
+    assert_eq!(*runner.gui.motor_2_value.lock().unwrap(), 1024);
+    assert_eq!(*runner.gui.motor_3_value.lock().unwrap(), 1000);
+    assert_eq!(*runner.gui.motor_4_value.lock().unwrap(), 1100);
+}
+
+#[test]
+fn test_ground_station_control_across_the_tick_wrap() {
+    let (mut runner, mut drone) = link(slow(), slow());
+    handshake(&mut runner, &mut drone);
+    let control: ControlDT = ControlDT {
+        lift: 700,
+        ..NEUTRAL
+    };
+
+    // held once, shortly before the tick counter wraps
+    runner.iter_count = u32::MAX - 2;
+    runner.hold(control);
+    for _ in 0..12 {
+        runner.tick();
+        drone.tick();
+    }
+
+    assert_eq!(runner.iter_count, 9);
+    assert_eq!(drone.controls.last(), Some(&control));
+}
+
+#[test]
+fn test_mode_change_over_lossy_link() {
+    for seed in 1..=5 {
+        let (mut runner, mut drone) = link(lossy(seed), lossy(seed + 100));
+        handshake(&mut runner, &mut drone);
+
+        let id: RequestId = runner.request(CommandT::Mode(DroneMode::Manual));
+        assert_eq!(
+            wait_for(&mut runner, &mut drone, id),
+            Outcome::Answered(ResultT::Ok)
+        );
+        assert_eq!(drone.mode, DroneMode::Manual);
+
+        let id: RequestId = runner.request(CommandT::Mode(DroneMode::Safe));
+        assert_eq!(
+            wait_for(&mut runner, &mut drone, id),
+            Outcome::Answered(ResultT::Ok)
+        );
+
+        // retransmissions are not executed twice
+        assert_eq!(drone.mode_changes, 2, "seed {}", seed);
+    }
+}
+
+// asks for manual mode with the sticks held at `control`
+fn ask_manual(runner: &mut Runner, drone: &mut Drone, control: ControlDT) -> Outcome {
+    for _ in 0..20 {
+        runner.hold(control);
+        runner.tick();
+        drone.tick();
+    }
+
+    let id: RequestId = runner.request(CommandT::Mode(DroneMode::Manual));
+    wait_for(runner, drone, id)
+}
+
+#[test]
+fn test_mode_change_needs_neutral_sticks() {
+    let (mut runner, mut drone) = link(slow(), slow());
+    handshake(&mut runner, &mut drone);
+    let rejected: Outcome = Outcome::Answered(ResultT::Rejected(WarningDT::ControlNotNeutral));
+
+    let throttle: ControlDT = ControlDT {
+        lift: 300,
+        ..NEUTRAL
+    };
+    assert_eq!(ask_manual(&mut runner, &mut drone, throttle), rejected);
+    let roll: ControlDT = ControlDT {
+        roll: 1024 + DEAD_MARGIN + 1,
+        ..NEUTRAL
+    };
+    assert_eq!(ask_manual(&mut runner, &mut drone, roll), rejected);
+    assert_eq!(drone.mode, DroneMode::Safe);
+
+    // within the dead margin of the drone
+    let roll: ControlDT = ControlDT {
+        roll: 1024 + DEAD_MARGIN,
+        ..NEUTRAL
+    };
+    assert_eq!(
+        ask_manual(&mut runner, &mut drone, roll),
+        Outcome::Answered(ResultT::Ok)
+    );
+    assert_eq!(drone.mode, DroneMode::Manual);
+}
+
+#[test]
+fn test_deadline_overruns_are_reported_once_per_health_period() {
+    let (mut runner, mut drone) = link(slow(), slow());
+    handshake(&mut runner, &mut drone);
+
+    // every tick takes longer than its period
+    drone.busy = 12_000;
+    for _ in 0..250 {
+        step(&mut runner, &mut drone);
+    }
+    drone.busy = 2_000;
+    for _ in 0..(HEALTH_PERIOD + 20) {
+        step(&mut runner, &mut drone);
+    }
+
+    // the first miss at once, then a count per health period, the last one
+    // once the loop was on time again
+    let missed: Vec<u32> = runner
+        .received
+        .iter()
+        .filter_map(|data| match data {
+            DataT::Event(event) if event.code == EventCodeT::DeadlineOverrun => Some(event.args[0]),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(missed, vec![1, 100, 100, 49]);
+
+    let events: String = runner.gui.events.lock().unwrap().clone();
+    assert!(
+        events.starts_with("last: Timing: 49 tick(s) missed their deadline, worst took 12000 us")
+    );
+    assert!(events.contains("Timing/DeadlineOverrun (error): 4"));
+}
+
+#[test]
+fn test_gain_update_over_corrupting_link() {
+    let (mut runner, mut drone) = link(corrupting(3), corrupting(4));
+    handshake(&mut runner, &mut drone);
+    let gain: I16F16 = I16F16::from_num(7.25);
+
+    // enough traffic for the corruption to hit some frames, each control is
+    // sent with a throttle of its own
+    let mut sent: Vec<ControlDT> = Vec::new();
+    for lift in 1..=200 {
+        let control: ControlDT = ControlDT { lift, ..NEUTRAL };
+        runner.hold(control);
+        sent.push(control);
+        runner.tick();
+        drone.tick();
+    }
+
+    let id: RequestId = runner.request(CommandT::ParamSet(ParamValueDT {
+        id: GAIN_ID,
+        value: ParamValue::Fixed(gain),
+    }));
+    assert_eq!(
+        wait_for(&mut runner, &mut drone, id),
+        Outcome::Answered(ResultT::Ok)
+    );
+    assert_eq!(drone.gain, gain);
+
+    // frames were corrupted on the way and dropped by the drone, none of
+    // them was applied: every control it got is one the runner sent
+    assert!(runner.reader.sent_faults().corrupted > 0);
+    assert!(drone.link.pipe().link_stats().crc_errors > 0);
+    assert!(!drone.controls.is_empty());
+    assert!(drone.controls.iter().all(|control| sent.contains(control)));
+}
+
+#[test]
+fn test_gain_out_of_range_is_rejected() {
+    let (mut runner, mut drone) = link(slow(), slow());
+    handshake(&mut runner, &mut drone);
+
+    let id: RequestId = runner.request(CommandT::ParamSet(ParamValueDT {
+        id: GAIN_ID,
+        value: ParamValue::Fixed(I16F16::from_num(1000)),
+    }));
+    assert_eq!(
+        wait_for(&mut runner, &mut drone, id),
+        Outcome::Answered(ResultT::Rejected(WarningDT::ParamRejected))
+    );
+    assert_eq!(drone.gain, I16F16::from_num(5));
+}
+
+#[test]
+fn test_heartbeat_too_slow_is_rejected() {
+    let (mut runner, mut drone) = link(slow(), slow());
+    handshake(&mut runner, &mut drone);
+    let max: u32 = heartbeat_period_max(TICK_FREQUENCY);
+
+    // the runner would take the link for lost between two heartbeats
+    let id: RequestId = runner.request(CommandT::ParamSet(ParamValueDT {
+        id: HB_PERIOD_ID,
+        value: ParamValue::U32(max + 1),
+    }));
+    assert_eq!(
+        wait_for(&mut runner, &mut drone, id),
+        Outcome::Answered(ResultT::Rejected(WarningDT::ParamRejected))
+    );
+    assert_eq!(drone.link.stream_period(StreamT::Heartbeat), 40);
+
+    let id: RequestId = runner.request(CommandT::ParamSet(ParamValueDT {
+        id: HB_PERIOD_ID,
+        value: ParamValue::U32(max),
+    }));
+    assert_eq!(
+        wait_for(&mut runner, &mut drone, id),
+        Outcome::Answered(ResultT::Ok)
+    );
+    assert_eq!(drone.link.stream_period(StreamT::Heartbeat), max);
+}
+
+#[test]
+fn test_frame_changes_only_in_safe_mode() {
+    let (mut runner, mut drone) = link(slow(), slow());
+    handshake(&mut runner, &mut drone);
+    let x_frame: CommandT = CommandT::ParamSet(ParamValueDT {
+        id: FRAME_ID,
+        value: ParamValue::U32(1),
+    });
+
+    let id: RequestId = runner.request(CommandT::Mode(DroneMode::Manual));
+    assert_eq!(
+        wait_for(&mut runner, &mut drone, id),
+        Outcome::Answered(ResultT::Ok)
+    );
+    let id: RequestId = runner.request(x_frame);
+    assert_eq!(
+        wait_for(&mut runner, &mut drone, id),
+        Outcome::Answered(ResultT::InvalidInMode(DroneMode::Manual))
+    );
+    assert_eq!(drone.frame, 0);
+
+    let id: RequestId = runner.request(CommandT::Mode(DroneMode::Safe));
+    assert_eq!(
+        wait_for(&mut runner, &mut drone, id),
+        Outcome::Answered(ResultT::Ok)
+    );
+    let id: RequestId = runner.request(x_frame);
+    assert_eq!(
+        wait_for(&mut runner, &mut drone, id),
+        Outcome::Answered(ResultT::Ok)
+    );
+    assert_eq!(drone.frame, 1);
+}
+
+#[test]
+fn test_log_report_sequence() {
+    let (mut runner, mut drone) = link(slow(), slow());
+    handshake(&mut runner, &mut drone);
+    drone.log = log(200);
+
+    // not while flying
+    drone.mode = DroneMode::Manual;
+    let id: RequestId = runner.request(CommandT::StartLogReporting);
+    assert_eq!(
+        wait_for(&mut runner, &mut drone, id),
+        Outcome::Answered(ResultT::InvalidInMode(DroneMode::Manual))
+    );
+
+    drone.mode = DroneMode::Safe;
+    let id: RequestId = runner.request(CommandT::StartLogReporting);
+    assert_eq!(
+        wait_for(&mut runner, &mut drone, id),
+        Outcome::Answered(ResultT::Ok)
+    );
+
+    for _ in 0..MAX_TICKS {
+        if runner.report_done() {
+            break;
+        }
+        step(&mut runner, &mut drone);
+    }
+
+    assert!(runner.report_done());
+    assert_eq!(runner.samples(), drone.log);
+}
+
+#[test]
+fn test_log_report_over_lossy_link() {
+    let (mut runner, mut drone) = link(lossy(11), lossy(12));
+    handshake(&mut runner, &mut drone);
+    drone.log = log(200);
+
+    let id: RequestId = runner.request(CommandT::StartLogReporting);
+    assert_eq!(
+        wait_for(&mut runner, &mut drone, id),
+        Outcome::Answered(ResultT::Ok)
+    );
+
+    for _ in 0..MAX_TICKS {
+        if runner.report_done() {
+            break;
+        }
+        step(&mut runner, &mut drone);
+    }
+
+    // the end of the report is critical, the chunks are not: some are lost
+    // but what arrives is intact and in order
+    let samples: Vec<SensorSample> = runner.samples();
+    assert!(runner.report_done());
+    assert!(!samples.is_empty());
+    assert!(samples.len() < drone.log.len());
+    let mut sent = drone.log.iter();
+    assert!(samples.iter().all(|r| sent.any(|s| s == r)));
+}
@@ -1,39 +1,180 @@
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
 use fixed::types::I16F16;
 
 use common::{
     io::*,
-    protocol::{ControlDT, DataT, UpdateP1P2DT, UpdatePDT, WarningDT},
+    protocol::{
+        BatteryT, CommandT, ControlDT, DataT, EventCodeT, EventDT, HandshakeDT, HealthDT,
+        HeartbeatDT, LargeDataT, LinkStatsDT, ParamId, ParamInfoDT, ParamType, ParamValue,
+        ParamValueDT, RequestId, ResultT, SensorLogDT, StreamRateDT, StreamRatesDT, StreamT,
+        TimeSyncDT, Timestamp, WarningDT, FAULT_BATTERY, FAULT_HANDSHAKE, FAULT_LINK,
+        FAULT_LOG_FULL, LARGE_SERIALIZED_CAP,
+    },
+    telemetry::{rates::HEARTBEAT_TIMEOUT_MS, SensorDecoder},
+    utility::clock::ClockSync,
     DroneMode,
 };
 
-use crate::gui::GuiParams;
+#[cfg(test)]
+mod loopback;
+
+use crate::async_com::{race, AsyncComT, Interval};
+use crate::gui::{GuiParams, ParamRequest, StreamRequest};
 use crate::input::joystick::INPUT_STATE_JS;
 use crate::input::keyboard::INPUT_STATE_KB;
+use crate::mavlink::translate::GcsCommand;
+use crate::mavlink::MavlinkBridge;
+use crate::serial_wrapper::SerialTransport;
 use crate::{
     input::{self, get_pitch, get_roll, get_throttle, get_yaw},
-    utils::constants::{RUNNER_PERIOD_COMMAND, RUNNER_PERIOD_KEEP_ALIVE},
+    utils::constants::{
+        RUNNER_FRAGMENT_SLOTS, RUNNER_FRAGMENT_TIMEOUT_TICKS, RUNNER_MAVLINK_CONTROL_TIMEOUT_TICKS,
+        RUNNER_PERIOD_COMMAND, RUNNER_PERIOD_HANDSHAKE, RUNNER_PERIOD_KEEP_ALIVE,
+        RUNNER_PERIOD_LINK_STATS, RUNNER_PERIOD_MAVLINK_HEARTBEAT, RUNNER_PERIOD_TIME_SYNC,
+        RUNNER_RELIABLE_MAX_RETRIES, RUNNER_RELIABLE_RETRANSMIT_TICKS, RUNNER_REQUEST_SLOTS,
+        RUNNER_REQUEST_TIMEOUT_TICKS,
+    },
 };
 
 const BUF_CAP: usize = 256;
 
-pub struct Logic {
-    pipe: ComT<BUF_CAP>,
+/// Progress of the protocol handshake with the drone.
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum HandshakeState {
+    Pending,               // waiting for the answer of the drone
+    Done,                  // both sides use the same protocol definition
+    Mismatch(HandshakeDT), // the drone was built from another definition
+}
+
+// what woke up `Logic::run`
+enum WakeT {
+    Tick(u32), // ticks elapsed
+    Received(Result<DataT, ComErr>),
+}
+
+pub struct Logic<T: Transport = SerialTransport> {
+    pipe: AsyncComT<BUF_CAP, T>,
 
     mode: DroneMode,
+    heartbeat: Option<HeartbeatDT>, // last status reported by the drone
+    heartbeat_at: Option<Instant>,  // when it arrived, none once the link is lost
+    events: BTreeMap<EventCodeT, u32>, // how many of each the drone reported
+
+    // no command is sent to the drone until it is `Done`
+    handshake: HandshakeState,
+
+    // commands sent to the drone, waiting for its answer
+    requests: Requests<RUNNER_REQUEST_SLOTS>,
+    // safety mode change that could not be sent yet, retried every tick
+    safety_command: Option<CommandT>,
+
+    // large messages from the drone
+    reassembler: Reassembler<LARGE_SERIALIZED_CAP, RUNNER_FRAGMENT_SLOTS>,
+
+    // parameters of the drone, as listed by it
+    param_info: BTreeMap<ParamId, ParamInfoDT>,
+    param_values: BTreeMap<ParamId, ParamValue>,
+
+    // maps the drone timestamps to our clock, which starts with `started`
+    clock: ClockSync,
+    started: Instant,
+
+    // ground station, if enabled, and the last manual control it sent with
+    // the tick it arrived at
+    mavlink: Option<MavlinkBridge>,
+    gcs_control: Option<(ControlDT, u32)>,
 }
 
 impl Logic {
-    pub fn default() -> Self {
+    pub fn new(serial: SerialTransport, mavlink: Option<MavlinkBridge>) -> Self {
+        Self::with_pipe(AsyncComT::new(serial), mavlink)
+    }
+}
+
+impl<T: Transport> Logic<T> {
+    // talks to the drone through `pipe`, whatever its link is
+    fn with_pipe(mut pipe: AsyncComT<BUF_CAP, T>, mavlink: Option<MavlinkBridge>) -> Self {
+        pipe.enable_reliable(ReliableConfig {
+            retransmit_ticks: RUNNER_RELIABLE_RETRANSMIT_TICKS,
+            max_retries: RUNNER_RELIABLE_MAX_RETRIES,
+            // remember received frames for longer than the drone retries them
+            dedup_ticks: 4 * RUNNER_RELIABLE_RETRANSMIT_TICKS * RUNNER_RELIABLE_MAX_RETRIES as u32,
+        });
+
+        if let Some(key) = PRESHARED_KEY {
+            // bound to the session of the drone once it answered the handshake
+            pipe.sign_with(key);
+            log::info!("Frames to the drone are authenticated");
+        }
+
         Self {
-            pipe: ComT::<BUF_CAP>::new(
-                crate::serial_wrapper::receive_bytes,
-                crate::serial_wrapper::send_bytes,
-            ),
+            pipe,
             mode: DroneMode::Safe,
+            heartbeat: None,
+            heartbeat_at: None,
+            events: BTreeMap::new(),
+            handshake: HandshakeState::Pending,
+            requests: Requests::new(RUNNER_REQUEST_TIMEOUT_TICKS),
+            safety_command: None,
+            reassembler: Reassembler::new(RUNNER_FRAGMENT_TIMEOUT_TICKS),
+            param_info: BTreeMap::new(),
+            param_values: BTreeMap::new(),
+            clock: ClockSync::new(),
+            started: Instant::now(),
+            mavlink,
+            gcs_control: None,
+        }
+    }
+
+    /// Sends our protocol version and schema hash, the drone answers with its
+    /// own. It is repeated from `tick` until the drone answers.
+    pub fn start_handshake(&mut self) {
+        match self
+            .pipe
+            .send_data::<BUF_CAP>(DataT::Handshake(HandshakeDT::local()))
+        {
+            Ok(_) => {}
+            Err(e) => log::error!("[ERROR]: sending handshake {:#?}", e),
         }
     }
 
-    pub fn tick(
+    /// Runs the logic on the executor: `tick` every `period` and the messages
+    /// of the drone as soon as they arrive.
+    pub async fn run(&mut self, period: Duration, gui_params_modifier_3: GuiParams) {
+        let mut interval: Interval = Interval::new(period);
+        let mut iter_count: u32 = 1;
+
+        // make sure the drone speaks the same protocol before commanding it
+        self.start_handshake();
+
+        loop {
+            let wake: WakeT = race(async { WakeT::Tick(interval.tick().await) }, async {
+                WakeT::Received(self.pipe.recv::<BUF_CAP>().await)
+            })
+            .await;
+
+            match wake {
+                WakeT::Tick(elapsed) => {
+                    if elapsed > 1 {
+                        log::error!("Deadline exceeded by {} tick(s)", elapsed - 1);
+                    }
+
+                    self.tick(iter_count, &period, gui_params_modifier_3.clone())
+                        .await;
+                    // the missed ticks count, the periodic tasks keep their pace
+                    iter_count = iter_count.wrapping_add(elapsed);
+                }
+                WakeT::Received(Ok(data)) => {
+                    self.handle_message(data, gui_params_modifier_3.clone())
+                }
+                WakeT::Received(Err(e)) => self.handle_read_error(e),
+            }
+        }
+    }
+
+    pub async fn tick(
         &mut self,
         iter_count: u32,
         delta: &std::time::Duration,
@@ -41,47 +182,121 @@ impl Logic {
     ) {
         // for now only simple functionality to send over control commands,
         // mode changes and keep alive messages
-        self.perform_periodic_tasks(iter_count, delta);
+        self.perform_periodic_tasks(iter_count, delta).await;
+        self.handle_param_request(&gui_params_modifier_3).await;
+        self.handle_stream_request(&gui_params_modifier_3);
+        self.handle_gcs_commands(iter_count).await;
+        self.report_requests(&gui_params_modifier_3);
+        self.check_heartbeat(&gui_params_modifier_3);
+
+        if iter_count % RUNNER_PERIOD_LINK_STATS == 0 {
+            *gui_params_modifier_3.link_stats_runner.lock().unwrap() =
+                format_link_stats(&self.pipe.link_stats());
+        }
+    }
 
-        self.check_drone_coms(gui_params_modifier_3);
+    // the drone sends heartbeats well within `HEARTBEAT_TIMEOUT_MS`, without
+    // one for longer the link is taken for lost (reported once)
+    fn check_heartbeat(&mut self, gui_params_modifier_3: &GuiParams) {
+        let Some(at) = self.heartbeat_at else {
+            return;
+        };
+
+        if at.elapsed() > Duration::from_millis(HEARTBEAT_TIMEOUT_MS as u64) {
+            log::error!(
+                "[ERROR]: no heartbeat from the drone for {} ms, link lost",
+                HEARTBEAT_TIMEOUT_MS
+            );
+            self.heartbeat_at = None;
+            *gui_params_modifier_3.heartbeat.lock().unwrap() = String::from("link lost");
+        }
     }
 
-    fn perform_periodic_tasks(&mut self, iter_count: u32, _delta: &std::time::Duration) {
-        if iter_count % RUNNER_PERIOD_COMMAND == 0 {
-            // TODO: do error handling
+    async fn perform_periodic_tasks(&mut self, iter_count: u32, _delta: &std::time::Duration) {
+        let given_up: usize = self.pipe.tick_reliable::<BUF_CAP>();
+        if given_up > 0 {
+            log::error!(
+                "[ERROR]: {} critical message(s) were not acknowledged by the drone",
+                given_up
+            );
+        }
 
-            let control_data = DataT::Control(ControlDT {
-                lift: get_throttle(),
-                roll: get_roll(),
-                pitch: get_pitch(),
-                yaw: get_yaw(),
-            });
-            // log::debug!("Sending control package: {:#?}", control_data);
-            self.pipe.send_data::<BUF_CAP>(control_data).unwrap();
+        self.requests.tick();
+
+        if self.reassembler.tick() > 0 {
+            log::error!("[ERROR]: incomplete large message from the drone was dropped");
+        }
+
+        if self.handshake == HandshakeState::Pending && iter_count % RUNNER_PERIOD_HANDSHAKE == 0 {
+            self.start_handshake();
+        }
+
+        if iter_count % RUNNER_PERIOD_MAVLINK_HEARTBEAT == 0 {
+            if let Some(mavlink) = self.mavlink.as_mut() {
+                mavlink.heartbeat();
+            }
         }
 
         if iter_count % RUNNER_PERIOD_KEEP_ALIVE == 0 {
-            // TODO: do error handling
+            // a lost keep alive is covered by the next one
             match self.pipe.send_data::<BUF_CAP>(DataT::KeepAlive) {
                 Ok(_) => {}
                 Err(e) => log::debug!("[ERROR]: failed to send ACK with code: {:#?}", e),
             }
         }
 
+        if self.handshake != HandshakeState::Done {
+            self.refuse_commands();
+            return;
+        }
+
+        if let Some(command) = self.safety_command.take() {
+            self.send_safety(command).await;
+        }
+
+        if iter_count % RUNNER_PERIOD_TIME_SYNC == 0 {
+            let request: DataT = DataT::TimeSync(TimeSyncDT {
+                host_time: self.host_time(),
+                drone_time: 0,
+            });
+            match self.pipe.send_data::<BUF_CAP>(request) {
+                Ok(_) => {}
+                Err(e) => log::debug!("[ERROR]: failed to send time sync {:#?}", e),
+            }
+        }
+
+        if iter_count % RUNNER_PERIOD_COMMAND == 0 {
+            // the ground station takes over while it sends manual control
+            let control_data = match &self.gcs_control {
+                Some((control, since))
+                    if iter_count.wrapping_sub(*since) < RUNNER_MAVLINK_CONTROL_TIMEOUT_TICKS =>
+                {
+                    DataT::Control(*control)
+                }
+                _ => DataT::Control(ControlDT {
+                    lift: get_throttle(),
+                    roll: get_roll(),
+                    pitch: get_pitch(),
+                    yaw: get_yaw(),
+                }),
+            };
+            // log::debug!("Sending control package: {:#?}", control_data);
+            // the next control supersedes a lost one, a busy link must not
+            // stop the runner
+            match self.pipe.send::<BUF_CAP>(control_data).await {
+                Ok(_) => {}
+                Err(e) => log::debug!("[ERROR]: failed to send control {:#?}", e),
+            }
+        }
+
         if (*INPUT_STATE_KB.is_new_mode_request_received.lock().unwrap())
             || (*INPUT_STATE_JS.is_new_mode_request_received.lock().unwrap())
         {
             // perform mode change
             if let Some(req_mode) = input::get_mode_pressed() {
-                // TODO: do error handling
+                // `send_request` reports the failures and the outcome
                 log::debug!("Switch mode request: {:?}", req_mode);
-
-                match self.pipe.send_data::<BUF_CAP>(DataT::Mode(req_mode)) {
-                    Ok(_) => {}
-                    Err(e) => {
-                        log::error!("[ERROR]: sending mode change {:#?}", e);
-                    }
-                }
+                self.send_request(CommandT::Mode(req_mode)).await;
             }
             *INPUT_STATE_KB.is_new_mode_request_received.lock().unwrap() = false;
         }
@@ -90,27 +305,15 @@ impl Logic {
             // adjust P values if different from 0
             let yaw_p_trim = input::get_yaw_p();
             if yaw_p_trim != 0 {
-                match self.pipe.send_data::<BUF_CAP>(DataT::UpdateP(UpdatePDT {
-                    p: I16F16::from_num(yaw_p_trim),
-                })) {
-                    Ok(_) => {}
-                    Err(e) => {
-                        log::error!("[ERROR]: sending yaw p trim {:#?}", e);
-                    }
-                }
+                self.send_param("yaw_p", ParamValue::Fixed(I16F16::from_num(yaw_p_trim)))
+                    .await;
             }
             *INPUT_STATE_KB.is_pid_updated.lock().unwrap() = false;
         }
 
         if *INPUT_STATE_KB.data_logging_state.lock().unwrap() {
-            let logging_state: DataT = INPUT_STATE_KB.data_logging_action.lock().unwrap().clone();
-
-            match self.pipe.send_data::<BUF_CAP>(logging_state) {
-                Ok(_) => {}
-                Err(e) => {
-                    log::error!("[ERROR]: sending Logging state {:#?}", e);
-                }
-            }
+            let logging_state: CommandT = *INPUT_STATE_KB.data_logging_action.lock().unwrap();
+            self.send_request(logging_state).await;
 
             *INPUT_STATE_KB.data_logging_state.lock().unwrap() = false;
         }
@@ -119,61 +322,318 @@ impl Logic {
             let p1 = input::get_full_control_p1();
             let p2 = input::get_full_control_p2();
 
-            match self
-                .pipe
-                .send_data::<BUF_CAP>(DataT::UpdateP1P2(UpdateP1P2DT {
-                    p1: I16F16::from_num(p1),
-                    p2: I16F16::from_num(p2),
-                })) {
-                Ok(_) => {}
-                Err(e) => {
-                    log::error!("[ERROR]: sending full p trim {:#?}", e);
+            self.send_param("rp_p1", ParamValue::Fixed(I16F16::from_num(p1)))
+                .await;
+            self.send_param("rp_p2", ParamValue::Fixed(I16F16::from_num(p2)))
+                .await;
+            *INPUT_STATE_KB.is_full_pid_updated.lock().unwrap() = false;
+        }
+    }
+
+    /// Sends `command` to the drone, its outcome is reported by
+    /// `report_requests` once the drone answered or the request timed out.
+    ///
+    /// A safety mode change that can not be sent as a request is sent as a
+    /// plain mode message instead, see `send_safety`.
+    /// @return the id of the request, `None` if it could not be sent
+    async fn send_request(&mut self, command: CommandT) -> Option<RequestId> {
+        let (id, data) = match self.requests.start(command) {
+            Ok(request) => request,
+            Err(_) if command.is_safety() => {
+                log::warn!(
+                    "Too many commands in flight, {:?} sent without answer",
+                    command
+                );
+                self.send_safety(command).await;
+                return None;
+            }
+            Err(e) => {
+                log::error!(
+                    "[ERROR]: too many commands in flight, {:?} {:?}",
+                    command,
+                    e
+                );
+                return None;
+            }
+        };
+
+        match self.pipe.send::<BUF_CAP>(data).await {
+            Ok(_) => Some(id),
+            Err(e) => {
+                self.requests.cancel(id);
+                if command.is_safety() {
+                    self.send_safety(command).await;
+                } else {
+                    log::error!("[ERROR]: sending {:?} {:#?}", command, e);
                 }
+                None
+            }
+        }
+    }
+
+    // sends a safety mode change as a plain mode message, which needs no
+    // request slot, and keeps retrying it every tick until it went out
+    async fn send_safety(&mut self, command: CommandT) {
+        match self.pipe.send::<BUF_CAP>(command.to_data()).await {
+            Ok(_) => self.safety_command = None,
+            Err(e) => {
+                log::error!("[ERROR]: sending {:?} {:#?}, retrying", command, e);
+                self.safety_command = Some(command);
             }
-            *INPUT_STATE_KB.is_full_pid_updated.lock().unwrap() = false;
         }
     }
 
-    fn check_drone_coms(&mut self, gui_params_modifier_3: GuiParams) {
-        match self.pipe.read_data::<BUF_CAP>() {
-            Ok(data) => self.handle_message(data, gui_params_modifier_3),
+    // shows the outcome of the commands the drone answered or that timed out
+    fn report_requests(&mut self, gui_params_modifier_3: &GuiParams) {
+        while let Some((id, command, outcome)) = self.requests.take_finished() {
+            let text: String = format_outcome(command, &outcome);
 
-            Err(e) => self.handle_read_error(e),
+            match outcome {
+                Outcome::Answered(ResultT::Ok) => log::info!("Request {}: {}", id, text),
+                _ => log::warn!("Request {}: {}", id, text),
+            }
+            *gui_params_modifier_3.last_request.lock().unwrap() = text;
         }
     }
 
-    fn handle_message(&mut self, data: DataT, gui_params_modifier_3: GuiParams) {
-        match data {
-            DataT::Mode(mode) => {
-                log::info!("Drone mode is now: {:#?}", mode);
-                self.mode = mode;
-                match mode {
-                    DroneMode::Safe => {
-                        *gui_params_modifier_3.drone_mode.lock().unwrap() = "Safe".to_string();
-                    }
-                    DroneMode::Manual => {
-                        *gui_params_modifier_3.drone_mode.lock().unwrap() = "Manual".to_string();
+    // drops the commands requested before a successful handshake, so they are
+    // not sent unexpectedly later on
+    fn refuse_commands(&mut self) {
+        let mut refused: bool = false;
+
+        if (*INPUT_STATE_KB.is_new_mode_request_received.lock().unwrap())
+            || (*INPUT_STATE_JS.is_new_mode_request_received.lock().unwrap())
+        {
+            input::get_mode_pressed();
+            *INPUT_STATE_KB.is_new_mode_request_received.lock().unwrap() = false;
+            *INPUT_STATE_JS.is_new_mode_request_received.lock().unwrap() = false;
+            refused = true;
+        }
+
+        for flag in [
+            &INPUT_STATE_KB.is_pid_updated,
+            &INPUT_STATE_KB.is_full_pid_updated,
+            &INPUT_STATE_KB.data_logging_state,
+        ] {
+            let mut flag = flag.lock().unwrap();
+            refused |= *flag;
+            *flag = false;
+        }
+
+        if refused {
+            log::warn!(
+                "Refusing to send commands, handshake state: {:?}",
+                self.handshake
+            );
+        }
+    }
+
+    /// Asks the drone for the definitions and values of all its parameters.
+    fn request_params(&mut self) {
+        match self.pipe.send_data::<BUF_CAP>(DataT::ParamList) {
+            Ok(_) => {}
+            Err(e) => log::error!("[ERROR]: requesting the parameter list {:#?}", e),
+        }
+    }
+
+    fn find_param(&self, name: &str) -> Option<&ParamInfoDT> {
+        self.param_info.values().find(|info| info.name == name)
+    }
+
+    /// Changes the parameter called `name`, the drone answers with its new
+    /// value.
+    async fn send_param(&mut self, name: &str, value: ParamValue) {
+        let id: ParamId = match self.find_param(name) {
+            Some(info) => info.id,
+            None => {
+                log::error!("[ERROR]: the drone has no parameter {}", name);
+                return;
+            }
+        };
+
+        self.send_request(CommandT::ParamSet(ParamValueDT { id, value }))
+            .await;
+    }
+
+    async fn handle_param_request(&mut self, gui_params_modifier_3: &GuiParams) {
+        let request: ParamRequest = match gui_params_modifier_3.param_request.lock().unwrap().take()
+        {
+            Some(request) => request,
+            None => return,
+        };
+
+        if self.handshake != HandshakeState::Done {
+            log::warn!(
+                "Refusing parameter request, handshake state: {:?}",
+                self.handshake
+            );
+            return;
+        }
+
+        match request {
+            ParamRequest::List => self.request_params(),
+            ParamRequest::Set { name, value } => {
+                let kind: ParamType = match self.find_param(&name) {
+                    Some(info) => info.default.kind(),
+                    None => {
+                        *gui_params_modifier_3.last_message_received.lock().unwrap() =
+                            format!("Error: unknown parameter {}", name);
+                        return;
                     }
-                    DroneMode::Panic => {
-                        *gui_params_modifier_3.drone_mode.lock().unwrap() = "Panic".to_string();
+                };
+
+                match parse_param_value(kind, &value) {
+                    Some(value) => self.send_param(&name, value).await,
+                    None => {
+                        *gui_params_modifier_3.last_message_received.lock().unwrap() =
+                            format!("Error: {} is not a valid {:?}", value, kind);
                     }
-                    DroneMode::Calibrate => {
-                        *gui_params_modifier_3.drone_mode.lock().unwrap() = "Calibrate".to_string();
+                }
+            }
+        }
+    }
+
+    fn set_mode(&mut self, mode: DroneMode, gui_params_modifier_3: &GuiParams) {
+        self.mode = mode;
+        *gui_params_modifier_3.drone_mode.lock().unwrap() = format!("{:?}", mode);
+    }
+
+    fn handle_stream_request(&mut self, gui_params_modifier_3: &GuiParams) {
+        let request: StreamRequest =
+            match gui_params_modifier_3.stream_request.lock().unwrap().take() {
+                Some(request) => request,
+                None => return,
+            };
+
+        if self.handshake != HandshakeState::Done {
+            log::warn!(
+                "Refusing telemetry stream request, handshake state: {:?}",
+                self.handshake
+            );
+            return;
+        }
+
+        let data: DataT = match request {
+            StreamRequest::Get => DataT::StreamGet,
+            StreamRequest::Set { name, interval } => {
+                let stream: StreamT = match parse_stream(&name) {
+                    Some(stream) => stream,
+                    None => {
+                        *gui_params_modifier_3.last_message_received.lock().unwrap() =
+                            format!("Error: unknown telemetry stream {}", name);
+                        return;
                     }
-                    DroneMode::YawControl => {
-                        *gui_params_modifier_3.drone_mode.lock().unwrap() =
-                            "YawControl".to_string();
+                };
+
+                match interval.parse::<u16>() {
+                    Ok(interval_ms) => DataT::StreamSet(StreamRateDT {
+                        stream,
+                        interval_ms,
+                    }),
+                    Err(_) => {
+                        *gui_params_modifier_3.last_message_received.lock().unwrap() =
+                            format!("Error: {} is not an interval in milliseconds", interval);
+                        return;
                     }
-                    DroneMode::FullControl => {
-                        *gui_params_modifier_3.drone_mode.lock().unwrap() =
-                            "FullControl".to_string();
+                }
+            }
+        };
+
+        match self.pipe.send_data::<BUF_CAP>(data) {
+            Ok(_) => {}
+            Err(e) => log::error!("[ERROR]: sending telemetry stream request {:#?}", e),
+        }
+    }
+
+    // the commands of the ground station go through the same checks as ours
+    async fn handle_gcs_commands(&mut self, iter_count: u32) {
+        let commands: Vec<GcsCommand> = match self.mavlink.as_mut() {
+            Some(mavlink) => mavlink.poll(),
+            None => return,
+        };
+
+        for command in commands {
+            if self.handshake != HandshakeState::Done {
+                log::warn!(
+                    "Refusing ground station command, handshake state: {:?}",
+                    self.handshake
+                );
+                continue;
+            }
+
+            match command {
+                GcsCommand::Mode(mode) => {
+                    log::debug!("Switch mode request from the ground station: {:?}", mode);
+                    self.send_request(CommandT::Mode(mode)).await;
+                }
+                GcsCommand::Control(control) => self.gcs_control = Some((control, iter_count)),
+            }
+        }
+    }
+
+    fn handle_message(&mut self, data: DataT, gui_params_modifier_3: GuiParams) {
+        if let Some(mavlink) = self.mavlink.as_mut() {
+            mavlink.forward(&data);
+        }
+
+        match data {
+            DataT::Fragment(fragment) => {
+                if let Some(payload) = self.reassembler.push(fragment) {
+                    match LargeDataT::from_bytes(&payload) {
+                        Ok(large) => self.handle_large_message(large, gui_params_modifier_3),
+                        Err(e) => log::error!("[ERROR]: bad large message {:?}", e),
                     }
-                    DroneMode::RawMode => {
-                        *gui_params_modifier_3.drone_mode.lock().unwrap() = "RawMode".to_string();
+                }
+            }
+
+            DataT::TimeSync(sync) => {
+                if self
+                    .clock
+                    .push(sync.host_time, sync.drone_time, self.host_time())
+                {
+                    *gui_params_modifier_3.clock_sync.lock().unwrap() = self.format_clock();
+                }
+            }
+
+            DataT::LinkStats(stats) => {
+                *gui_params_modifier_3.link_stats_drone.lock().unwrap() = format_link_stats(&stats);
+            }
+
+            DataT::Handshake(remote) => {
+                if remote.is_compatible() {
+                    log::info!("Handshake done, protocol version {}", remote.version);
+                    // the drone may have restarted, its sequence numbers and
+                    // its clock too
+                    self.pipe.reset_dedup();
+                    self.pipe.set_session(remote.nonce);
+                    self.clock = ClockSync::new();
+                    *gui_params_modifier_3.clock_sync.lock().unwrap() =
+                        "not synchronised".to_string();
+                    if self.handshake != HandshakeState::Done {
+                        self.request_params();
+                        match self.pipe.send_data::<BUF_CAP>(DataT::StreamGet) {
+                            Ok(_) => {}
+                            Err(e) => log::error!("[ERROR]: requesting the stream rates {:#?}", e),
+                        }
                     }
+                    self.handshake = HandshakeState::Done;
+                } else {
+                    log::error!(
+                        "[ERROR]: drone protocol mismatch, drone: {:?}, runner: {:?}",
+                        remote,
+                        HandshakeDT::local()
+                    );
+                    self.handshake = HandshakeState::Mismatch(remote);
+                    *gui_params_modifier_3.last_message_received.lock().unwrap() =
+                        "Error: drone firmware uses another protocol version".to_string();
                 }
             }
 
+            DataT::Mode(mode) => {
+                log::info!("Drone mode is now: {:#?}", mode);
+                self.set_mode(mode, &gui_params_modifier_3);
+            }
+
             DataT::Warning(warn) => {
                 log::warn!("Drone gave warning: {:#?}", warn);
                 match warn {
@@ -185,6 +645,18 @@ impl Logic {
                         *gui_params_modifier_3.last_message_received.lock().unwrap() =
                             "Warning:gave warning:Sensor Not Calibrated ".to_string();
                     }
+                    WarningDT::HandshakeMissing => {
+                        *gui_params_modifier_3.last_message_received.lock().unwrap() =
+                            "Warning:Handshake Missing ".to_string();
+                    }
+                    WarningDT::ParamRejected => {
+                        *gui_params_modifier_3.last_message_received.lock().unwrap() =
+                            "Warning:Parameter Rejected ".to_string();
+                    }
+                    WarningDT::LogUnavailable => {
+                        *gui_params_modifier_3.last_message_received.lock().unwrap() =
+                            "Warning:Log Unavailable ".to_string();
+                    }
                 }
             }
 
@@ -196,8 +668,42 @@ impl Logic {
                     .unwrap() = "Drone Sent:".to_string() + &*s.to_string();
             }
 
-            DataT::KeepAlive => {
-                // TODO: keep track of keep alive on the PC
+            DataT::Event(event) => {
+                if event.code.is_error() {
+                    log::error!("[ERROR]: drone reported {}", event);
+                } else {
+                    log::info!("Drone reported {}", event);
+                }
+
+                *self.events.entry(event.code).or_insert(0) += 1;
+                *gui_params_modifier_3.events.lock().unwrap() = format_events(&event, &self.events);
+            }
+
+            DataT::Heartbeat(heartbeat) => {
+                // the mode is repeated by every heartbeat, in case a `Mode`
+                // message got lost
+                if heartbeat.mode != self.mode {
+                    log::info!("Drone mode is now: {:#?} (heartbeat)", heartbeat.mode);
+                    self.set_mode(heartbeat.mode, &gui_params_modifier_3);
+                }
+                if self.heartbeat.map(|last| last.faults) != Some(heartbeat.faults) {
+                    log::info!("Drone status: {}", format_heartbeat(&heartbeat));
+                }
+                if heartbeat.faults & FAULT_HANDSHAKE != 0 && self.handshake == HandshakeState::Done
+                {
+                    // the drone restarted, it has to be handshaken again
+                    log::info!("Drone lost the handshake, starting a new one");
+                    self.handshake = HandshakeState::Pending;
+                }
+                if self.heartbeat_at.is_none() && self.heartbeat.is_some() {
+                    log::info!("Drone link is back");
+                }
+                self.heartbeat = Some(heartbeat);
+                self.heartbeat_at = Some(Instant::now());
+
+                *gui_params_modifier_3.is_battery_weak.lock().unwrap() =
+                    matches!(heartbeat.battery, BatteryT::Low | BatteryT::Critical);
+                *gui_params_modifier_3.heartbeat.lock().unwrap() = format_heartbeat(&heartbeat);
             }
 
             DataT::CalibratedAck(calibrated_values) => {
@@ -230,12 +736,14 @@ impl Logic {
 
             DataT::HealthData(healthDT) => {
                 log::info!(
-                    "Drone sent health data -> battery: {}, cpu: {}, pressure: {}",
+                    "Drone sent health data at {} -> battery: {}, pressure: {}, {}",
+                    self.format_drone_time(healthDT.time),
                     healthDT.bat,
-                    healthDT.cpu,
                     healthDT.pres,
+                    format_loop_health(&healthDT),
                 );
                 *gui_params_modifier_3.battery_health.lock().unwrap() = healthDT.bat;
+                *gui_params_modifier_3.loop_health.lock().unwrap() = format_loop_health(&healthDT);
             }
 
             DataT::MovementErrors(offsets) => {
@@ -261,33 +769,241 @@ impl Logic {
                 *gui_params_modifier_3.motor_4_value.lock().unwrap() = motors.ae4;
             }
 
-            DataT::UpdateP(updated_p_value) => {
-                log::info!("Updated P : {}", updated_p_value.p,);
+            DataT::ParamInfo(info) => {
+                self.param_info.insert(info.id, info);
+                *gui_params_modifier_3.params.lock().unwrap() = self.format_params();
+            }
 
-                *gui_params_modifier_3.yaw_p.lock().unwrap() = updated_p_value.p.to_num::<i32>();
+            DataT::ParamValue(param) => {
+                log::info!("Parameter {} is now {:?}", param.id, param.value);
+                self.param_values.insert(param.id, param.value);
+                *gui_params_modifier_3.params.lock().unwrap() = self.format_params();
             }
-            DataT::UpdateP1P2(updated_p1_p2_values) => {
-                log::info!(
-                    "Updated P1: {}, P2: {}",
-                    updated_p1_p2_values.p1,
-                    updated_p1_p2_values.p2
-                );
-                *gui_params_modifier_3.rp_p1.lock().unwrap() =
-                    updated_p1_p2_values.p1.to_num::<i32>();
-                *gui_params_modifier_3.rp_p2.lock().unwrap() =
-                    updated_p1_p2_values.p2.to_num::<i32>();
+
+            DataT::Response(response) => {
+                if !self.requests.on_response(response.clone()) {
+                    log::debug!("[ERROR]: answer to an unknown request {:?}", response);
+                }
             }
 
-            DataT::SensorLog(sensor_data) => {
-                log::info!(
-                    "gyrox: {} , gyroy: {} , gyroz: {}, accelx: {} , accely: {} , accelz: {} , roll: {}, pitch: {} , yaw: {}", sensor_data.gyro_x , sensor_data.gyro_y , sensor_data.gyro_z , sensor_data.accel_x, sensor_data.accel_y , sensor_data.accel_z , sensor_data.pitch , sensor_data.roll, sensor_data.yaw
-                );
+            DataT::StreamRates(rates) => {
+                log::info!("Drone telemetry streams: {}", format_stream_rates(&rates));
+                *gui_params_modifier_3.streams.lock().unwrap() = format_stream_rates(&rates);
+            }
+
+            DataT::SensorLog(sensor_data) => self.log_sensor_data(&sensor_data),
+
+            DataT::SensorStream(stream) => {
+                for sample in SensorDecoder::new(&stream) {
+                    match sample {
+                        Ok(sample) => {
+                            let sample: SensorLogDT = sample.to_log();
+                            self.log_sensor_data(&sample);
+                        }
+                        Err(e) => log::error!("[ERROR]: bad sensor stream {:?}", e),
+                    }
+                }
             }
 
             _ => {}
         }
     }
 
-    // TODO
-    fn handle_read_error(&mut self, _e: ComErr) {}
+    fn log_sensor_data(&self, sensor_data: &SensorLogDT) {
+        log::info!(
+            "at {}: gyrox: {} , gyroy: {} , gyroz: {}, accelx: {} , accely: {} , accelz: {} , roll: {}, pitch: {} , yaw: {}", self.format_drone_time(sensor_data.time), sensor_data.gyro_x , sensor_data.gyro_y , sensor_data.gyro_z , sensor_data.accel_x, sensor_data.accel_y , sensor_data.accel_z , sensor_data.pitch , sensor_data.roll, sensor_data.yaw
+        );
+    }
+
+    fn handle_large_message(&mut self, data: LargeDataT, gui_params_modifier_3: GuiParams) {
+        match data {
+            LargeDataT::Message(s) => {
+                log::info!("Large message from drone: {}", s);
+                *gui_params_modifier_3.last_message_received.lock().unwrap() = s.to_string();
+            }
+        }
+    }
+
+    // frames that could not be read are dropped, the reliable channel
+    // retransmits the critical ones
+    fn handle_read_error(&mut self, e: ComErr) {
+        match e {
+            ComErr::Empty => {}
+            // counted in the link statistics
+            e => log::debug!("[ERROR]: failed to read from the drone: {:?}", e),
+        }
+    }
+
+    /// Time since the runner started, in microseconds.
+    fn host_time(&self) -> u64 {
+        self.started.elapsed().as_micros() as u64
+    }
+
+    // a drone timestamp on our timeline, if the clocks are synchronised
+    fn format_drone_time(&self, time: Timestamp) -> String {
+        match self.clock.to_host(time) {
+            Some(host) => format!("{:.3} s", host as f64 / 1e6),
+            None => format!("drone {} us", time),
+        }
+    }
+
+    fn format_clock(&self) -> String {
+        format!(
+            "offset {} us | drift {} ppm | rtt {} us",
+            self.clock.offset().unwrap_or_default(),
+            self.clock.drift_ppm(),
+            self.clock.rtt().unwrap_or_default()
+        )
+    }
+
+    // one line per parameter: name, value and bounds
+    fn format_params(&self) -> String {
+        let mut ret: String = String::new();
+
+        for (id, info) in self.param_info.iter() {
+            let value: String = match self.param_values.get(id) {
+                Some(value) => format_param_value(value),
+                None => "?".to_string(),
+            };
+
+            ret += &format!(
+                "{}: {} [{} .. {}]\n",
+                info.name,
+                value,
+                format_param_value(&info.min),
+                format_param_value(&info.max)
+            );
+        }
+
+        ret
+    }
+}
+
+fn format_param_value(value: &ParamValue) -> String {
+    match value {
+        ParamValue::Bool(v) => v.to_string(),
+        ParamValue::U32(v) => v.to_string(),
+        ParamValue::Fixed(v) => v.to_string(),
+    }
+}
+
+/// Parses `text` as a value of type `kind`, `None` if it is not one.
+fn parse_param_value(kind: ParamType, text: &str) -> Option<ParamValue> {
+    match kind {
+        ParamType::Bool => match text {
+            "true" | "1" => Some(ParamValue::Bool(true)),
+            "false" | "0" => Some(ParamValue::Bool(false)),
+            _ => None,
+        },
+        ParamType::U32 => text.parse::<u32>().ok().map(ParamValue::U32),
+        ParamType::Fixed => text
+            .parse::<f64>()
+            .ok()
+            .and_then(I16F16::checked_from_num)
+            .map(ParamValue::Fixed),
+    }
+}
+
+// the last event, then how many of each were reported
+fn format_events(last: &EventDT, counts: &BTreeMap<EventCodeT, u32>) -> String {
+    let mut ret: String = format!("last: {}", last);
+
+    for (code, count) in counts.iter() {
+        ret += &format!(
+            "\n{:?}/{:?}{}: {}",
+            code.source(),
+            code,
+            if code.is_error() { " (error)" } else { "" },
+            count
+        );
+    }
+
+    ret
+}
+
+// the flags of the drone, then its faults
+fn format_heartbeat(heartbeat: &HeartbeatDT) -> String {
+    let mut ret: String = format!(
+        "{} | {} | logging {} | log report {} | battery {:?}",
+        if heartbeat.armed { "armed" } else { "disarmed" },
+        if heartbeat.calibrated {
+            "calibrated"
+        } else {
+            "not calibrated"
+        },
+        if heartbeat.logging { "on" } else { "off" },
+        if heartbeat.log_report { "on" } else { "off" },
+        heartbeat.battery,
+    );
+
+    for (fault, name) in [
+        (FAULT_BATTERY, "battery critical"),
+        (FAULT_LINK, "link lost"),
+        (FAULT_HANDSHAKE, "no handshake"),
+        (FAULT_LOG_FULL, "log full"),
+    ] {
+        if heartbeat.has_fault(fault) {
+            ret += &format!(" | FAULT: {}", name);
+        }
+    }
+
+    ret
+}
+
+/// The telemetry stream called `name` (case insensitive), `None` if there is
+/// none.
+fn parse_stream(name: &str) -> Option<StreamT> {
+    StreamT::ALL
+        .iter()
+        .find(|stream| format!("{:?}", stream).eq_ignore_ascii_case(name))
+        .copied()
+}
+
+// one line per stream, then the bandwidth they use
+fn format_stream_rates(rates: &StreamRatesDT) -> String {
+    let mut ret: String = String::new();
+
+    for stream in StreamT::ALL.iter() {
+        ret += &match rates.interval_ms[stream.index()] {
+            0 => format!("{:?}: off\n", stream),
+            interval => format!("{:?}: every {} ms\n", stream, interval),
+        };
+    }
+
+    ret + &format!("using {} of {} B/s", rates.used, rates.budget)
+}
+
+fn format_outcome(command: CommandT, outcome: &Outcome) -> String {
+    match outcome {
+        Outcome::Pending => format!("{:?} pending", command),
+        Outcome::Answered(ResultT::Ok) => format!("{:?} done", command),
+        Outcome::Answered(ResultT::Rejected(reason)) => {
+            format!("{:?} rejected: {:?}", command, reason)
+        }
+        Outcome::Answered(ResultT::InvalidInMode(mode)) => {
+            format!("{:?} invalid in {:?} mode", command, mode)
+        }
+        Outcome::TimedOut => format!("{:?} not answered by the drone", command),
+    }
+}
+
+fn format_link_stats(stats: &LinkStatsDT) -> String {
+    format!(
+        "rx {} | tx {} | crc {} | des {} | resync {} | skipped {} B | busy {} | auth {}",
+        stats.frames_received,
+        stats.frames_sent,
+        stats.crc_errors,
+        stats.des_errors,
+        stats.resyncs,
+        stats.skipped_bytes,
+        stats.busy_sends,
+        stats.auth_errors
+    )
+}
+
+fn format_loop_health(health: &HealthDT) -> String {
+    format!(
+        "cpu {}% | avg {} us | max {} us | jitter {} us | overruns {}",
+        health.cpu, health.loop_avg, health.loop_max, health.jitter, health.overruns
+    )
 }
@@ -1,29 +1,32 @@
 #![feature(once_cell)]
 
 // This crate configuration
+mod async_com;
 mod gui;
 mod input;
 mod logger;
 mod logic;
+mod mavlink;
 mod serial_wrapper;
 mod utils;
 
 // Rust libraries
 use std::env::args;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 use std::path::PathBuf;
 use std::process::{exit, Command};
 use std::sync::{Arc, Mutex};
 use std::thread;
 use std::thread::sleep;
 use std::time::Duration;
-use std::time::Instant;
 
 // TUDelft library
 use crate::gui::{gui_terminal_init, GuiParams};
 use tudelft_serial_upload::{upload_file_or_stop, PortSelector};
 
 use crate::input::{joystick, keyboard};
-use crate::utils::constants::TICK_RATE;
+use crate::mavlink::MavlinkBridge;
+use crate::utils::constants::{MAVLINK_DEFAULT_PEER, MAVLINK_LOCAL, TICK_RATE};
 
 // Our libraries
 
@@ -39,6 +42,7 @@ fn main() {
     let gui_values = GuiParams {
         status: Arc::new(Mutex::new(0)),
         drone_mode: Arc::new(Mutex::new("Safe".to_string())),
+        heartbeat: Arc::new(Mutex::new("no heartbeat yet".to_string())),
         last_keyboard_key_pressed: Arc::new(Mutex::new("NA".to_string())),
         joystick_roll_input: Arc::new(Mutex::new(0)),
         joystick_pitch_input: Arc::new(Mutex::new(0)),
@@ -48,29 +52,54 @@ fn main() {
         motor_2_value: Arc::new(Mutex::new(0)),
         motor_3_value: Arc::new(Mutex::new(0)),
         motor_4_value: Arc::new(Mutex::new(0)),
-        yaw_p: Arc::new(Mutex::new(25)),
-        rp_p1: Arc::new(Mutex::new(6)),
-        rp_p2: Arc::new(Mutex::new(57)),
+        params: Arc::new(Mutex::new("nothing so far".to_string())),
+        param_name_input: Arc::new(Mutex::new(String::new())),
+        param_value_input: Arc::new(Mutex::new(String::new())),
+        param_request: Arc::new(Mutex::new(None)),
+        streams: Arc::new(Mutex::new("nothing so far".to_string())),
+        stream_name_input: Arc::new(Mutex::new(String::new())),
+        stream_interval_input: Arc::new(Mutex::new(String::new())),
+        stream_request: Arc::new(Mutex::new(None)),
         last_message_received: Arc::new(Mutex::new("nothing so far".to_string())),
         debug_prints_from_drone: Arc::new(Mutex::new("nothing so far".to_string())),
         is_battery_weak: Arc::new(Mutex::new(false)),
         battery_health: Arc::new(Mutex::new(0)),
+        loop_health: Arc::new(Mutex::new("nothing so far".to_string())),
+        events: Arc::new(Mutex::new("nothing so far".to_string())),
+        link_stats_runner: Arc::new(Mutex::new("nothing so far".to_string())),
+        link_stats_drone: Arc::new(Mutex::new("nothing so far".to_string())),
+        clock_sync: Arc::new(Mutex::new("not synchronised".to_string())),
+        last_request: Arc::new(Mutex::new("nothing so far".to_string())),
     };
 
     let gui_params_modifier_1 = gui_values.clone();
     let gui_params_modifier_2 = gui_values.clone();
     let gui_params_modifier_3 = gui_values.clone();
 
+    // arguments: the firmware to upload and `--mavlink[=ADDRESS]` to show the
+    // drone to a ground station listening at that UDP address
+    let mut file: Option<String> = None;
+    let mut mavlink_peer: Option<String> = None;
+    for arg in args().skip(1) {
+        if arg == "--mavlink" {
+            mavlink_peer = Some(MAVLINK_DEFAULT_PEER.to_string());
+        } else if let Some(peer) = arg.strip_prefix("--mavlink=") {
+            mavlink_peer = Some(peer.to_string());
+        } else {
+            file = Some(arg);
+        }
+    }
+    let mavlink: Option<MavlinkBridge> = mavlink_peer.map(|peer| open_mavlink(&peer));
+
     // Try to upload the updated code to the drone
-    let file = args().nth(1);
     let port = upload_file_or_stop(PortSelector::AutoManufacturer, file);
     // Setup the serialport connection to the drone, for debugging purposes, serial is an option.
-    serial_wrapper::init_global_serial(port, 115200);
+    let serial = serial_wrapper::SerialTransport::open(port, 115200);
 
     // The code then enters the main loop, which runs every 1000 ms for now.
     let interval = Duration::from_millis(TICK_RATE);
 
-    let mut logic: logic::Logic = logic::Logic::default();
+    let mut logic: logic::Logic = logic::Logic::new(serial, mavlink);
 
     start_keybord_joystick_interface(gui_params_modifier_1);
 
@@ -80,25 +109,39 @@ fn main() {
 
     sleep(Duration::from_millis(1000));
 
+    // program logic, it handles the messages of the drone as soon as they arrive
     let _ = thread::spawn(move || {
-        let mut next_time;
-        for i in 1.. {
-            next_time = Instant::now() + interval;
-
-            // program logic
-            logic.tick(i, &interval, gui_params_modifier_3.clone());
+        pasts::block_on(async move { logic.run(interval, gui_params_modifier_3).await });
+    });
 
-            if next_time < Instant::now() {
-                log::error!("Deadline exceeded by {:?}", Instant::now() - next_time);
-                next_time = Instant::now() + interval;
-            }
+    gui_terminal_init(gui_values).expect("Unable to start Gui")
+}
 
-            // Busy wait
-            sleep(next_time - Instant::now());
+fn open_mavlink(peer: &str) -> MavlinkBridge {
+    let peer: SocketAddr = match peer.parse() {
+        Ok(peer) => peer,
+        Err(e) => {
+            log::error!("[ERROR]: bad MAVLink address {}: {}", peer, e);
+            exit(1);
         }
-    });
+    };
 
-    gui_terminal_init(gui_values).expect("Unable to start Gui")
+    // only reachable from the network if the ground station is elsewhere
+    let mut local: SocketAddr = MAVLINK_LOCAL.parse().unwrap();
+    if !peer.ip().is_loopback() {
+        local.set_ip(IpAddr::V4(Ipv4Addr::UNSPECIFIED));
+    }
+
+    match MavlinkBridge::open(local, peer) {
+        Ok(bridge) => {
+            log::info!("MAVLink bridge to {}", peer);
+            bridge
+        }
+        Err(e) => {
+            log::error!("[ERROR]: opening the MAVLink bridge {}", e);
+            exit(1);
+        }
+    }
 }
 
 fn gui_values_aggregator(gui_params_modifier_2: GuiParams) {
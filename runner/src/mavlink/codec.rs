@@ -0,0 +1,572 @@
+//! the small part of MAVLink v2 the runner needs to show the drone in ground
+//! station tools, see `translate` for the mapping from and to `DataT`
+//!
+//! Only unsigned frames are supported. The payloads are laid out as in the
+//! MAVLink definitions: the fields sorted by size, little endian, without the
+//! extension fields. Trailing zeros are cut when sending and restored when
+//! receiving, as the standard asks.
+
+use crc::{Crc, CRC_16_MCRF4XX};
+
+pub const MAV_STX: u8 = 0xFD;
+pub const MAV_HEADER_SIZE: usize = 10; // start byte included
+pub const MAV_CHECKSUM_SIZE: usize = 2;
+pub const MAV_MAX_PAYLOAD: usize = 255;
+pub const MAV_FRAME_CAP: usize = MAV_HEADER_SIZE + MAV_MAX_PAYLOAD + MAV_CHECKSUM_SIZE;
+
+// the frame is signed or uses another feature we do not know
+const MAV_INCOMPAT_FLAGS_SUPPORTED: u8 = 0;
+
+const MAV_CRC: Crc<u16> = Crc::<u16>::new(&CRC_16_MCRF4XX);
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum MavErr {
+    Truncated,           // the frame goes past the end of the bytes
+    NotAFrame,           // no MAVLink v2 start byte
+    Unsupported,         // incompatibility flags we do not understand
+    UnknownMessage(u32), // the checksum of the message can not be verified
+    Checksum,            // the frame is corrupted
+}
+
+/// Who sent a frame, and its position in the frames sent by them.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct MavHeader {
+    pub seq: u8,
+    pub system: u8,
+    pub component: u8,
+}
+
+/// HEARTBEAT (0)
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct Heartbeat {
+    pub custom_mode: u32,
+    pub kind: u8, // `type` in the definition
+    pub autopilot: u8,
+    pub base_mode: u8,
+    pub system_status: u8,
+    pub mavlink_version: u8,
+}
+
+/// SYS_STATUS (1)
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct SysStatus {
+    pub sensors_present: u32,
+    pub sensors_enabled: u32,
+    pub sensors_health: u32,
+    pub load: u16,            // in 0.1 %
+    pub voltage_battery: u16, // in mV
+    pub current_battery: i16, // in cA, -1 if unknown
+    pub drop_rate_comm: u16,  // in 0.01 %
+    pub errors_comm: u16,
+    pub errors_count: [u16; 4],
+    pub battery_remaining: i8, // in %, -1 if unknown
+}
+
+/// SET_MODE (11)
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct SetMode {
+    pub custom_mode: u32,
+    pub target_system: u8,
+    pub base_mode: u8,
+}
+
+/// ATTITUDE (30)
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct Attitude {
+    pub time_boot_ms: u32,
+    pub roll: f32, // in rad
+    pub pitch: f32,
+    pub yaw: f32,
+    pub rollspeed: f32, // in rad/s
+    pub pitchspeed: f32,
+    pub yawspeed: f32,
+}
+
+/// SERVO_OUTPUT_RAW (36)
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct ServoOutputRaw {
+    pub time_usec: u32,
+    pub servo_raw: [u16; 8], // in us
+    pub port: u8,
+}
+
+/// MANUAL_CONTROL (69)
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct ManualControl {
+    pub x: i16, // pitch, in [-1000, 1000]
+    pub y: i16, // roll, in [-1000, 1000]
+    pub z: i16, // thrust, in [0, 1000]
+    pub r: i16, // yaw, in [-1000, 1000]
+    pub buttons: u16,
+    pub target: u8,
+}
+
+/// COMMAND_LONG (76)
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct CommandLong {
+    pub params: [f32; 7],
+    pub command: u16,
+    pub target_system: u8,
+    pub target_component: u8,
+    pub confirmation: u8,
+}
+
+/// COMMAND_ACK (77)
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct CommandAck {
+    pub command: u16,
+    pub result: u8,
+}
+
+/// The messages we send or understand.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum MavMessage {
+    Heartbeat(Heartbeat),
+    SysStatus(SysStatus),
+    SetMode(SetMode),
+    Attitude(Attitude),
+    ServoOutputRaw(ServoOutputRaw),
+    ManualControl(ManualControl),
+    CommandLong(CommandLong),
+    CommandAck(CommandAck),
+}
+
+impl MavMessage {
+    pub fn id(&self) -> u32 {
+        match self {
+            MavMessage::Heartbeat(_) => 0,
+            MavMessage::SysStatus(_) => 1,
+            MavMessage::SetMode(_) => 11,
+            MavMessage::Attitude(_) => 30,
+            MavMessage::ServoOutputRaw(_) => 36,
+            MavMessage::ManualControl(_) => 69,
+            MavMessage::CommandLong(_) => 76,
+            MavMessage::CommandAck(_) => 77,
+        }
+    }
+
+    /// Hash of the definition of message `id`, mixed into the checksum so
+    /// that both sides must agree on the layout. `None` for the messages we
+    /// do not know.
+    pub fn crc_extra(id: u32) -> Option<u8> {
+        match id {
+            0 => Some(50),
+            1 => Some(124),
+            11 => Some(89),
+            30 => Some(39),
+            36 => Some(222),
+            69 => Some(243),
+            76 => Some(152),
+            77 => Some(143),
+            _ => None,
+        }
+    }
+
+    fn write(&self, w: &mut Writer) {
+        match self {
+            MavMessage::Heartbeat(m) => {
+                w.u32(m.custom_mode);
+                w.u8(m.kind);
+                w.u8(m.autopilot);
+                w.u8(m.base_mode);
+                w.u8(m.system_status);
+                w.u8(m.mavlink_version);
+            }
+            MavMessage::SysStatus(m) => {
+                w.u32(m.sensors_present);
+                w.u32(m.sensors_enabled);
+                w.u32(m.sensors_health);
+                w.u16(m.load);
+                w.u16(m.voltage_battery);
+                w.u16(m.current_battery as u16);
+                w.u16(m.drop_rate_comm);
+                w.u16(m.errors_comm);
+                for count in m.errors_count {
+                    w.u16(count);
+                }
+                w.u8(m.battery_remaining as u8);
+            }
+            MavMessage::SetMode(m) => {
+                w.u32(m.custom_mode);
+                w.u8(m.target_system);
+                w.u8(m.base_mode);
+            }
+            MavMessage::Attitude(m) => {
+                w.u32(m.time_boot_ms);
+                for value in [
+                    m.roll,
+                    m.pitch,
+                    m.yaw,
+                    m.rollspeed,
+                    m.pitchspeed,
+                    m.yawspeed,
+                ] {
+                    w.f32(value);
+                }
+            }
+            MavMessage::ServoOutputRaw(m) => {
+                w.u32(m.time_usec);
+                for servo in m.servo_raw {
+                    w.u16(servo);
+                }
+                w.u8(m.port);
+            }
+            MavMessage::ManualControl(m) => {
+                for axis in [m.x, m.y, m.z, m.r] {
+                    w.u16(axis as u16);
+                }
+                w.u16(m.buttons);
+                w.u8(m.target);
+            }
+            MavMessage::CommandLong(m) => {
+                for param in m.params {
+                    w.f32(param);
+                }
+                w.u16(m.command);
+                w.u8(m.target_system);
+                w.u8(m.target_component);
+                w.u8(m.confirmation);
+            }
+            MavMessage::CommandAck(m) => {
+                w.u16(m.command);
+                w.u8(m.result);
+            }
+        }
+    }
+
+    fn read(id: u32, r: &mut Reader) -> Option<Self> {
+        let message: MavMessage = match id {
+            0 => MavMessage::Heartbeat(Heartbeat {
+                custom_mode: r.u32(),
+                kind: r.u8(),
+                autopilot: r.u8(),
+                base_mode: r.u8(),
+                system_status: r.u8(),
+                mavlink_version: r.u8(),
+            }),
+            1 => MavMessage::SysStatus(SysStatus {
+                sensors_present: r.u32(),
+                sensors_enabled: r.u32(),
+                sensors_health: r.u32(),
+                load: r.u16(),
+                voltage_battery: r.u16(),
+                current_battery: r.u16() as i16,
+                drop_rate_comm: r.u16(),
+                errors_comm: r.u16(),
+                errors_count: [r.u16(), r.u16(), r.u16(), r.u16()],
+                battery_remaining: r.u8() as i8,
+            }),
+            11 => MavMessage::SetMode(SetMode {
+                custom_mode: r.u32(),
+                target_system: r.u8(),
+                base_mode: r.u8(),
+            }),
+            30 => MavMessage::Attitude(Attitude {
+                time_boot_ms: r.u32(),
+                roll: r.f32(),
+                pitch: r.f32(),
+                yaw: r.f32(),
+                rollspeed: r.f32(),
+                pitchspeed: r.f32(),
+                yawspeed: r.f32(),
+            }),
+            36 => MavMessage::ServoOutputRaw(ServoOutputRaw {
+                time_usec: r.u32(),
+                servo_raw: [
+                    r.u16(),
+                    r.u16(),
+                    r.u16(),
+                    r.u16(),
+                    r.u16(),
+                    r.u16(),
+                    r.u16(),
+                    r.u16(),
+                ],
+                port: r.u8(),
+            }),
+            69 => MavMessage::ManualControl(ManualControl {
+                x: r.u16() as i16,
+                y: r.u16() as i16,
+                z: r.u16() as i16,
+                r: r.u16() as i16,
+                buttons: r.u16(),
+                target: r.u8(),
+            }),
+            76 => MavMessage::CommandLong(CommandLong {
+                params: [
+                    r.f32(),
+                    r.f32(),
+                    r.f32(),
+                    r.f32(),
+                    r.f32(),
+                    r.f32(),
+                    r.f32(),
+                ],
+                command: r.u16(),
+                target_system: r.u8(),
+                target_component: r.u8(),
+                confirmation: r.u8(),
+            }),
+            77 => MavMessage::CommandAck(CommandAck {
+                command: r.u16(),
+                result: r.u8(),
+            }),
+            _ => return None,
+        };
+
+        Some(message)
+    }
+}
+
+/// Builds the frame carrying `message`.
+pub fn encode(header: &MavHeader, message: &MavMessage) -> heapless::Vec<u8, MAV_FRAME_CAP> {
+    let mut payload: Writer = Writer {
+        bytes: heapless::Vec::new(),
+    };
+    message.write(&mut payload);
+
+    // trailing zeros are implied, but the payload is at least one byte long
+    let mut len: usize = payload.bytes.len();
+    while len > 1 && payload.bytes[len - 1] == 0 {
+        len -= 1;
+    }
+
+    let id: [u8; 4] = message.id().to_le_bytes();
+    let mut frame: heapless::Vec<u8, MAV_FRAME_CAP> = heapless::Vec::new();
+    // the payloads we know are all far below the capacity
+    let _ = frame.extend_from_slice(&[
+        MAV_STX,
+        len as u8,
+        0, // incompatibility flags
+        0, // compatibility flags
+        header.seq,
+        header.system,
+        header.component,
+        id[0],
+        id[1],
+        id[2],
+    ]);
+    let _ = frame.extend_from_slice(&payload.bytes[..len]);
+
+    let crc_extra: u8 = MavMessage::crc_extra(message.id()).unwrap_or_default();
+    let checksum: u16 = checksum(&frame[1..], crc_extra);
+    let _ = frame.extend_from_slice(&checksum.to_le_bytes());
+
+    frame
+}
+
+/// Reads the frame at the start of `bytes`.
+/// @return the header, the message and the size of the frame. The size is
+/// also given for the frames of messages we do not know, so they can be
+/// skipped.
+pub fn decode(bytes: &[u8]) -> Result<(MavHeader, MavMessage, usize), (MavErr, usize)> {
+    if bytes.first() != Some(&MAV_STX) {
+        return Err((MavErr::NotAFrame, 1));
+    }
+    if bytes.len() < MAV_HEADER_SIZE {
+        return Err((MavErr::Truncated, bytes.len()));
+    }
+
+    let len: usize = bytes[1] as usize;
+    let size: usize = MAV_HEADER_SIZE + len + MAV_CHECKSUM_SIZE;
+    if bytes.len() < size {
+        return Err((MavErr::Truncated, bytes.len()));
+    }
+    if bytes[2] & !MAV_INCOMPAT_FLAGS_SUPPORTED != 0 {
+        return Err((MavErr::Unsupported, size));
+    }
+
+    let id: u32 = u32::from_le_bytes([bytes[7], bytes[8], bytes[9], 0]);
+    let crc_extra: u8 = match MavMessage::crc_extra(id) {
+        Some(crc_extra) => crc_extra,
+        None => return Err((MavErr::UnknownMessage(id), size)),
+    };
+
+    let received: u16 = u16::from_le_bytes([bytes[size - 2], bytes[size - 1]]);
+    if checksum(&bytes[1..size - MAV_CHECKSUM_SIZE], crc_extra) != received {
+        return Err((MavErr::Checksum, size));
+    }
+
+    let header: MavHeader = MavHeader {
+        seq: bytes[4],
+        system: bytes[5],
+        component: bytes[6],
+    };
+    let mut payload: Reader = Reader {
+        bytes: &bytes[MAV_HEADER_SIZE..MAV_HEADER_SIZE + len],
+        pos: 0,
+    };
+
+    match MavMessage::read(id, &mut payload) {
+        Some(message) => Ok((header, message, size)),
+        None => Err((MavErr::UnknownMessage(id), size)),
+    }
+}
+
+// X.25 checksum of the frame without the start byte, then of the hash of the
+// message definition
+fn checksum(frame: &[u8], crc_extra: u8) -> u16 {
+    let mut digest = MAV_CRC.digest();
+    digest.update(frame);
+    digest.update(&[crc_extra]);
+    digest.finalize()
+}
+
+struct Writer {
+    bytes: heapless::Vec<u8, MAV_MAX_PAYLOAD>,
+}
+
+// the messages we know are much smaller than `MAV_MAX_PAYLOAD`, so the
+// results of `extend_from_slice` are ignored
+impl Writer {
+    fn u8(&mut self, value: u8) {
+        let _ = self.bytes.push(value);
+    }
+
+    fn u16(&mut self, value: u16) {
+        let _ = self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn u32(&mut self, value: u32) {
+        let _ = self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn f32(&mut self, value: f32) {
+        let _ = self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+}
+
+// reads past the end of the payload give zeros, they were cut by the sender
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take<const N: usize>(&mut self) -> [u8; N] {
+        let mut ret: [u8; N] = [0; N];
+        for byte in ret.iter_mut() {
+            *byte = self.bytes.get(self.pos).copied().unwrap_or(0);
+            self.pos += 1;
+        }
+        ret
+    }
+
+    fn u8(&mut self) -> u8 {
+        self.take::<1>()[0]
+    }
+
+    fn u16(&mut self) -> u16 {
+        u16::from_le_bytes(self.take())
+    }
+
+    fn u32(&mut self) -> u32 {
+        u32::from_le_bytes(self.take())
+    }
+
+    fn f32(&mut self) -> f32 {
+        f32::from_le_bytes(self.take())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::mavlink::codec::*;
+
+    const HEADER: MavHeader = MavHeader {
+        seq: 7,
+        system: 1,
+        component: 1,
+    };
+
+    #[test]
+    fn test_heartbeat_frame() {
+        // built with an independent implementation of the standard
+        let expected: [u8; 21] = [
+            0xfd, 0x09, 0x00, 0x00, 0x07, 0x01, 0x01, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00,
+            0x02, 0x00, 0x81, 0x04, 0x03, 0x5e, 0x11,
+        ];
+        let heartbeat: MavMessage = MavMessage::Heartbeat(Heartbeat {
+            custom_mode: 2,
+            kind: 2,
+            autopilot: 0,
+            base_mode: 0x81,
+            system_status: 4,
+            mavlink_version: 3,
+        });
+
+        assert_eq!(encode(&HEADER, &heartbeat)[..], expected[..]);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let messages: [MavMessage; 4] = [
+            MavMessage::Attitude(Attitude {
+                time_boot_ms: 1234,
+                roll: 0.5,
+                pitch: -0.25,
+                yaw: 3.0,
+                rollspeed: 0.0,
+                pitchspeed: 0.0,
+                yawspeed: 0.0, // cut from the frame
+            }),
+            MavMessage::ManualControl(ManualControl {
+                x: -1000,
+                y: 1000,
+                z: 500,
+                r: 0,
+                buttons: 0,
+                target: 1,
+            }),
+            MavMessage::CommandLong(CommandLong {
+                params: [1.0, 3.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+                command: 176,
+                target_system: 1,
+                target_component: 1,
+                confirmation: 0,
+            }),
+            MavMessage::SysStatus(SysStatus {
+                voltage_battery: 11_100,
+                current_battery: -1,
+                battery_remaining: -1,
+                ..Default::default()
+            }),
+        ];
+
+        for message in messages {
+            let frame = encode(&HEADER, &message);
+            assert_eq!(decode(&frame), Ok((HEADER, message, frame.len())));
+        }
+    }
+
+    #[test]
+    fn test_trailing_zeros_are_cut() {
+        let frame = encode(&HEADER, &MavMessage::CommandAck(CommandAck::default()));
+        // one byte of payload is always sent
+        assert_eq!(frame.len(), MAV_HEADER_SIZE + 1 + MAV_CHECKSUM_SIZE);
+        assert_eq!(frame[1], 1);
+    }
+
+    #[test]
+    fn test_bad_frames() {
+        let message: MavMessage = MavMessage::CommandAck(CommandAck {
+            command: 176,
+            result: 0,
+        });
+        let mut frame = encode(&HEADER, &message);
+        let size: usize = frame.len();
+
+        assert_eq!(
+            decode(&frame[..size - 1]),
+            Err((MavErr::Truncated, size - 1))
+        );
+        assert_eq!(decode(&frame[1..]), Err((MavErr::NotAFrame, 1)));
+
+        frame[MAV_HEADER_SIZE] ^= 0x01;
+        assert_eq!(decode(&frame), Err((MavErr::Checksum, size)));
+
+        // a message we do not know is skipped as a whole
+        frame[7] = 0xff;
+        assert_eq!(decode(&frame), Err((MavErr::UnknownMessage(0xff), size)));
+    }
+}
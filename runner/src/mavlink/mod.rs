@@ -0,0 +1,360 @@
+pub(crate) mod codec;
+pub(crate) mod translate;
+
+// Rust libraries
+use std::io;
+use std::net::{SocketAddr, UdpSocket};
+
+// Our libraries
+use common::protocol::DataT;
+use common::telemetry::SensorDecoder;
+use common::DroneMode;
+
+// This module imports
+use codec::{MavErr, MavHeader, MavMessage};
+use translate::{GcsCommand, MAV_COMPONENT_ID, MAV_SYSTEM_ID};
+
+// a datagram carries one or more frames
+const DATAGRAM_CAP: usize = 2048;
+
+/// Shows the drone to a MAVLink ground station over UDP and takes commands
+/// from it, see `translate` for what is translated.
+///
+/// The frames are only exchanged with the ground station at `peer`, datagrams
+/// from anywhere else are dropped: they could command the drone.
+pub struct MavlinkBridge {
+    socket: UdpSocket,
+    peer: SocketAddr,
+    seq: u8,
+
+    // reported by the heartbeat
+    mode: DroneMode,
+}
+
+impl MavlinkBridge {
+    /// Binds `local` and starts talking to `peer`.
+    pub fn open(local: SocketAddr, peer: SocketAddr) -> io::Result<Self> {
+        let socket: UdpSocket = UdpSocket::bind(local)?;
+        socket.set_nonblocking(true)?;
+
+        Ok(Self {
+            socket,
+            peer,
+            seq: 0,
+            mode: DroneMode::Safe,
+        })
+    }
+
+    /// Translates a message of the drone, if MAVLink has an equivalent. Only
+    /// the live sensor samples are shown, not the ones read back from the log.
+    pub fn forward(&mut self, data: &DataT) {
+        match data {
+            DataT::Mode(mode) => self.mode = *mode,
+            DataT::Heartbeat(status) => self.mode = status.mode,
+            DataT::SensorStream(stream) if stream.live => {
+                for sample in SensorDecoder::new(stream) {
+                    match sample {
+                        Ok(sample) => {
+                            self.send(&MavMessage::Attitude(translate::attitude(&sample.to_log())))
+                        }
+                        // reported by the logic
+                        Err(_) => break,
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        if let Some(message) = translate::from_data(data) {
+            self.send(&message);
+        }
+    }
+
+    /// Ground stations expect one about every second, or consider the vehicle
+    /// lost.
+    pub fn heartbeat(&mut self) {
+        self.send(&MavMessage::Heartbeat(translate::heartbeat(self.mode)));
+    }
+
+    /// Reads everything the ground station sent since the last call.
+    /// @return the commands for the drone, in the order they were sent
+    pub fn poll(&mut self) -> Vec<GcsCommand> {
+        let mut commands: Vec<GcsCommand> = Vec::new();
+        let mut datagram: [u8; DATAGRAM_CAP] = [0; DATAGRAM_CAP];
+
+        loop {
+            let (len, from): (usize, SocketAddr) = match self.socket.recv_from(&mut datagram) {
+                Ok(received) => received,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) => {
+                    log::debug!("[ERROR]: MAVLink receive failed {:?}", e);
+                    break;
+                }
+            };
+            if from != self.peer {
+                log::warn!("Dropped a MAVLink datagram from {}", from);
+                continue;
+            }
+
+            let mut pos: usize = 0;
+            while pos < len {
+                match codec::decode(&datagram[pos..len]) {
+                    Ok((_, message, size)) => {
+                        pos += size;
+                        self.handle(&message, &mut commands);
+                    }
+                    Err((MavErr::UnknownMessage(_), size)) => pos += size,
+                    Err((e, size)) => {
+                        log::debug!("[ERROR]: bad MAVLink frame {:?}", e);
+                        pos += size;
+                    }
+                }
+            }
+        }
+
+        commands
+    }
+
+    fn handle(&mut self, message: &MavMessage, commands: &mut Vec<GcsCommand>) {
+        if let MavMessage::CommandLong(long) = message {
+            self.send(&MavMessage::CommandAck(translate::command_ack(long)));
+        }
+
+        if let Some(command) = translate::command(message) {
+            commands.push(command);
+        }
+    }
+
+    fn send(&mut self, message: &MavMessage) {
+        let header: MavHeader = MavHeader {
+            seq: self.seq,
+            system: MAV_SYSTEM_ID,
+            component: MAV_COMPONENT_ID,
+        };
+        self.seq = self.seq.wrapping_add(1);
+
+        let frame = codec::encode(&header, message);
+        match self.socket.send_to(&frame, self.peer) {
+            Ok(_) => {}
+            // nobody listens yet
+            Err(e) if e.kind() == io::ErrorKind::ConnectionRefused => {}
+            Err(e) => log::debug!("[ERROR]: MAVLink send failed {:?}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, Instant};
+
+    use crate::mavlink::codec::{CommandLong, ManualControl, SetMode, MAV_FRAME_CAP};
+    use crate::mavlink::translate::MAV_CMD_DO_SET_MODE;
+    use crate::mavlink::*;
+    use common::protocol::{ControlDT, MotorsDT, SensorStreamDT};
+    use common::telemetry::{SensorEncoder, SensorSample};
+
+    const GCS_SYSTEM_ID: u8 = 255;
+
+    // a ground station and a bridge talking to it
+    fn open() -> (UdpSocket, MavlinkBridge) {
+        let gcs: UdpSocket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        gcs.set_read_timeout(Some(Duration::from_millis(200)))
+            .unwrap();
+
+        let bridge: MavlinkBridge =
+            MavlinkBridge::open("127.0.0.1:0".parse().unwrap(), gcs.local_addr().unwrap()).unwrap();
+        gcs.connect(bridge.socket.local_addr().unwrap()).unwrap();
+
+        (gcs, bridge)
+    }
+
+    // everything the ground station received, until nothing more comes
+    fn receive(gcs: &UdpSocket) -> Vec<(MavHeader, MavMessage)> {
+        let mut received: Vec<(MavHeader, MavMessage)> = Vec::new();
+        let mut datagram: [u8; MAV_FRAME_CAP] = [0; MAV_FRAME_CAP];
+
+        while let Ok(len) = gcs.recv(&mut datagram) {
+            let mut pos: usize = 0;
+            while pos < len {
+                let (header, message, size) = codec::decode(&datagram[pos..len]).unwrap();
+                received.push((header, message));
+                pos += size;
+            }
+        }
+        received
+    }
+
+    fn send(socket: &UdpSocket, to: &MavlinkBridge, messages: &[MavMessage]) {
+        let header: MavHeader = MavHeader {
+            seq: 0,
+            system: GCS_SYSTEM_ID,
+            component: 190,
+        };
+
+        // ground stations may put several frames in a datagram
+        let mut datagram: Vec<u8> = Vec::new();
+        for message in messages {
+            datagram.extend_from_slice(&codec::encode(&header, message));
+        }
+        socket
+            .send_to(&datagram, to.socket.local_addr().unwrap())
+            .unwrap();
+    }
+
+    // the commands of the datagrams sent to the bridge, they may take a while
+    fn poll(bridge: &mut MavlinkBridge, expected: usize) -> Vec<GcsCommand> {
+        let start: Instant = Instant::now();
+        let mut commands: Vec<GcsCommand> = Vec::new();
+
+        while commands.len() < expected && start.elapsed() < Duration::from_millis(200) {
+            commands.extend(bridge.poll());
+        }
+        commands.extend(bridge.poll());
+        commands
+    }
+
+    fn stream(live: bool) -> SensorStreamDT {
+        let mut encoder: SensorEncoder = SensorEncoder::new(live);
+        for time in [1_000, 2_000] {
+            assert!(encoder.push(&SensorSample {
+                time,
+                roll: 100,
+                ..Default::default()
+            }));
+        }
+        encoder.take().unwrap()
+    }
+
+    #[test]
+    fn test_telemetry_to_ground_station() {
+        let (gcs, mut bridge) = open();
+
+        bridge.forward(&DataT::Mode(DroneMode::Manual));
+        bridge.forward(&DataT::MotorsState(MotorsDT {
+            time: 20_000,
+            ae1: 200,
+            ae2: 210,
+            ae3: 220,
+            ae4: 230,
+        }));
+        bridge.forward(&DataT::KeepAlive);
+
+        let received: Vec<(MavHeader, MavMessage)> = receive(&gcs);
+        assert_eq!(received.len(), 2);
+
+        let (header, heartbeat) = &received[0];
+        assert_eq!(header.system, MAV_SYSTEM_ID);
+        assert_eq!(
+            *heartbeat,
+            MavMessage::Heartbeat(translate::heartbeat(DroneMode::Manual))
+        );
+
+        let (header, servo) = &received[1];
+        assert_eq!(header.seq, 1);
+        match servo {
+            MavMessage::ServoOutputRaw(servo) => assert_eq!(servo.servo_raw[0], 1200),
+            other => panic!("expected SERVO_OUTPUT_RAW, got {:?}", other),
+        }
+
+        // the mode is remembered for the periodic heartbeat
+        bridge.heartbeat();
+        assert_eq!(
+            receive(&gcs)[0].1,
+            MavMessage::Heartbeat(translate::heartbeat(DroneMode::Manual))
+        );
+    }
+
+    #[test]
+    fn test_only_live_samples_are_forwarded() {
+        let (gcs, mut bridge) = open();
+
+        // read back from the log
+        bridge.forward(&DataT::SensorStream(stream(false)));
+        bridge.forward(&DataT::SensorLog(SensorSample::default().to_log()));
+        assert!(receive(&gcs).is_empty());
+
+        bridge.forward(&DataT::SensorStream(stream(true)));
+        let attitudes: Vec<MavMessage> = receive(&gcs).into_iter().map(|(_, m)| m).collect();
+        assert_eq!(attitudes.len(), 2);
+        match &attitudes[1] {
+            MavMessage::Attitude(attitude) => {
+                assert_eq!(attitude.time_boot_ms, 2);
+                assert!(attitude.roll > 0.0);
+            }
+            other => panic!("expected ATTITUDE, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_commands_from_ground_station() {
+        let (gcs, mut bridge) = open();
+
+        let set_mode: MavMessage = MavMessage::SetMode(SetMode {
+            custom_mode: translate::custom_mode(DroneMode::FullControl),
+            target_system: MAV_SYSTEM_ID,
+            base_mode: 0,
+        });
+        let manual: MavMessage = MavMessage::ManualControl(ManualControl {
+            x: 0,
+            y: 0,
+            z: 500,
+            r: 0,
+            buttons: 0,
+            target: MAV_SYSTEM_ID,
+        });
+        send(&gcs, &bridge, &[set_mode, manual]);
+
+        assert_eq!(
+            poll(&mut bridge, 2),
+            [
+                GcsCommand::Mode(DroneMode::FullControl),
+                GcsCommand::Control(ControlDT {
+                    lift: 1023,
+                    roll: 1024,
+                    pitch: 1024,
+                    yaw: 1024,
+                }),
+            ]
+        );
+
+        // a COMMAND_LONG is acknowledged
+        let mut params: [f32; 7] = [0.0; 7];
+        params[1] = translate::custom_mode(DroneMode::Panic) as f32;
+        send(
+            &gcs,
+            &bridge,
+            &[MavMessage::CommandLong(CommandLong {
+                params,
+                command: MAV_CMD_DO_SET_MODE,
+                target_system: MAV_SYSTEM_ID,
+                ..Default::default()
+            })],
+        );
+        assert_eq!(poll(&mut bridge, 1), [GcsCommand::Mode(DroneMode::Panic)]);
+        assert!(matches!(
+            receive(&gcs)[..],
+            [(_, MavMessage::CommandAck(ack))] if ack.command == MAV_CMD_DO_SET_MODE
+        ));
+    }
+
+    #[test]
+    fn test_strangers_are_ignored() {
+        let (gcs, mut bridge) = open();
+        let stranger: UdpSocket = UdpSocket::bind("127.0.0.1:0").unwrap();
+
+        send(
+            &stranger,
+            &bridge,
+            &[MavMessage::SetMode(SetMode {
+                custom_mode: translate::custom_mode(DroneMode::Manual),
+                target_system: MAV_SYSTEM_ID,
+                base_mode: 0,
+            })],
+        );
+        assert!(poll(&mut bridge, 1).is_empty());
+
+        // the frames still go to the ground station
+        bridge.heartbeat();
+        assert_eq!(receive(&gcs).len(), 1);
+    }
+}
@@ -0,0 +1,352 @@
+//! mapping of the drone messages to MAVLink and of the commands of a ground
+//! station to drone messages
+
+use fixed::types::I16F16;
+
+use common::protocol::{ControlDT, DataT, HealthDT, MotorsDT, SensorLogDT};
+use common::DroneMode;
+
+use crate::mavlink::codec::{
+    Attitude, CommandAck, CommandLong, Heartbeat, MavMessage, ServoOutputRaw, SysStatus,
+};
+
+/// System and component id of the drone on the MAVLink side.
+pub const MAV_SYSTEM_ID: u8 = 1;
+pub const MAV_COMPONENT_ID: u8 = 1; // MAV_COMP_ID_AUTOPILOT1
+
+const MAV_TYPE_QUADROTOR: u8 = 2;
+const MAV_AUTOPILOT_GENERIC: u8 = 0;
+const MAVLINK_VERSION: u8 = 3;
+
+// MAV_MODE_FLAG
+const MAV_MODE_FLAG_CUSTOM_MODE_ENABLED: u8 = 1;
+const MAV_MODE_FLAG_STABILIZE_ENABLED: u8 = 16;
+const MAV_MODE_FLAG_MANUAL_INPUT_ENABLED: u8 = 64;
+const MAV_MODE_FLAG_SAFETY_ARMED: u8 = 128;
+
+// MAV_STATE
+const MAV_STATE_CALIBRATING: u8 = 2;
+const MAV_STATE_STANDBY: u8 = 3;
+const MAV_STATE_ACTIVE: u8 = 4;
+const MAV_STATE_EMERGENCY: u8 = 6;
+
+// MAV_CMD and MAV_RESULT
+pub const MAV_CMD_DO_SET_MODE: u16 = 176;
+pub const MAV_RESULT_ACCEPTED: u8 = 0;
+pub const MAV_RESULT_DENIED: u8 = 2;
+pub const MAV_RESULT_UNSUPPORTED: u8 = 3;
+
+// the gyroscope measures +-2000 deg/s over the range of an i16
+const GYRO_RAD_PER_LSB: f32 = 2000.0 / 32768.0 * core::f32::consts::PI / 180.0;
+
+// the motors are shown as the pulse width of an ESC, starting at 1000 us
+const SERVO_ZERO: u16 = 1000;
+
+// range of the commands of `ControlDT`, see `motor_control::motor_mapping`
+const CONTROL_MAX: i32 = 2047;
+const CONTROL_NEUTRAL: i32 = 1024;
+// range of the axes of MANUAL_CONTROL
+const MANUAL_MAX: i32 = 1000;
+
+/// The modes in the order of their MAVLink custom mode number.
+const CUSTOM_MODES: [DroneMode; 7] = [
+    DroneMode::Safe,
+    DroneMode::Manual,
+    DroneMode::Panic,
+    DroneMode::Calibrate,
+    DroneMode::YawControl,
+    DroneMode::FullControl,
+    DroneMode::RawMode,
+];
+
+/// What a ground station asks the drone to do.
+#[derive(Debug, PartialEq, Clone)]
+pub enum GcsCommand {
+    Mode(DroneMode),
+    Control(ControlDT),
+}
+
+pub fn custom_mode(mode: DroneMode) -> u32 {
+    CUSTOM_MODES.iter().position(|m| *m == mode).unwrap_or(0) as u32
+}
+
+pub fn mode_from_custom(custom_mode: u32) -> Option<DroneMode> {
+    CUSTOM_MODES.get(custom_mode as usize).copied()
+}
+
+/// The MAVLink message carrying the same information as `data`, if any. The
+/// sensor samples arrive packed in `SensorStream` messages, the live ones are
+/// translated one by one with `attitude`.
+pub fn from_data(data: &DataT) -> Option<MavMessage> {
+    match data {
+        DataT::Mode(mode) => Some(MavMessage::Heartbeat(heartbeat(*mode))),
+        DataT::Heartbeat(status) => Some(MavMessage::Heartbeat(heartbeat(status.mode))),
+        DataT::HealthData(health) => Some(MavMessage::SysStatus(sys_status(health))),
+        DataT::MotorsState(motors) => Some(MavMessage::ServoOutputRaw(servo_output(motors))),
+        _ => None,
+    }
+}
+
+/// The heartbeat of the drone when it is in `mode`. The custom mode is the
+/// position of the mode in `CUSTOM_MODES`.
+pub fn heartbeat(mode: DroneMode) -> Heartbeat {
+    let (base_mode, system_status): (u8, u8) = match mode {
+        DroneMode::Safe => (0, MAV_STATE_STANDBY),
+        DroneMode::Panic => (MAV_MODE_FLAG_SAFETY_ARMED, MAV_STATE_EMERGENCY),
+        DroneMode::Calibrate => (0, MAV_STATE_CALIBRATING),
+        DroneMode::Manual | DroneMode::RawMode => (
+            MAV_MODE_FLAG_SAFETY_ARMED | MAV_MODE_FLAG_MANUAL_INPUT_ENABLED,
+            MAV_STATE_ACTIVE,
+        ),
+        DroneMode::YawControl | DroneMode::FullControl => (
+            MAV_MODE_FLAG_SAFETY_ARMED
+                | MAV_MODE_FLAG_MANUAL_INPUT_ENABLED
+                | MAV_MODE_FLAG_STABILIZE_ENABLED,
+            MAV_STATE_ACTIVE,
+        ),
+    };
+
+    Heartbeat {
+        custom_mode: custom_mode(mode),
+        kind: MAV_TYPE_QUADROTOR,
+        autopilot: MAV_AUTOPILOT_GENERIC,
+        base_mode: base_mode | MAV_MODE_FLAG_CUSTOM_MODE_ENABLED,
+        system_status,
+        mavlink_version: MAVLINK_VERSION,
+    }
+}
+
+/// Battery voltage and load of the control loop, the current and the charge
+/// left are not measured.
+pub fn sys_status(health: &HealthDT) -> SysStatus {
+    SysStatus {
+        load: health.cpu as u16 * 10,
+        voltage_battery: health.bat.saturating_mul(10),
+        current_battery: -1,
+        battery_remaining: -1,
+        ..Default::default()
+    }
+}
+
+pub fn attitude(sample: &SensorLogDT) -> Attitude {
+    let angle = |angle: I16F16| -> f32 { angle.to_num::<f32>() };
+    let rate = |raw: i16| -> f32 { raw as f32 * GYRO_RAD_PER_LSB };
+
+    Attitude {
+        time_boot_ms: sample.time / 1000,
+        roll: angle(sample.roll),
+        pitch: angle(sample.pitch),
+        yaw: angle(sample.yaw),
+        rollspeed: rate(sample.gyro_x),
+        pitchspeed: rate(sample.gyro_y),
+        yawspeed: rate(sample.gyro_z),
+    }
+}
+
+pub fn servo_output(motors: &MotorsDT) -> ServoOutputRaw {
+    let servo = |motor: u16| -> u16 { SERVO_ZERO.saturating_add(motor) };
+
+    ServoOutputRaw {
+        time_usec: motors.time,
+        servo_raw: [
+            servo(motors.ae1),
+            servo(motors.ae2),
+            servo(motors.ae3),
+            servo(motors.ae4),
+            0,
+            0,
+            0,
+            0,
+        ],
+        port: 0,
+    }
+}
+
+/// The command of a ground station in `message`, if it is one for the drone.
+pub fn command(message: &MavMessage) -> Option<GcsCommand> {
+    match message {
+        MavMessage::SetMode(set) if for_us(set.target_system) => {
+            mode_from_custom(set.custom_mode).map(GcsCommand::Mode)
+        }
+        MavMessage::CommandLong(long)
+            if for_us(long.target_system) && long.command == MAV_CMD_DO_SET_MODE =>
+        {
+            // param2 is the custom mode
+            mode_from_custom(long.params[1] as u32).map(GcsCommand::Mode)
+        }
+        MavMessage::ManualControl(manual) if for_us(manual.target) => {
+            let axis = |value: i16| -> u16 {
+                let value: i32 = (value as i32).clamp(-MANUAL_MAX, MANUAL_MAX);
+                (CONTROL_NEUTRAL + value * (CONTROL_MAX - CONTROL_NEUTRAL) / MANUAL_MAX) as u16
+            };
+
+            Some(GcsCommand::Control(ControlDT {
+                lift: ((manual.z as i32).clamp(0, MANUAL_MAX) * CONTROL_MAX / MANUAL_MAX) as u16,
+                roll: axis(manual.y),
+                pitch: axis(manual.x),
+                yaw: axis(manual.r),
+            }))
+        }
+        _ => None,
+    }
+}
+
+/// The answer to `long`, which the ground station waits for.
+pub fn command_ack(long: &CommandLong) -> CommandAck {
+    let result: u8 = match long.command {
+        MAV_CMD_DO_SET_MODE => match command(&MavMessage::CommandLong(*long)) {
+            Some(_) => MAV_RESULT_ACCEPTED,
+            None => MAV_RESULT_DENIED,
+        },
+        _ => MAV_RESULT_UNSUPPORTED,
+    };
+
+    CommandAck {
+        command: long.command,
+        result,
+    }
+}
+
+// 0 is a broadcast
+fn for_us(target_system: u8) -> bool {
+    target_system == 0 || target_system == MAV_SYSTEM_ID
+}
+
+#[cfg(test)]
+mod test {
+    use crate::mavlink::codec::{ManualControl, SetMode};
+    use crate::mavlink::translate::*;
+    use common::protocol::{BatteryT, HeartbeatDT};
+
+    #[test]
+    fn test_mode_numbers() {
+        for mode in CUSTOM_MODES {
+            assert_eq!(mode_from_custom(custom_mode(mode)), Some(mode));
+        }
+        assert_eq!(mode_from_custom(CUSTOM_MODES.len() as u32), None);
+
+        let panic: Heartbeat = heartbeat(DroneMode::Panic);
+        assert_eq!(panic.custom_mode, 2);
+        assert_eq!(panic.system_status, MAV_STATE_EMERGENCY);
+    }
+
+    #[test]
+    fn test_from_data() {
+        let health: DataT = DataT::HealthData(HealthDT {
+            time: 0,
+            bat: 1110,
+            cpu: 42,
+            pres: 0,
+            loop_avg: 0,
+            loop_max: 0,
+            jitter: 0,
+            overruns: 0,
+        });
+        let Some(MavMessage::SysStatus(status)) = from_data(&health) else {
+            panic!("no SYS_STATUS");
+        };
+        assert_eq!(status.voltage_battery, 11_100);
+        assert_eq!(status.load, 420);
+
+        let motors: DataT = DataT::MotorsState(MotorsDT {
+            time: 5,
+            ae1: 0,
+            ae2: 300,
+            ae3: 400,
+            ae4: 800,
+        });
+        let Some(MavMessage::ServoOutputRaw(servo)) = from_data(&motors) else {
+            panic!("no SERVO_OUTPUT_RAW");
+        };
+        assert_eq!(servo.servo_raw[..4], [1000, 1300, 1400, 1800]);
+
+        let status: DataT = DataT::Heartbeat(HeartbeatDT {
+            mode: DroneMode::YawControl,
+            armed: true,
+            calibrated: true,
+            logging: false,
+            log_report: false,
+            battery: BatteryT::Ok,
+            faults: 0,
+        });
+        assert_eq!(
+            from_data(&status),
+            Some(MavMessage::Heartbeat(heartbeat(DroneMode::YawControl)))
+        );
+
+        assert_eq!(from_data(&DataT::KeepAlive), None);
+    }
+
+    #[test]
+    fn test_manual_control() {
+        let manual = |x: i16, y: i16, z: i16, r: i16, target: u8| {
+            command(&MavMessage::ManualControl(ManualControl {
+                x,
+                y,
+                z,
+                r,
+                buttons: 0,
+                target,
+            }))
+        };
+
+        assert_eq!(
+            manual(0, 0, 0, 0, MAV_SYSTEM_ID),
+            Some(GcsCommand::Control(ControlDT {
+                lift: 0,
+                roll: 1024,
+                pitch: 1024,
+                yaw: 1024,
+            }))
+        );
+        assert_eq!(
+            manual(1000, -1000, 1000, 2000, 0),
+            Some(GcsCommand::Control(ControlDT {
+                lift: 2047,
+                roll: 1,
+                pitch: 2047,
+                yaw: 2047,
+            }))
+        );
+        // for another vehicle
+        assert_eq!(manual(0, 0, 0, 0, MAV_SYSTEM_ID + 1), None);
+    }
+
+    #[test]
+    fn test_mode_commands() {
+        let set: MavMessage = MavMessage::SetMode(SetMode {
+            custom_mode: custom_mode(DroneMode::Manual),
+            target_system: MAV_SYSTEM_ID,
+            base_mode: 0,
+        });
+        assert_eq!(command(&set), Some(GcsCommand::Mode(DroneMode::Manual)));
+
+        let mut long: CommandLong = CommandLong {
+            params: [
+                1.0,
+                custom_mode(DroneMode::YawControl) as f32,
+                0.0,
+                0.0,
+                0.0,
+                0.0,
+                0.0,
+            ],
+            command: MAV_CMD_DO_SET_MODE,
+            target_system: MAV_SYSTEM_ID,
+            target_component: MAV_COMPONENT_ID,
+            confirmation: 0,
+        };
+        assert_eq!(
+            command(&MavMessage::CommandLong(long)),
+            Some(GcsCommand::Mode(DroneMode::YawControl))
+        );
+        assert_eq!(command_ack(&long).result, MAV_RESULT_ACCEPTED);
+
+        long.params[1] = 100.0;
+        assert_eq!(command_ack(&long).result, MAV_RESULT_DENIED);
+
+        long.command = 400; // MAV_CMD_COMPONENT_ARM_DISARM
+        assert_eq!(command(&MavMessage::CommandLong(long)), None);
+        assert_eq!(command_ack(&long).result, MAV_RESULT_UNSUPPORTED);
+    }
+}
@@ -1,60 +1,68 @@
-use std::cell::OnceCell;
-use std::sync::Mutex;
+use std::sync::Arc;
 use std::time::Duration;
 
+use common::io::Transport;
 use serial2::SerialPort;
 
-pub static SERIAL: Mutex<OnceCell<SerialPort>> = Mutex::new(OnceCell::new());
+/// Serial connection to the drone, owned by the `ComT` that uses it. A clone
+/// is another handle to the same port, e.g. to read it from another thread.
+#[derive(Clone)]
+pub struct SerialTransport {
+    serial: Arc<SerialPort>,
+}
 
-pub(crate) fn init_global_serial(
-    name: impl AsRef<std::path::Path>,
-    settings: impl serial2::IntoSettings,
-) {
-    match SerialPort::open(name, settings) {
-        Ok(mut serial) => {
-            let lock = SERIAL.lock().unwrap();
+impl SerialTransport {
+    /// Opens the serial port, exits the runner if it can not be opened.
+    pub(crate) fn open(
+        name: impl AsRef<std::path::Path>,
+        settings: impl serial2::IntoSettings,
+    ) -> Self {
+        match SerialPort::open(name, settings) {
+            Ok(mut serial) => {
+                if let Err(e) = serial.set_write_timeout(Duration::from_millis(15)) {
+                    log::error!(
+                        "[ERROR]: Failed to initialize the serial when setting write timeout: {}\n",
+                        e
+                    );
+                }
 
-            if let Err(e) = serial.set_write_timeout(Duration::from_millis(15)) {
-                log::error!(
-                    "[ERROR]: Failed to initialize the serial when setting write timeout: {}\n",
-                    e
-                );
-            }
+                if let Err(e) = serial.set_read_timeout(Duration::from_millis(10)) {
+                    log::error!(
+                        "[ERROR]: Failed to initialize the serial when setting read timeout: {}\n",
+                        e
+                    );
+                }
 
-            if let Err(e) = serial.set_read_timeout(Duration::from_millis(10)) {
-                log::error!(
-                    "[ERROR]: Failed to initialize the serial when setting read timeout: {}\n",
-                    e
-                );
-            }
+                log::info!("Serial initialized\n");
 
-            match lock.set(serial) {
-                Ok(_) => {
-                    log::info!("Serial initialized\n");
-                }
-                Err(_) => {
-                    log::error!("[ERROR]: Tried to reinitialize the serial\n");
+                Self {
+                    serial: Arc::new(serial),
                 }
             }
-        }
-        Err(_) => {
-            log::error!("[ERROR]: Could not open the serial\n");
-            std::process::exit(-1);
+            Err(_) => {
+                log::error!("[ERROR]: Could not open the serial\n");
+                std::process::exit(-1);
+            }
         }
     }
 }
 
-pub fn send_bytes(bytes: &[u8]) -> bool {
-    let lock = SERIAL.lock().unwrap();
-
-    match lock.get() {
-        Option::None => {
-            log::error!("[ERROR]: Usage of SERIAL before initialization.\n");
-            assert!(false);
-            return false;
+impl Transport for SerialTransport {
+    // waits up to the read timeout of the port (10 ms) for bytes, which lets
+    // the reader thread of `AsyncComT` sleep instead of spinning
+    fn read(&mut self, bytes: &mut [u8]) -> usize {
+        match self.serial.read(bytes) {
+            Ok(read) => {
+                return read;
+            }
+            Err(_) => {
+                return 0;
+            }
         }
+    }
 
-        Option::Some(serial) => match serial.write(bytes) {
+    fn write(&mut self, bytes: &[u8]) -> bool {
+        match self.serial.write(bytes) {
             Ok(written) => {
                 if written == bytes.len() {
                     return true;
@@ -71,27 +79,12 @@ pub fn send_bytes(bytes: &[u8]) -> bool {
                 log::error!("[ERROR]: failed to send on the serial\n");
                 return false;
             }
-        },
-    };
-}
-
-pub fn receive_bytes(bytes: &mut [u8]) -> usize {
-    let lock = SERIAL.lock().unwrap();
-
-    match lock.get() {
-        Option::None => {
-            log::error!("[ERROR]: Usage of SERIAL before initialization.\n");
-            assert!(false);
-            return 0;
         }
+    }
 
-        Option::Some(serial) => match serial.read(bytes) {
-            Ok(read) => {
-                return read;
-            }
-            Err(_) => {
-                return 0;
-            }
-        },
-    };
+    fn flush(&mut self) {
+        if let Err(e) = self.serial.flush() {
+            log::error!("[ERROR]: failed to flush the serial: {}\n", e);
+        }
+    }
 }
@@ -2,3 +2,31 @@ pub const TICK_RATE: u64 = 25;
 
 pub const RUNNER_PERIOD_COMMAND: u32 = 4;
 pub const RUNNER_PERIOD_KEEP_ALIVE: u32 = 20;
+pub const RUNNER_PERIOD_HANDSHAKE: u32 = 40; // retried until the drone answers
+pub const RUNNER_PERIOD_LINK_STATS: u32 = 40; // refresh of the link health in the GUI
+pub const RUNNER_PERIOD_TIME_SYNC: u32 = 40; // clock sync exchanges with the drone
+
+// retransmission of the critical messages (mode changes, gain updates), in ticks
+pub const RUNNER_RELIABLE_RETRANSMIT_TICKS: u32 = 8;
+pub const RUNNER_RELIABLE_MAX_RETRIES: u8 = 5;
+
+// requests (mode changes, gain updates, logging) the drone did not answer
+// after this many ticks, longer than their retransmissions, are given up on
+pub const RUNNER_REQUEST_TIMEOUT_TICKS: u32 = 60;
+pub const RUNNER_REQUEST_SLOTS: usize = 8;
+
+// large messages from the drone that are not complete after this many ticks are dropped
+pub const RUNNER_FRAGMENT_TIMEOUT_TICKS: u32 = 40;
+pub const RUNNER_FRAGMENT_SLOTS: usize = 2;
+
+// MAVLink bridge, see `mavlink::MavlinkBridge`
+pub const MAVLINK_DEFAULT_PEER: &str = "127.0.0.1:14550"; // where ground stations listen
+pub const MAVLINK_LOCAL: &str = "127.0.0.1:14551"; // on the network only for a remote peer
+pub const RUNNER_PERIOD_MAVLINK_HEARTBEAT: u32 = 40;
+// manual control from the ground station is dropped for the local inputs after this many ticks
+pub const RUNNER_MAVLINK_CONTROL_TIMEOUT_TICKS: u32 = 20;
+
+// bytes from the drone waiting to be decoded, the oldest are dropped beyond it
+pub const RUNNER_INBOX_CAP: usize = 4096;
+// attempts of an async send while the serial is busy
+pub const RUNNER_SEND_RETRIES: u32 = 3;